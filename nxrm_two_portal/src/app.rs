@@ -0,0 +1,752 @@
+use std::time::Duration;
+
+use axum::{
+    middleware,
+    routing::{delete, get, post, put},
+    Router,
+};
+use tower_http::timeout::{RequestBodyTimeoutLayer, TimeoutLayer};
+
+use crate::auth::auth;
+use crate::compression::compress_responses;
+use crate::endpoints::{
+    fallback::fallback,
+    manual::manual_upload_default_repository,
+    pending::{pending_uploads_endpoint, retry_pending_upload_endpoint},
+    proxy_config::proxy_config_endpoint,
+    staging::{
+        staging_bulk_close, staging_bulk_promote, staging_deploy_by_repository_id,
+        staging_deploy_by_repository_id_delete, staging_deploy_by_repository_id_get,
+        staging_deploy_by_repository_id_head, staging_deploy_maven2, staging_deploy_maven2_get,
+        staging_deploy_maven2_head, staging_profile_evaluate_endpoint,
+        staging_profile_repositories, staging_profiles_endpoint, staging_profiles_finish_endpoint,
+        staging_profiles_list_endpoint, staging_profiles_start_endpoint, staging_repository,
+        staging_repository_activity,
+    },
+    status::status_endpoint,
+};
+use crate::errors::negotiate_errors;
+use crate::limit::limit_uploads;
+use crate::state::AppState;
+use crate::user_agent::check_user_agent;
+
+/// Assemble the NXRM2 routes and their middleware around the provided state
+///
+/// The routes are nested under the configured `base_path`, if any.
+pub fn build_app(app_state: AppState) -> Router {
+    // uploads of large bundles and the publishes that send them to Central may legitimately take
+    // longer than any other request, so they are only cut off when their body stalls, shedding slow
+    // clients without limiting the size of uploads or interrupting a publish part way through
+    let request_timeout = TimeoutLayer::new(Duration::from_secs(
+        app_state.app_config.request_timeout_secs,
+    ));
+    let body_idle_timeout = RequestBodyTimeoutLayer::new(Duration::from_secs(
+        app_state.app_config.body_idle_timeout_secs,
+    ));
+
+    let staging_endpoints = Router::new()
+        .route("/profile_evaluate", get(staging_profile_evaluate_endpoint))
+        .route("/profiles", get(staging_profiles_list_endpoint))
+        .route("/profiles/:profile_id", get(staging_profiles_endpoint))
+        .route(
+            "/profiles/:profile_id/start",
+            post(staging_profiles_start_endpoint),
+        )
+        .route(
+            "/deployByRepositoryId/:staging_repository_id",
+            delete(staging_deploy_by_repository_id_delete),
+        )
+        .route("/repository/:repository_id", get(staging_repository))
+        .route(
+            "/repository/:repository_id/activity",
+            get(staging_repository_activity),
+        )
+        .route(
+            "/profile_repositories/:profile_id",
+            get(staging_profile_repositories),
+        )
+        .route("/bulk/promote", post(staging_bulk_promote))
+        .route_layer(request_timeout)
+        .route(
+            "/profiles/:profile_id/finish",
+            post(staging_profiles_finish_endpoint).route_layer(body_idle_timeout.clone()),
+        )
+        .route(
+            "/bulk/close",
+            post(staging_bulk_close).route_layer(body_idle_timeout.clone()),
+        )
+        .route(
+            "/deployByRepositoryId/:staging_repository_id/*file_path",
+            put(staging_deploy_by_repository_id)
+                .route_layer(middleware::from_fn_with_state(
+                    app_state.clone(),
+                    limit_uploads,
+                ))
+                .route_layer(body_idle_timeout.clone())
+                .get(staging_deploy_by_repository_id_get)
+                .head(staging_deploy_by_repository_id_head),
+        )
+        // required for Gradle maven-publish plugin
+        .route(
+            "/deploy/maven2/*file_path",
+            put(staging_deploy_maven2)
+                .route_layer(middleware::from_fn_with_state(
+                    app_state.clone(),
+                    limit_uploads,
+                ))
+                .route_layer(body_idle_timeout.clone())
+                .get(staging_deploy_maven2_get)
+                .head(staging_deploy_maven2_head),
+        )
+        .route_layer(middleware::from_fn(auth));
+
+    let manual_endpoints = Router::new()
+        .route(
+            "/upload",
+            post(manual_upload_default_repository).route_layer(body_idle_timeout.clone()),
+        )
+        .route_layer(middleware::from_fn(auth));
+
+    let proxy_endpoints = Router::new()
+        .route("/proxy_config", get(proxy_config_endpoint))
+        .route("/pending_uploads", get(pending_uploads_endpoint))
+        .route_layer(request_timeout)
+        .route(
+            "/pending_uploads/:repository_id/retry",
+            post(retry_pending_upload_endpoint).route_layer(body_idle_timeout),
+        )
+        .route_layer(middleware::from_fn(auth));
+
+    let app = Router::new()
+        .route(
+            "/service/local/status",
+            get(status_endpoint).route_layer(request_timeout),
+        )
+        .nest("/service/local", proxy_endpoints)
+        .nest("/service/local/staging", staging_endpoints)
+        .nest("/manual", manual_endpoints)
+        .fallback(fallback);
+    let app = match app_state.app_config.base_path() {
+        Some(base_path) => Router::new().nest(&base_path, app).fallback(fallback),
+        None => app,
+    };
+
+    app.layer(middleware::from_fn_with_state(
+        app_state.clone(),
+        check_user_agent,
+    ))
+    .with_state(app_state)
+    .layer(middleware::from_fn(negotiate_errors))
+    .layer(compress_responses())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+    use std::sync::Arc;
+
+    use axum::{
+        body::Body,
+        extract::connect_info::MockConnectInfo,
+        http::{
+            header::{ACCEPT, AUTHORIZATION, CONTENT_TYPE, HOST, USER_AGENT},
+            Method, Request, StatusCode,
+        },
+    };
+    use base64::prelude::{Engine, BASE64_STANDARD};
+    use portal_api::PortalApiClient;
+    use repository::local_repository::LocalRepository;
+    use repository::traits::RepositoryState;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+    use tower::ServiceExt;
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::*;
+    use crate::config::AppConfig;
+
+    /// Send a request as a client authenticated with a user token would
+    async fn send(
+        app: &Router,
+        method: Method,
+        uri: &str,
+        body: impl Into<Body>,
+    ) -> eyre::Result<(StatusCode, Vec<u8>)> {
+        let user_token = BASE64_STANDARD.encode("test_user:test_password");
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(HOST, "localhost")
+            .header(USER_AGENT, "gradle-nexus-publish-plugin")
+            .header(AUTHORIZATION, format!("Basic {user_token}"))
+            .header(ACCEPT, "application/json")
+            .header(CONTENT_TYPE, "application/json")
+            .body(body.into())?;
+
+        let response = app.clone().oneshot(request).await?;
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await?;
+
+        Ok((status, body.to_vec()))
+    }
+
+    #[tokio::test]
+    async fn status() -> eyre::Result<()> {
+        let app_state = AppState::new(
+            LocalRepository::new()?,
+            PortalApiClient::client("http://localhost")?,
+            AppConfig::with_overrides(&[])?,
+        );
+        let app = build_app(app_state);
+
+        let response = app
+            .oneshot(
+                Request::get("/service/local/status")
+                    .header(HOST, "localhost:2727")
+                    .header(USER_AGENT, "test")
+                    .body(Body::empty())?,
+            )
+            .await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers().get(CONTENT_TYPE),
+            Some(&"application/xml".parse()?)
+        );
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await?;
+        let body = String::from_utf8(body.to_vec())?;
+        assert!(body.starts_with("<?xml version=\"1.0\" encoding=\"utf-8\"?>"));
+        assert!(body.contains("<appName>Nexus Repository Manager</appName>"));
+        assert!(body.contains("<version>2.15.1-02</version>"));
+        assert!(body.contains("<baseUrl>localhost:2727</baseUrl>"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn status_json() -> eyre::Result<()> {
+        let app_state = AppState::new(
+            LocalRepository::new()?,
+            PortalApiClient::client("http://localhost")?,
+            AppConfig::with_overrides(&[])?,
+        );
+        let app =
+            build_app(app_state).layer(MockConnectInfo(SocketAddr::from(([127, 0, 0, 1], 2727))));
+
+        let (status, body) =
+            send(&app, Method::GET, "/service/local/status", Body::empty()).await?;
+        assert_eq!(status, StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(&body)?;
+        assert_eq!(body["data"]["appName"], "Nexus Repository Manager");
+        assert_eq!(body["data"]["apiVersion"], "2.15.1-02");
+        assert_eq!(body["data"]["editionShort"], "PRO");
+        assert_eq!(body["data"]["baseUrl"], "localhost");
+        assert_eq!(
+            body["data"]["userLicenseURL"],
+            "http://links.sonatype.com/products/nexus/pro/eula"
+        );
+        assert!(body["data"].get("app_name").is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn cut_off_stalled_requests() -> eyre::Result<()> {
+        let app_state = AppState::new(
+            LocalRepository::new()?,
+            PortalApiClient::client("http://localhost")?,
+            AppConfig::with_overrides(&[
+                ("request_timeout_secs", "30"),
+                ("body_idle_timeout_secs", "5"),
+            ])?,
+        );
+        let app =
+            build_app(app_state).layer(MockConnectInfo(SocketAddr::from(([127, 0, 0, 1], 2727))));
+        let stalled_body =
+            || Body::from_stream(futures::stream::pending::<Result<Vec<u8>, std::io::Error>>());
+
+        let started = tokio::time::Instant::now();
+        let (status, _) = send(
+            &app,
+            Method::POST,
+            "/service/local/staging/bulk/promote",
+            stalled_body(),
+        )
+        .await?;
+        assert_eq!(status, StatusCode::REQUEST_TIMEOUT);
+        assert_eq!(started.elapsed().as_secs(), 30);
+
+        // uploads and publishes are only cut off once their body stalls
+        for (method, uri) in [
+            (
+                Method::PUT,
+                "/service/local/staging/deploy/maven2/com/example/example/1.0/example-1.0.jar",
+            ),
+            (Method::POST, "/service/local/staging/bulk/close"),
+            (
+                Method::POST,
+                "/service/local/staging/profiles/comexample/finish",
+            ),
+        ] {
+            let started = tokio::time::Instant::now();
+            let (status, body) = send(&app, method, uri, stalled_body()).await?;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{uri}");
+            assert!(
+                String::from_utf8(body)?.contains("designated timeout"),
+                "{uri}"
+            );
+            assert_eq!(started.elapsed().as_secs(), 5, "{uri}");
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn read_without_opening_repositories() -> eyre::Result<()> {
+        let app_state = AppState::new(
+            LocalRepository::new()?,
+            PortalApiClient::client("http://localhost")?,
+            AppConfig::with_overrides(&[])?,
+        );
+        let app = build_app(app_state.clone())
+            .layer(MockConnectInfo(SocketAddr::from(([127, 0, 0, 1], 2727))));
+        let uri = "/service/local/staging/deploy/maven2/com/example/example/1.0/example-1.0.jar";
+        let ip_addr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
+
+        for method in [Method::GET, Method::HEAD] {
+            let (status, _) = send(&app, method, uri, Body::empty()).await?;
+            assert_eq!(status, StatusCode::NOT_FOUND);
+        }
+        assert!(app_state
+            .repository
+            .find_no_profile_repository("test_user", &ip_addr, None)
+            .await
+            .is_none());
+
+        let (status, _) = send(&app, Method::PUT, uri, "test_file_content").await?;
+        assert_eq!(status, StatusCode::CREATED);
+        let repository_key = app_state
+            .repository
+            .find_no_profile_repository("test_user", &ip_addr, None)
+            .await
+            .ok_or_else(|| eyre::eyre!("No repository for the upload"))?;
+        for method in [Method::GET, Method::HEAD] {
+            let (status, _) = send(&app, method, uri, Body::empty()).await?;
+            assert_eq!(status, StatusCode::OK);
+        }
+
+        // a closed repository is neither read from nor reopened
+        app_state.repository.finish(&repository_key).await?;
+        for method in [Method::GET, Method::HEAD] {
+            let (status, _) = send(&app, method, uri, Body::empty()).await?;
+            assert_eq!(status, StatusCode::NOT_FOUND);
+        }
+        assert!(matches!(
+            app_state.repository.get_state(&repository_key).await?,
+            RepositoryState::Closed
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn read_without_starting_upload_sessions() -> eyre::Result<()> {
+        let app_state = AppState::new(
+            LocalRepository::new()?,
+            PortalApiClient::client("http://localhost")?,
+            AppConfig::with_overrides(&[])?,
+        );
+        let app = build_app(app_state.clone())
+            .layer(MockConnectInfo(SocketAddr::from(([127, 0, 0, 1], 2727))));
+        let user_token = BASE64_STANDARD.encode("test_user:test_password");
+        let request = |method: Method, session_id: &str, body: &'static str| {
+            Request::builder()
+                .method(method)
+                .uri("/service/local/staging/deploy/maven2/com/example/example/1.0/example-1.0.jar")
+                .header(HOST, "localhost")
+                .header(USER_AGENT, "gradle-nexus-publish-plugin")
+                .header(AUTHORIZATION, format!("Basic {user_token}"))
+                .header("x-upload-session", session_id)
+                .body(Body::from(body))
+        };
+        let ip_addr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
+
+        for session_id in ["build-1", "build-2", "build-3"] {
+            for method in [Method::GET, Method::HEAD] {
+                let response = app
+                    .clone()
+                    .oneshot(request(method, session_id, "")?)
+                    .await?;
+                assert_eq!(response.status(), StatusCode::NOT_FOUND);
+            }
+        }
+
+        // no repository index was allocated to the sessions that only read
+        let response = app
+            .oneshot(request(Method::PUT, "build-4", "test_file_content")?)
+            .await?;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let repository_key = app_state
+            .repository
+            .find_no_profile_repository("test_user", &ip_addr, Some("build-4"))
+            .await
+            .ok_or_else(|| eyre::eyre!("No repository for the upload session"))?;
+        assert_eq!(repository_key.repository_index, 0);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn nest_under_base_path() -> eyre::Result<()> {
+        let app_state = AppState::new(
+            LocalRepository::new()?,
+            PortalApiClient::client("http://localhost")?,
+            AppConfig::with_overrides(&[("base_path", "/nexus")])?,
+        );
+        let app =
+            build_app(app_state).layer(MockConnectInfo(SocketAddr::from(([127, 0, 0, 1], 2727))));
+
+        let (status, body) = send(
+            &app,
+            Method::GET,
+            "/nexus/service/local/staging/profiles/com.example",
+            Body::empty(),
+        )
+        .await?;
+        assert_eq!(status, StatusCode::OK, "{}", String::from_utf8_lossy(&body));
+        let body: serde_json::Value = serde_json::from_slice(&body)?;
+        assert_eq!(
+            body["data"]["deployURI"],
+            "localhost/nexus/service/local/staging/deploy/maven2"
+        );
+        assert_eq!(
+            body["data"]["resourceURI"],
+            "localhost/nexus/service/local/staging/profiles/com.example/com.example"
+        );
+
+        let (status, _) = send(
+            &app,
+            Method::GET,
+            "/service/local/staging/profiles/com.example",
+            Body::empty(),
+        )
+        .await?;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn link_to_public_base_url() -> eyre::Result<()> {
+        for (overrides, expected_deploy_uri) in [
+            (&[][..], "localhost/service/local/staging/deploy/maven2"),
+            (
+                &[("public_base_url", "https://repo.example.com")][..],
+                "https://repo.example.com/service/local/staging/deploy/maven2",
+            ),
+        ] {
+            let app_state = AppState::new(
+                LocalRepository::new()?,
+                PortalApiClient::client("http://localhost")?,
+                AppConfig::with_overrides(overrides)?,
+            );
+            let app = build_app(app_state)
+                .layer(MockConnectInfo(SocketAddr::from(([127, 0, 0, 1], 2727))));
+
+            let (status, body) = send(
+                &app,
+                Method::GET,
+                "/service/local/staging/profiles/com.example",
+                Body::empty(),
+            )
+            .await?;
+            assert_eq!(status, StatusCode::OK);
+            let body: serde_json::Value = serde_json::from_slice(&body)?;
+            assert_eq!(body["data"]["deployURI"], expected_deploy_uri);
+        }
+
+        Ok(())
+    }
+
+    /// Read from the connection until the end of the headers of a response
+    async fn read_response_head(stream: &mut TcpStream) -> eyre::Result<String> {
+        let mut head = Vec::new();
+        while !head.ends_with(b"\r\n\r\n") {
+            head.push(stream.read_u8().await?);
+        }
+
+        Ok(String::from_utf8(head)?)
+    }
+
+    #[tokio::test]
+    async fn expect_continue_upload() -> eyre::Result<()> {
+        let app_state = AppState::new(
+            LocalRepository::new()?,
+            PortalApiClient::client("http://localhost")?,
+            AppConfig::with_overrides(&[])?,
+        );
+        let repository_key = app_state
+            .repository
+            .start(
+                "test_user",
+                &IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+                "com.example",
+            )
+            .await?;
+        let repository = Arc::clone(&app_state.repository);
+
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        tokio::spawn(async move {
+            axum::serve(
+                listener,
+                build_app(app_state).into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .await
+        });
+
+        let file_path = "com/example/example/0.1.0/example-0.1.0.jar";
+        let file_contents = "test_file_content";
+        let upload_head = |authorization: &str| {
+            format!(
+                "PUT /service/local/staging/deployByRepositoryId/{}/{file_path} HTTP/1.1\r\n\
+                 Host: localhost\r\n\
+                 User-Agent: Apache-Maven/3.9.6\r\n\
+                 Authorization: Basic {authorization}\r\n\
+                 Content-Length: {}\r\n\
+                 Expect: 100-continue\r\n\r\n",
+                repository_key.get_repository_id(),
+                file_contents.len()
+            )
+        };
+
+        // the body is only sent once the server asks for it
+        let mut stream = TcpStream::connect(addr).await?;
+        let user_token = BASE64_STANDARD.encode("test_user:test_password");
+        stream
+            .write_all(upload_head(&user_token).as_bytes())
+            .await?;
+        assert!(read_response_head(&mut stream)
+            .await?
+            .starts_with("HTTP/1.1 100 Continue"));
+        stream.write_all(file_contents.as_bytes()).await?;
+        assert!(read_response_head(&mut stream)
+            .await?
+            .starts_with("HTTP/1.1 201 Created"));
+        assert_eq!(
+            repository
+                .get_file(&repository_key, std::path::Path::new(file_path))
+                .await?,
+            Some(file_contents.as_bytes().to_vec())
+        );
+
+        // rejected without waiting for the body
+        let mut stream = TcpStream::connect(addr).await?;
+        stream.write_all(upload_head("invalid").as_bytes()).await?;
+        assert!(read_response_head(&mut stream)
+            .await?
+            .starts_with("HTTP/1.1 401 Unauthorized"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn gradle_publish_reaches_central() -> eyre::Result<()> {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/api/v1/publisher/upload"))
+            .and(query_param("publishingType", "AUTOMATIC"))
+            .respond_with(ResponseTemplate::new(201).set_body_string("test_deployment_id"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/api/v1/publisher/status"))
+            .and(query_param("id", "test_deployment_id"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                r#"{"deploymentId": "test_deployment_id", "deploymentName": "test_deployment", "deploymentState": "PUBLISHED"}"#,
+            ))
+            .mount(&mock_server)
+            .await;
+
+        let app_state = AppState::new(
+            LocalRepository::new()?,
+            PortalApiClient::client(&mock_server.uri())?,
+            AppConfig::with_overrides(&[])?,
+        );
+        let app =
+            build_app(app_state).layer(MockConnectInfo(SocketAddr::from(([127, 0, 0, 1], 2727))));
+
+        let (status, body) = send(
+            &app,
+            Method::POST,
+            "/service/local/staging/profiles/com.example/start",
+            r#"{"data": {"description": "Created by the Gradle Nexus Publish Plugin"}}"#,
+        )
+        .await?;
+        assert_eq!(status, StatusCode::OK, "{}", String::from_utf8_lossy(&body));
+        let body: serde_json::Value = serde_json::from_slice(&body)?;
+        let repository_id = body["data"]["stagedRepositoryId"]
+            .as_str()
+            .ok_or_else(|| eyre::eyre!("No staged repository in {body}"))?
+            .to_string();
+
+        for file_path in [
+            "com/example/example/0.1.0/example-0.1.0.jar",
+            "com/example/example/0.1.0/example-0.1.0.pom",
+        ] {
+            let (status, _) = send(
+                &app,
+                Method::PUT,
+                &format!("/service/local/staging/deployByRepositoryId/{repository_id}/{file_path}"),
+                file_path.to_string(),
+            )
+            .await?;
+            assert_eq!(status, StatusCode::CREATED);
+        }
+
+        let staging_action = format!(
+            r#"{{"data": {{"stagedRepositoryIds": ["{repository_id}"], "description": "", "autoDropAfterRelease": true}}}}"#
+        );
+        let (status, body) = send(
+            &app,
+            Method::POST,
+            "/service/local/staging/bulk/close",
+            staging_action.clone(),
+        )
+        .await?;
+        assert_eq!(status, StatusCode::OK, "{}", String::from_utf8_lossy(&body));
+
+        let (status, body) = send(
+            &app,
+            Method::GET,
+            &format!("/service/local/staging/repository/{repository_id}"),
+            Body::empty(),
+        )
+        .await?;
+        assert_eq!(status, StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(&body)?;
+        assert_eq!(body["type"], "closed");
+        assert_eq!(body["transitioning"], false);
+        assert_eq!(body["description"], "Central deployment test_deployment_id");
+
+        // keep the released repository around to check its activity
+        let (status, _) = send(
+            &app,
+            Method::POST,
+            "/service/local/staging/bulk/promote",
+            staging_action.replace(
+                r#""autoDropAfterRelease": true"#,
+                r#""autoDropAfterRelease": false"#,
+            ),
+        )
+        .await?;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, body) = send(
+            &app,
+            Method::GET,
+            &format!("/service/local/staging/repository/{repository_id}/activity"),
+            Body::empty(),
+        )
+        .await?;
+        assert_eq!(status, StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(&body)?;
+        assert_eq!(body[0]["name"], "open");
+        assert_eq!(body[1]["name"], "close");
+        assert_eq!(body[2]["name"], "release");
+
+        mock_server.verify().await;
+        let uploads = mock_server.received_requests().await.unwrap_or_default();
+        let bundle_path = b"com/example/example/0.1.0/example-0.1.0.pom";
+        assert!(uploads.iter().any(|upload| upload
+            .body
+            .windows(bundle_path.len())
+            .any(|window| window == bundle_path)));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn failed_publish_shows_in_repository_status() -> eyre::Result<()> {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/api/v1/publisher/upload"))
+            .respond_with(ResponseTemplate::new(400).set_body_string("missing signature"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let app_state = AppState::new(
+            LocalRepository::new()?,
+            PortalApiClient::client(&mock_server.uri())?,
+            AppConfig::with_overrides(&[])?,
+        );
+        let app =
+            build_app(app_state).layer(MockConnectInfo(SocketAddr::from(([127, 0, 0, 1], 2727))));
+
+        let (status, body) = send(
+            &app,
+            Method::POST,
+            "/service/local/staging/profiles/com.example/start",
+            r#"{"data": {"description": "Created by the Gradle Nexus Publish Plugin"}}"#,
+        )
+        .await?;
+        assert_eq!(status, StatusCode::OK, "{}", String::from_utf8_lossy(&body));
+        let body: serde_json::Value = serde_json::from_slice(&body)?;
+        let repository_id = body["data"]["stagedRepositoryId"]
+            .as_str()
+            .ok_or_else(|| eyre::eyre!("No staged repository in {body}"))?
+            .to_string();
+
+        let file_path = "com/example/example/0.1.0/example-0.1.0.jar";
+        let (status, _) = send(
+            &app,
+            Method::PUT,
+            &format!("/service/local/staging/deployByRepositoryId/{repository_id}/{file_path}"),
+            file_path.to_string(),
+        )
+        .await?;
+        assert_eq!(status, StatusCode::CREATED);
+
+        let (status, body) = send(
+            &app,
+            Method::POST,
+            "/service/local/staging/bulk/close",
+            format!(
+                r#"{{"data": {{"stagedRepositoryIds": ["{repository_id}"], "description": "", "autoDropAfterRelease": true}}}}"#
+            ),
+        )
+        .await?;
+        assert_eq!(
+            status,
+            StatusCode::BAD_REQUEST,
+            "{}",
+            String::from_utf8_lossy(&body)
+        );
+
+        let (status, body) = send(
+            &app,
+            Method::GET,
+            &format!("/service/local/staging/repository/{repository_id}"),
+            Body::empty(),
+        )
+        .await?;
+        assert_eq!(status, StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(&body)?;
+        assert_eq!(body["type"], "open");
+        assert_eq!(body["notifications"], 1);
+        let validation_errors = body["validationErrors"]
+            .as_array()
+            .ok_or_else(|| eyre::eyre!("No validation errors in {body}"))?;
+        assert_eq!(validation_errors.len(), 1);
+        assert!(validation_errors[0]
+            .as_str()
+            .is_some_and(|error| error.contains("missing signature")));
+
+        mock_server.verify().await;
+
+        Ok(())
+    }
+}
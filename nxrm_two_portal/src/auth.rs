@@ -29,7 +29,7 @@ impl UserToken {
         })
     }
 
-    pub fn as_credentials(self) -> Credentials {
+    pub fn into_credentials(self) -> Credentials {
         Credentials::new(self.token_username, self.token_password)
     }
 }
@@ -57,22 +57,58 @@ pub async fn auth(mut req: Request, next: Next) -> Result<Response, StatusCode>
     Ok(next.run(req).await)
 }
 
-const BASIC_PREFIX: &str = "Basic ";
-const BEARER_PREFIX: &str = "Bearer ";
+const BASIC_SCHEME: &str = "Basic";
+const BEARER_SCHEME: &str = "Bearer";
 
+/// Extract the token, matching the scheme regardless of case and ignoring surrounding whitespace
 fn token_from_header(auth_header: &str) -> eyre::Result<String> {
-    if auth_header.starts_with(BASIC_PREFIX) {
+    let (scheme, token) = auth_header
+        .trim()
+        .split_once(char::is_whitespace)
+        .ok_or_eyre("Auth header provided without a token")?;
+    let token = token.trim();
+    if token.is_empty() {
+        bail!("Auth header provided without a token");
+    }
+
+    if scheme.eq_ignore_ascii_case(BASIC_SCHEME) {
         tracing::trace!("Basic authorization header provided");
-        return auth_header
-            .strip_prefix(BASIC_PREFIX)
-            .map(String::from)
-            .ok_or_eyre("Improperly formatted Basic auth header");
-    } else if auth_header.starts_with(BEARER_PREFIX) {
+    } else if scheme.eq_ignore_ascii_case(BEARER_SCHEME) {
         tracing::trace!("Bearer authorization header provided");
-        return auth_header
-            .strip_prefix(BEARER_PREFIX)
-            .map(String::from)
-            .ok_or_eyre("Improperly formatted Bearer auth header");
+    } else {
+        bail!("Auth header provided with some other prefix");
+    }
+
+    Ok(token.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn token_from_basic_and_bearer_headers() -> eyre::Result<()> {
+        assert_eq!(token_from_header("Basic dXNlcjpwYXNz")?, "dXNlcjpwYXNz");
+        assert_eq!(token_from_header("basic dXNlcjpwYXNz")?, "dXNlcjpwYXNz");
+        assert_eq!(token_from_header("Bearer dXNlcjpwYXNz")?, "dXNlcjpwYXNz");
+        assert_eq!(token_from_header("BEARER dXNlcjpwYXNz")?, "dXNlcjpwYXNz");
+
+        Ok(())
+    }
+
+    #[test]
+    fn token_with_extra_whitespace() -> eyre::Result<()> {
+        assert_eq!(token_from_header("Bearer  dXNlcjpwYXNz")?, "dXNlcjpwYXNz");
+        assert_eq!(token_from_header(" Basic\tdXNlcjpwYXNz ")?, "dXNlcjpwYXNz");
+
+        Ok(())
+    }
+
+    #[test]
+    fn reject_unknown_schemes_and_missing_tokens() {
+        assert!(token_from_header("Digest dXNlcjpwYXNz").is_err());
+        assert!(token_from_header("Basicc dXNlcjpwYXNz").is_err());
+        assert!(token_from_header("dXNlcjpwYXNz").is_err());
+        assert!(token_from_header("Basic   ").is_err());
     }
-    bail!("Auth header provided with some other prefix");
 }
@@ -0,0 +1,102 @@
+use tower_http::compression::{
+    predicate::{DefaultPredicate, NotForContentType, Predicate},
+    CompressionLayer,
+};
+
+/// Compress responses for clients that send `Accept-Encoding`, leaving archives as they are
+///
+/// Jars and zips are already compressed, so recompressing them only costs time.
+pub fn compress_responses() -> CompressionLayer<impl Predicate> {
+    let predicate = DefaultPredicate::new()
+        .and(NotForContentType::new("application/java-archive"))
+        .and(NotForContentType::new("application/zip"))
+        .and(NotForContentType::new("application/octet-stream"));
+
+    CompressionLayer::new().compress_when(predicate)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use axum::{
+        body::Body,
+        http::{
+            header::{ACCEPT, ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_TYPE, HOST, USER_AGENT},
+            Request, StatusCode,
+        },
+        routing::get,
+        Router,
+    };
+    use flate2::read::GzDecoder;
+    use portal_api::PortalApiClient;
+    use repository::local_repository::LocalRepository;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::config::AppConfig;
+    use crate::endpoints::staging::staging_profiles_list_endpoint;
+    use crate::state::AppState;
+
+    #[tokio::test]
+    async fn gzip_profile_list() -> eyre::Result<()> {
+        let app = Router::new()
+            .route("/profiles", get(staging_profiles_list_endpoint))
+            .with_state(AppState::new(
+                LocalRepository::new()?,
+                PortalApiClient::client("http://localhost")?,
+                AppConfig::with_overrides(&[])?,
+            ))
+            .layer(compress_responses());
+
+        let response = app
+            .oneshot(
+                Request::get("/profiles")
+                    .header(HOST, "localhost")
+                    .header(USER_AGENT, "test")
+                    .header(ACCEPT, "application/xml")
+                    .header(ACCEPT_ENCODING, "gzip")
+                    .body(Body::empty())?,
+            )
+            .await?;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers().get(CONTENT_ENCODING),
+            Some(&"gzip".parse()?)
+        );
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await?;
+        let mut profiles = String::new();
+        GzDecoder::new(body.as_ref()).read_to_string(&mut profiles)?;
+        assert!(profiles.contains("<stagingProfiles>"), "{profiles}");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn leave_jars_uncompressed() -> eyre::Result<()> {
+        let jar = vec![0u8; 1024];
+        let app = Router::new()
+            .route(
+                "/example.jar",
+                get(move || async move { ([(CONTENT_TYPE, "application/java-archive")], jar) }),
+            )
+            .layer(compress_responses());
+
+        let response = app
+            .oneshot(
+                Request::get("/example.jar")
+                    .header(ACCEPT_ENCODING, "gzip")
+                    .body(Body::empty())?,
+            )
+            .await?;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers().get(CONTENT_ENCODING), None);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await?;
+        assert_eq!(body.len(), 1024);
+
+        Ok(())
+    }
+}
@@ -1,22 +1,580 @@
+use std::num::{NonZeroU32, NonZeroU64};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
 use config::{Config, Environment};
-use portal_api::CENTRAL_HOST;
-use serde::Deserialize;
+use eyre::{OptionExt, WrapErr};
+use portal_api::{AuthScheme, CENTRAL_HOST};
+use repository::{
+    checksums::ChecksumMismatchPolicy, namespaces::NamespaceSet, options::RepositoryOptions,
+    signatures::SignatureVerifier,
+};
+use serde::{Deserialize, Serialize, Serializer};
+use tokio::sync::Semaphore;
+
+use crate::endpoints::staging::{
+    DEFAULT_STAGING_PROFILE_MODE, DEFAULT_STAGING_PROFILE_POLICY,
+    DEFAULT_STAGING_PROFILE_RELEASE_REPOSITORY, DEFAULT_STAGING_PROFILE_REPOSITORY_TYPE,
+};
+use crate::endpoints::status::{DEFAULT_NEXUS_EDITION, DEFAULT_NEXUS_VERSION};
+use crate::publish::{
+    DeploymentNameTemplate, PublishOptions, DEFAULT_DEPLOYMENT_NAME_TEMPLATE,
+    DEFAULT_MAX_DEPLOYMENT_NAME_LENGTH, DEFAULT_PUBLISH_TIMEOUT, DEFAULT_VALIDATION_POLL_INTERVAL,
+    DEFAULT_VALIDATION_TIMEOUT,
+};
 
-#[derive(Debug, Deserialize)]
+/// The settings of the proxy, loaded from `NXRM_TWO_PORTAL_`-prefixed environment variables
+///
+/// Serializes without anything sensitive, for operators to check the settings in effect.
+#[derive(Debug, Deserialize, Serialize)]
 pub(crate) struct AppConfig {
+    #[serde(serialize_with = "serialize_redacted_url")]
     pub central_url: String,
+    /// Comma-separated hosts besides Maven Central that the `central_url` must point at, always over `https`; any URL if unset
+    pub allowed_central_hosts: Option<String>,
+    /// How user tokens are sent to Central (`user_token`, or `basic` for compatible servers that expect HTTP Basic)
+    pub central_auth_scheme: AuthScheme,
     pub app_port: u16,
+    /// Path prefix that the proxy is mounted under, such as `/nexus`, with routes at the root if unset
+    pub base_path: Option<String>,
+    /// Scheme and host to link to in responses instead of the request `Host`, such as behind a TLS-terminating proxy
+    pub public_base_url: Option<String>,
+    /// The NXRM2 version reported by the status endpoint, for clients that check it
+    pub nexus_version: String,
+    /// The NXRM2 edition reported by the status endpoint (`PRO` or `OSS`)
+    pub nexus_edition: String,
+    /// The `mode` of the emulated staging profiles (`BOTH`, `DEPLOY`, or `UPLOAD`)
+    pub staging_profile_mode: String,
+    /// The repository policy of the emulated staging profiles (`release` or `snapshot`)
+    pub staging_profile_policy: String,
+    /// The `repositoryType` of the emulated staging profiles
+    pub staging_profile_repository_type: String,
+    /// The repository that the emulated staging profiles promote releases to
+    pub staging_profile_release_repository: String,
+    /// Where staging repositories are kept, with only `local` (on disk) available so far
+    pub repository_backend: RepositoryBackend,
+    /// Directory to keep staging repositories in across restarts, a temporary directory if unset
+    pub repository_root: Option<String>,
+    /// Start of the name of the temporary directory that repositories are kept in without a `repository_root`
+    pub repository_temp_dir_prefix: Option<String>,
+    /// Where to create the temporary directory that repositories are kept in, honoring `TMPDIR` if unset
+    pub repository_temp_dir_parent: Option<String>,
+    /// Restrict the directory that repositories are kept in to the user running the proxy
+    pub private_repository_root: bool,
+    /// Comma-separated namespaces that may be published to, each covering its subgroups and listed as a staging profile; any if unset
+    pub namespaces: Option<String>,
+    /// Give staging profiles numeric IDs, for clients that require them, instead of using the namespace
+    pub numeric_profile_ids: bool,
+    /// Comma-separated parts of the user agents that may use the proxy, matched case-insensitively; any if unset
+    pub allowed_user_agents: Option<String>,
+    /// Comma-separated parts of the user agents that are rejected with `403 Forbidden`, matched case-insensitively
+    pub blocked_user_agents: Option<String>,
+    /// Take client IPs from the `Forwarded`/`X-Forwarded-For` headers, for proxies behind a load balancer
+    pub trust_forwarded_headers: bool,
+    /// Uploads allowed to be in progress at once, with any more rejected as `503 Service Unavailable`; at least 1
+    pub max_concurrent_uploads: usize,
+    /// Uploads to Central allowed per minute for each namespace, with any more waiting their turn; unlimited if unset
+    pub uploads_per_minute: Option<NonZeroU32>,
+    /// Reject closing a repository where a `.jar`/`.pom` has no `.asc` signature
+    pub require_signatures: bool,
+    /// Verify uploaded signatures against the `signature_keyring` before closing a repository
+    pub verify_signatures: bool,
+    /// Path to an ASCII-armored keyring of the public keys trusted to sign artifacts
+    #[serde(skip_serializing)]
+    pub signature_keyring: Option<String>,
+    /// How to handle stale checksum sidecars when closing a repository (`ignore`, `regenerate`, or `reject`)
+    pub checksum_mismatch: String,
+    /// Reject closing a repository with a malformed POM, or one missing its groupId, artifactId, or version
+    pub validate_poms: bool,
+    /// Generate the `.md5` and `.sha1` checksums missing for any uploaded file in the bundles sent to Central
+    pub generate_checksums: bool,
+    /// Generate a `maven-metadata.xml` for each artifact in the bundles sent to Central
+    pub generate_maven_metadata: bool,
+    /// Build reproducible bundles, with entries sorted by path and fixed timestamps
+    pub deterministic_bundles: bool,
+    /// Largest size in bytes that a single staging repository may grow to, unlimited if unset
+    pub max_repository_size_bytes: Option<u64>,
+    /// Most files that a single staging repository may hold, unlimited if unset
+    pub max_files_per_repository: Option<usize>,
+    /// Largest bundle in bytes that will be sent to Central, unlimited if unset
+    pub max_bundle_size_bytes: Option<u64>,
+    /// Comma-separated file extensions that may be uploaded, any if unset
+    pub allowed_extensions: Option<String>,
+    /// Comma-separated file extensions that are rejected, such as `tmp,lock`
+    pub denied_extensions: Option<String>,
+    /// Fewest path segments an uploaded file may have, 4 being `groupId/artifactId/version/file`
+    pub min_path_depth: usize,
+    /// Close repositories without any files into empty bundles, instead of rejecting them
+    pub allow_empty_bundles: bool,
+    /// Respond to automatic publishes with `202 Accepted` and the repository status as the `Location`
+    pub accept_automatic_publishes: bool,
+    /// Build bundles when closing repositories, but skip uploading them to Central
+    pub dry_run: bool,
+    /// Directory to write the bundles built during a dry run to
+    pub dry_run_dir: Option<String>,
+    /// Directory to keep a copy of each bundle uploaded to Central in
+    pub bundle_archive_dir: Option<String>,
+    /// Directory to keep bundles in while they are uploaded, so that uploads interrupted by a crash can be retried through the API
+    pub pending_upload_dir: Option<String>,
+    /// Seconds an open repository may go without uploads or reads before it is removed; never if unset
+    pub idle_repository_ttl_secs: Option<NonZeroU64>,
+    /// Name of the deployments created at Central, with `{repository_id}`, `{user}`, and `{namespace}` placeholders
+    pub deployment_name_template: DeploymentNameTemplate,
+    /// Append ` (via OSSRH API Proxy)` to the names of the deployments created at Central
+    pub append_proxy_suffix: bool,
+    /// Longest name in characters of the deployments created at Central, with longer names truncated
+    pub max_deployment_name_length: usize,
+    /// Seconds between checks on a deployment that Central is validating
+    pub validation_poll_interval_secs: u64,
+    /// Seconds to wait for Central to validate a deployment before giving up
+    pub validation_timeout_secs: u64,
+    /// Seconds to wait for Central to accept an upload before leaving the repository in the `unknown` state
+    pub publish_timeout_secs: u64,
+    /// Seconds a request other than an upload or publish may take before it is answered with `408 Request Timeout`
+    pub request_timeout_secs: u64,
+    /// Seconds an upload or publish may go without sending any of its body before it is cut off
+    pub body_idle_timeout_secs: u64,
+    /// Most bytes of a request body written to the trace logs, the rest being truncated
+    pub debug_body_log_limit_bytes: usize,
+}
+
+/// The implementations of [repository::traits::Repository] that the proxy can be started with
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum RepositoryBackend {
+    /// Files on disk, under the `repository_root`
+    Local,
 }
 
 impl AppConfig {
     pub fn load() -> eyre::Result<Self> {
         let env_source = Environment::with_prefix("nxrm_two_portal");
+        Self::load_from(env_source)
+    }
+
+    fn load_from(env_source: Environment) -> eyre::Result<Self> {
         let app_config = Config::builder()
             .set_default("central_url", CENTRAL_HOST)?
-            .set_default("app_port", 2727 as u16)?
+            .set_default("central_auth_scheme", "user_token")?
+            .set_default("app_port", 2727_u16)?
+            .set_default("max_concurrent_uploads", 64_u64)?
+            .set_default("nexus_version", DEFAULT_NEXUS_VERSION)?
+            .set_default("nexus_edition", DEFAULT_NEXUS_EDITION)?
+            .set_default("staging_profile_mode", DEFAULT_STAGING_PROFILE_MODE)?
+            .set_default("staging_profile_policy", DEFAULT_STAGING_PROFILE_POLICY)?
+            .set_default(
+                "staging_profile_repository_type",
+                DEFAULT_STAGING_PROFILE_REPOSITORY_TYPE,
+            )?
+            .set_default(
+                "staging_profile_release_repository",
+                DEFAULT_STAGING_PROFILE_RELEASE_REPOSITORY,
+            )?
+            .set_default("repository_backend", "local")?
+            .set_default("private_repository_root", true)?
+            .set_default("numeric_profile_ids", false)?
+            .set_default("trust_forwarded_headers", false)?
+            .set_default("require_signatures", false)?
+            .set_default("verify_signatures", false)?
+            .set_default("checksum_mismatch", "ignore")?
+            .set_default("validate_poms", false)?
+            .set_default("generate_checksums", false)?
+            .set_default("generate_maven_metadata", false)?
+            .set_default("deterministic_bundles", false)?
+            .set_default("min_path_depth", 4_u64)?
+            .set_default("allow_empty_bundles", false)?
+            .set_default("accept_automatic_publishes", false)?
+            .set_default("dry_run", false)?
+            .set_default("deployment_name_template", DEFAULT_DEPLOYMENT_NAME_TEMPLATE)?
+            .set_default("append_proxy_suffix", true)?
+            .set_default(
+                "max_deployment_name_length",
+                DEFAULT_MAX_DEPLOYMENT_NAME_LENGTH as u64,
+            )?
+            .set_default(
+                "validation_poll_interval_secs",
+                DEFAULT_VALIDATION_POLL_INTERVAL.as_secs(),
+            )?
+            .set_default(
+                "validation_timeout_secs",
+                DEFAULT_VALIDATION_TIMEOUT.as_secs(),
+            )?
+            .set_default("publish_timeout_secs", DEFAULT_PUBLISH_TIMEOUT.as_secs())?
+            .set_default("request_timeout_secs", 900_u64)?
+            .set_default("body_idle_timeout_secs", 60_u64)?
+            .set_default("debug_body_log_limit_bytes", 4096_u64)?
             .add_source(env_source)
             .build()?
-            .try_deserialize()?;
+            .try_deserialize::<Self>()?;
+
+        if let Some(allowed_central_hosts) = app_config.allowed_central_hosts() {
+            portal_api::check_allowed_host(&app_config.central_url, &allowed_central_hosts)
+                .wrap_err("central_url is not allowed")?;
+        }
+
+        if !(1..=Semaphore::MAX_PERMITS).contains(&app_config.max_concurrent_uploads) {
+            eyre::bail!(
+                "max_concurrent_uploads must be between 1 and {}, not {}",
+                Semaphore::MAX_PERMITS,
+                app_config.max_concurrent_uploads
+            );
+        }
+
         Ok(app_config)
     }
+
+    /// The hosts besides Maven Central that the proxy may publish to, or none if any URL is allowed
+    pub fn allowed_central_hosts(&self) -> Option<Vec<String>> {
+        let allowed_central_hosts = self.allowed_central_hosts.as_deref()?;
+
+        Some(
+            allowed_central_hosts
+                .split(',')
+                .map(|host| host.trim().to_string())
+                .filter(|host| !host.is_empty())
+                .collect(),
+        )
+    }
+
+    /// The path prefix that every route is nested under, with a leading `/` and no trailing `/`
+    pub fn base_path(&self) -> Option<String> {
+        let base_path = self.base_path.as_deref()?.trim().trim_matches('/');
+        if base_path.is_empty() {
+            None
+        } else {
+            Some(format!("/{base_path}"))
+        }
+    }
+
+    /// The URL that the proxy is reached at through the host, for the links in responses
+    ///
+    /// The `public_base_url` takes the place of the host when set.
+    pub fn base_url(&self, host: &str) -> String {
+        let host = match self.public_base_url.as_deref().map(str::trim) {
+            Some(public_base_url) if !public_base_url.is_empty() => {
+                public_base_url.trim_end_matches('/')
+            }
+            _ => host,
+        };
+
+        format!("{host}{}", self.base_path().unwrap_or_default())
+    }
+
+    /// Whether a client with the user agent may use the proxy
+    pub fn user_agent_allowed(&self, user_agent: &str) -> bool {
+        let user_agent = user_agent.to_lowercase();
+        let matches = |user_agents: &Option<String>| {
+            user_agent_list(user_agents)
+                .iter()
+                .any(|part| user_agent.contains(part.as_str()))
+        };
+
+        let allowed = self.allowed_user_agents.is_none() || matches(&self.allowed_user_agents);
+        allowed && !matches(&self.blocked_user_agents)
+    }
+
+    pub fn repository_options(&self) -> eyre::Result<RepositoryOptions> {
+        let signature_verifier = if self.verify_signatures {
+            let signature_keyring = self
+                .signature_keyring
+                .as_ref()
+                .ok_or_eyre("signature_keyring is required to verify signatures")?;
+            Some(Arc::new(SignatureVerifier::from_keyring_file(
+                signature_keyring,
+            )?))
+        } else {
+            None
+        };
+
+        let checksum_mismatch = ChecksumMismatchPolicy::try_from(self.checksum_mismatch.as_str())
+            .map_err(|e| eyre::eyre!(e))?;
+
+        Ok(RepositoryOptions {
+            require_signatures: self.require_signatures,
+            signature_verifier,
+            checksum_mismatch,
+            validate_poms: self.validate_poms,
+            generate_checksums: self.generate_checksums,
+            generate_maven_metadata: self.generate_maven_metadata,
+            deterministic_bundles: self.deterministic_bundles,
+            max_repository_size: self.max_repository_size_bytes,
+            max_files_per_repository: self.max_files_per_repository,
+            allowed_extensions: self.allowed_extensions.as_deref().map(extension_list),
+            denied_extensions: self
+                .denied_extensions
+                .as_deref()
+                .map(extension_list)
+                .unwrap_or_default(),
+            min_path_depth: self.min_path_depth,
+            namespaces: Some(self.namespaces()).filter(|namespaces| !namespaces.is_empty()),
+            allow_empty_bundles: self.allow_empty_bundles,
+            temp_dir_prefix: self.repository_temp_dir_prefix.clone(),
+            temp_dir_parent: self.repository_temp_dir_parent.as_ref().map(PathBuf::from),
+            private_root: self.private_repository_root,
+        })
+    }
+
+    /// The configured namespaces, or none if publishing to any namespace is allowed
+    pub fn namespaces(&self) -> NamespaceSet {
+        NamespaceSet::new(
+            self.namespaces
+                .iter()
+                .flat_map(|namespaces| namespaces.split(',')),
+        )
+    }
+
+    pub fn publish_options(&self) -> PublishOptions {
+        PublishOptions {
+            dry_run: self.dry_run,
+            dry_run_dir: self.dry_run_dir.as_ref().map(PathBuf::from),
+            deployment_name_template: self.deployment_name_template.clone(),
+            append_proxy_suffix: self.append_proxy_suffix,
+            max_deployment_name_length: self.max_deployment_name_length,
+            validate_only: false,
+            validation_poll_interval: Duration::from_secs(self.validation_poll_interval_secs),
+            validation_timeout: Duration::from_secs(self.validation_timeout_secs),
+            publish_timeout: Duration::from_secs(self.publish_timeout_secs),
+            max_bundle_size: self.max_bundle_size_bytes,
+            bundle_archive_dir: self.bundle_archive_dir.as_ref().map(PathBuf::from),
+            // shared by every publish, so these come from the app state
+            upload_rate_limiter: None,
+            pending_uploads: None,
+            publishes_in_flight: None,
+        }
+    }
+}
+
+/// A URL with any credentials in it replaced
+fn serialize_redacted_url<S: Serializer>(url: &str, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&redact_url_credentials(url))
+}
+
+fn redact_url_credentials(url: &str) -> String {
+    let authority_start = url.find("://").map_or(0, |scheme_end| scheme_end + 3);
+    let authority_end = url[authority_start..]
+        .find(['/', '?', '#'])
+        .map_or(url.len(), |path_start| authority_start + path_start);
+
+    match url[authority_start..authority_end].rfind('@') {
+        Some(credentials_end) => format!(
+            "{}[REDACTED]{}",
+            &url[..authority_start],
+            &url[authority_start + credentials_end..]
+        ),
+        None => url.to_string(),
+    }
+}
+
+/// The lowercase user agent parts in a comma-separated list
+fn user_agent_list(user_agents: &Option<String>) -> Vec<String> {
+    user_agents
+        .iter()
+        .flat_map(|user_agents| user_agents.split(','))
+        .map(|user_agent| user_agent.trim().to_lowercase())
+        .filter(|user_agent| !user_agent.is_empty())
+        .collect()
+}
+
+/// The lowercase extensions in a comma-separated list, with any leading `.` removed
+fn extension_list(extensions: &str) -> Vec<String> {
+    extensions
+        .split(',')
+        .map(|extension| extension.trim().trim_start_matches('.').to_lowercase())
+        .filter(|extension| !extension.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+impl AppConfig {
+    /// The default configuration, with the provided settings overridden
+    pub fn with_overrides(overrides: &[(&str, &str)]) -> eyre::Result<Self> {
+        let overrides = overrides
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        Self::load_from(Environment::default().source(Some(overrides)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn select_repository_backend() -> eyre::Result<()> {
+        assert_eq!(
+            AppConfig::with_overrides(&[])?.repository_backend,
+            RepositoryBackend::Local
+        );
+        assert_eq!(
+            AppConfig::with_overrides(&[("repository_backend", "local")])?.repository_backend,
+            RepositoryBackend::Local
+        );
+
+        // no other backends have been implemented yet
+        for backend in ["memory", "s3"] {
+            assert!(
+                AppConfig::with_overrides(&[("repository_backend", backend)]).is_err(),
+                "Accepted {backend:?}"
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn restrict_central_url() -> eyre::Result<()> {
+        assert_eq!(AppConfig::with_overrides(&[])?.central_url, CENTRAL_HOST);
+
+        let app_config = AppConfig::with_overrides(&[
+            ("central_url", "https://central.example/api"),
+            ("allowed_central_hosts", "mirror.example, central.example"),
+        ])?;
+        assert_eq!(
+            app_config.allowed_central_hosts(),
+            Some(vec![
+                "mirror.example".to_string(),
+                "central.example".to_string()
+            ])
+        );
+
+        for central_url in [
+            "https://internal.example/api",
+            "http://central.sonatype.com",
+            "http://central.example",
+            "http://localhost:3000",
+        ] {
+            assert!(
+                AppConfig::with_overrides(&[
+                    ("central_url", central_url),
+                    ("allowed_central_hosts", "central.example"),
+                ])
+                .is_err(),
+                "Allowed {central_url:?}"
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn allow_any_central_url_without_allowed_hosts() -> eyre::Result<()> {
+        for central_url in [
+            "http://localhost:3000",
+            "https://staging.portal.central.sonatype.dev",
+        ] {
+            let app_config = AppConfig::with_overrides(&[("central_url", central_url)])?;
+            assert_eq!(app_config.central_url, central_url);
+            assert_eq!(app_config.allowed_central_hosts(), None);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_deployment_name_template_on_load() -> eyre::Result<()> {
+        let app_config =
+            AppConfig::with_overrides(&[("deployment_name_template", "{namespace}-{user}")])?;
+        assert_eq!(
+            app_config.deployment_name_template,
+            DeploymentNameTemplate::parse("{namespace}-{user}")?
+        );
+        assert!(AppConfig::with_overrides(&[("deployment_name_template", "{version}")]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn reject_unusable_max_concurrent_uploads() -> eyre::Result<()> {
+        assert_eq!(
+            AppConfig::with_overrides(&[("max_concurrent_uploads", "1")])?.max_concurrent_uploads,
+            1
+        );
+
+        let too_many = (Semaphore::MAX_PERMITS + 1).to_string();
+        for max_concurrent_uploads in ["0", too_many.as_str()] {
+            assert!(
+                AppConfig::with_overrides(&[("max_concurrent_uploads", max_concurrent_uploads)])
+                    .is_err(),
+                "Allowed {max_concurrent_uploads:?}"
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn normalize_base_path() -> eyre::Result<()> {
+        assert_eq!(AppConfig::with_overrides(&[])?.base_path(), None);
+        assert_eq!(
+            AppConfig::with_overrides(&[("base_path", "/")])?.base_path(),
+            None
+        );
+        for base_path in ["/nexus", "nexus", "/nexus/"] {
+            let app_config = AppConfig::with_overrides(&[("base_path", base_path)])?;
+            assert_eq!(app_config.base_path().as_deref(), Some("/nexus"));
+            assert_eq!(
+                app_config.base_url("https://example.com"),
+                "https://example.com/nexus"
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn override_base_url() -> eyre::Result<()> {
+        assert_eq!(
+            AppConfig::with_overrides(&[])?.base_url("localhost:2727"),
+            "localhost:2727"
+        );
+        assert_eq!(
+            AppConfig::with_overrides(&[("public_base_url", "https://repo.example.com/")])?
+                .base_url("localhost:2727"),
+            "https://repo.example.com"
+        );
+        assert_eq!(
+            AppConfig::with_overrides(&[
+                ("public_base_url", "https://repo.example.com"),
+                ("base_path", "/nexus"),
+            ])?
+            .base_url("localhost:2727"),
+            "https://repo.example.com/nexus"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn match_user_agents() -> eyre::Result<()> {
+        assert!(AppConfig::with_overrides(&[])?.user_agent_allowed("curl/8.5.0"));
+
+        let app_config = AppConfig::with_overrides(&[
+            ("allowed_user_agents", "Apache-Maven, gradle"),
+            ("blocked_user_agents", "Gradle/7."),
+        ])?;
+        assert!(app_config.user_agent_allowed("Apache-Maven/3.9.6 (Java 17; Linux)"));
+        assert!(app_config.user_agent_allowed("Gradle/8.5 (Linux;6.1;amd64)"));
+        assert!(!app_config.user_agent_allowed("gradle/7.6 (Linux;6.1;amd64)"));
+        assert!(!app_config.user_agent_allowed("curl/8.5.0"));
+        assert!(!app_config.user_agent_allowed(""));
+
+        Ok(())
+    }
+
+    #[test]
+    fn select_central_auth_scheme() -> eyre::Result<()> {
+        assert_eq!(
+            AppConfig::with_overrides(&[])?.central_auth_scheme,
+            AuthScheme::UserToken
+        );
+        assert_eq!(
+            AppConfig::with_overrides(&[("central_auth_scheme", "basic")])?.central_auth_scheme,
+            AuthScheme::Basic
+        );
+        assert!(AppConfig::with_overrides(&[("central_auth_scheme", "bearer")]).is_err());
+
+        Ok(())
+    }
 }
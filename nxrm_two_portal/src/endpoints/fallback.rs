@@ -1,31 +1,109 @@
 use std::net::SocketAddr;
 
 use axum::{
-    extract::{ConnectInfo, Request},
+    extract::{ConnectInfo, Request, State},
     http::StatusCode,
+    response::{IntoResponse, Response},
 };
 use tracing::instrument;
 
+use crate::errors::NexusErrorResponse;
+use crate::extract::{
+    accept_content_type, respond_to_accepts_header, BodyLogLimit, ContentType, LoggedBody,
+};
+
 #[instrument(skip(request))]
 pub(crate) async fn fallback(
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    State(body_log_limit): State<BodyLogLimit>,
     request: Request,
-) -> (StatusCode, String) {
+) -> Response {
+    // logged in detail to discover NXRM2 methods that clients rely on but are not yet supported
     tracing::error!("Request to {}: {}", request.method(), request.uri());
+    let message = format!(
+        "Unsupported NXRM2 method: {} {}",
+        request.method(),
+        request.uri().path()
+    );
+    let headers = request.headers().clone();
     tracing::trace!("Headers: {:#?}", request.headers());
     tracing::trace!("Authority: {:#?}", request.uri().authority());
-    let bytes = axum::body::to_bytes(request.into_body(), usize::MAX).await;
-    match bytes {
-        Ok(bytes) => {
-            tracing::trace!("Body: {:?}", bytes);
+    let logged_body = LoggedBody::read(request.into_body(), body_log_limit).await;
+    match logged_body {
+        Ok(logged_body) => {
+            tracing::trace!("Body: {logged_body}");
         }
         Err(e) => {
             tracing::error!("Failed to retrieve the body: {e:?}");
         }
     }
 
-    (
-        StatusCode::UNAUTHORIZED,
-        "New method identified".to_string(),
-    )
+    match accept_content_type(&headers) {
+        Ok(ContentType::Xml | ContentType::Json) => {
+            let mut response =
+                respond_to_accepts_header(&headers, NexusErrorResponse::new(message));
+            *response.status_mut() = StatusCode::NOT_FOUND;
+            response
+        }
+        _ => (StatusCode::NOT_FOUND, message).into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        extract::connect_info::MockConnectInfo,
+        http::header::{ACCEPT, CONTENT_TYPE},
+        Router,
+    };
+    use tower::ServiceExt;
+
+    use super::*;
+
+    fn app() -> Router {
+        Router::new()
+            .fallback(fallback)
+            .with_state(BodyLogLimit(1024))
+            .layer(MockConnectInfo(SocketAddr::from(([127, 0, 0, 1], 2727))))
+    }
+
+    #[tokio::test]
+    async fn unknown_path_not_found() -> eyre::Result<()> {
+        let response = app()
+            .oneshot(
+                Request::post("/service/local/unknown/method").body(Body::from("example body"))?,
+            )
+            .await?;
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn unknown_path_json_error() -> eyre::Result<()> {
+        let response = app()
+            .oneshot(
+                Request::get("/service/local/unknown/method")
+                    .header(ACCEPT, "application/json")
+                    .body(Body::empty())?,
+            )
+            .await?;
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(response.headers()[CONTENT_TYPE], "application/json");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await?;
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(&body)?,
+            serde_json::json!({
+                "errors": [{
+                    "id": "*",
+                    "msg": "Unsupported NXRM2 method: GET /service/local/unknown/method"
+                }]
+            })
+        );
+
+        Ok(())
+    }
 }
@@ -1,54 +1,111 @@
-use std::net::SocketAddr;
 use std::ops::Deref;
 
-use axum::extract::{ConnectInfo, Host, Query, State};
-use axum::http::StatusCode;
-use axum::Extension;
+use axum::extract::{Host, Query, State};
+use axum::http::HeaderMap;
+use axum::response::{IntoResponse, Response};
+use axum::{Extension, Json};
 use axum_extra::headers::UserAgent;
 use axum_extra::TypedHeader;
 use portal_api::api_types::PublishingType;
-use repository::traits::Repository;
 use serde::{Deserialize, Serialize};
 use tracing::instrument;
 
 use crate::auth::UserToken;
+use crate::endpoints::{published_response, upload_session};
 use crate::errors::ApiError;
-use crate::publish::publish;
+use crate::extract::{accept_content_type, ClientIp, ContentType, Xml};
+use crate::publish::{publish, PublishOptions};
 use crate::state::AppState;
 
-#[instrument(skip(app_state, user_token))]
-pub(crate) async fn manual_upload_default_repository<R: Repository>(
+#[instrument(skip(headers, app_state, user_token))]
+pub(crate) async fn manual_upload_default_repository(
     Host(host): Host,
-    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    ClientIp(client_ip): ClientIp,
     TypedHeader(_user_agent): TypedHeader<UserAgent>,
-    State(app_state): State<AppState<R>>,
+    headers: HeaderMap,
+    State(app_state): State<AppState>,
     Extension(user_token): Extension<UserToken>,
     Query(params): Query<ManualUploadQueryParams>,
-) -> Result<StatusCode, ApiError> {
+) -> Result<Response, ApiError> {
     tracing::debug!("Request to manually uplaod the bundle to Portal");
 
     let repository_key = app_state
         .repository
-        .open_no_profile_repository(&user_token.token_username, &addr.ip())
+        .open_no_profile_repository(
+            &user_token.token_username,
+            &client_ip,
+            upload_session(&headers),
+        )
         .await?;
 
-    let credentials = user_token.as_credentials();
+    let credentials = user_token.into_credentials();
+    // validated deployments are dropped rather than waiting on a manual release
+    let publishing_type = if params.validate_only.unwrap_or_default() {
+        PublishingType::UserManaged
+    } else {
+        params.get_publishing_type()
+    };
 
-    publish(
+    let outcome = publish(
         &app_state.portal_api_client,
         app_state.repository.deref(),
         &credentials,
         &repository_key,
-        params.get_publishing_type(),
+        publishing_type,
+        &PublishOptions {
+            validate_only: params.validate_only.unwrap_or_default(),
+            ..app_state.publish_options()
+        },
     )
     .await?;
 
-    Ok(StatusCode::OK)
+    let repository_id = repository_key.get_repository_id();
+    let manual_upload_response = ManualUploadResponse {
+        data: ManualUploadResponseData {
+            repository_id: repository_id.clone(),
+            deployment_id: outcome.deployment_id.clone().unwrap_or_default(),
+        },
+    };
+    let (parts, _) = published_response(
+        &app_state.app_config,
+        publishing_type,
+        &[repository_id],
+        &[outcome],
+    )
+    .into_parts();
+
+    // manual uploads are often sent without an Accept header, so those get JSON
+    let mut response = match accept_content_type(&headers) {
+        Ok(ContentType::Xml) => Xml(manual_upload_response).into_response(),
+        _ => Json(manual_upload_response).into_response(),
+    };
+    *response.status_mut() = parts.status;
+    response.headers_mut().extend(parts.headers);
+
+    Ok(response)
+}
+
+#[derive(Debug, Serialize, ex_em_ell::ToXmlDocument)]
+#[serde(rename_all = "camelCase")]
+#[ex_em_ell(rename = "manualUploadResponse")]
+pub(crate) struct ManualUploadResponse {
+    data: ManualUploadResponseData,
+}
+
+#[derive(Debug, Serialize, ex_em_ell::ToXmlElement)]
+#[serde(rename_all = "camelCase")]
+struct ManualUploadResponseData {
+    repository_id: String,
+    /// Empty for a dry run, which is never uploaded to Central
+    #[serde(skip_serializing_if = "String::is_empty")]
+    deployment_id: String,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct ManualUploadQueryParams {
     publishing_type: Option<String>,
+    /// Have Central validate the bundle without publishing it, reporting problems through the repository status
+    validate_only: Option<bool>,
 }
 
 impl ManualUploadQueryParams {
@@ -65,3 +122,92 @@ impl ManualUploadQueryParams {
             .unwrap_or(PublishingType::UserManaged)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+    use std::path::Path;
+
+    use axum::body::Body;
+    use axum::extract::connect_info::MockConnectInfo;
+    use axum::http::header::{ACCEPT, HOST, USER_AGENT};
+    use axum::http::{Request, StatusCode};
+    use axum::routing::post;
+    use axum::Router;
+    use base64::prelude::{Engine, BASE64_STANDARD};
+    use portal_api::PortalApiClient;
+    use repository::local_repository::LocalRepository;
+    use tower::ServiceExt;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::*;
+    use crate::config::AppConfig;
+
+    #[tokio::test]
+    async fn return_deployment_id() -> eyre::Result<()> {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/api/v1/publisher/upload"))
+            .respond_with(ResponseTemplate::new(201).set_body_string("test_deployment_id"))
+            .mount(&mock_server)
+            .await;
+
+        let app_state = AppState::new(
+            LocalRepository::new()?,
+            PortalApiClient::client(&mock_server.uri())?,
+            AppConfig::with_overrides(&[])?,
+        );
+        let addr = SocketAddr::from(([127, 0, 0, 1], 2727));
+        let user_token = UserToken::from_token(&BASE64_STANDARD.encode("test_user:test_password"))?;
+        let app = Router::new()
+            .route("/upload", post(manual_upload_default_repository))
+            .with_state(app_state.clone())
+            .layer(Extension(user_token))
+            .layer(MockConnectInfo(addr));
+
+        for accept in [None, Some("application/json"), Some("application/xml")] {
+            let repository_key = app_state
+                .repository
+                .open_no_profile_repository("test_user", &addr.ip(), None)
+                .await?;
+            app_state
+                .repository
+                .add_file(
+                    &repository_key,
+                    Path::new("com/example/example/0.1.0/example-0.1.0.jar"),
+                    Box::pin(futures::stream::once(async {
+                        Ok(bytes::Bytes::from("test_file_content"))
+                    })),
+                )
+                .await?;
+
+            let mut request = Request::post("/upload")
+                .header(HOST, "localhost")
+                .header(USER_AGENT, "curl");
+            if let Some(accept) = accept {
+                request = request.header(ACCEPT, accept);
+            }
+            let response = app.clone().oneshot(request.body(Body::empty())?).await?;
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX).await?;
+            if accept == Some("application/xml") {
+                let body = String::from_utf8(body.to_vec())?;
+                assert!(body.contains("<deploymentId>test_deployment_id</deploymentId>"));
+            } else {
+                assert_eq!(
+                    serde_json::from_slice::<serde_json::Value>(&body)?,
+                    serde_json::json!({
+                        "data": {
+                            "repositoryId": repository_key.get_repository_id(),
+                            "deploymentId": "test_deployment_id",
+                        }
+                    })
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
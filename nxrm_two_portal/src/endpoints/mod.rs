@@ -1,4 +1,699 @@
+use std::fmt::Display;
+use std::path::Path;
+
+use axum::body::{Body, Bytes};
+use axum::http::{
+    header::{CONTENT_LENGTH, CONTENT_TYPE, ETAG, IF_NONE_MATCH, LOCATION},
+    HeaderMap, HeaderName, HeaderValue, StatusCode,
+};
+use axum::response::{IntoResponse, Response};
+use base64::prelude::{Engine, BASE64_STANDARD};
+use futures::{Stream, StreamExt};
+use md5::{Digest, Md5};
+use portal_api::api_types::PublishingType;
+use repository::traits::{
+    FileMetadata, Repository, RepositoryKey, RepositoryNotFound, RepositoryNotOpen,
+    RepositoryState, StoredFile,
+};
+use serde::Serialize;
+use tokio_util::io::ReaderStream;
+
+use crate::config::AppConfig;
+use crate::extract::{accept_content_type, respond_to_accepts_header, ContentType};
+use crate::publish::{PublishOutcome, PublishStatus};
+use crate::state::AppState;
+
 pub(crate) mod fallback;
 pub(crate) mod manual;
+pub(crate) mod pending;
+pub(crate) mod proxy_config;
 pub(crate) mod staging;
 pub(crate) mod status;
+
+/// Identifies the uploads that belong together when deploying without a staging profile
+///
+/// Lets concurrent publishes from the same user and address be bundled separately.
+const UPLOAD_SESSION_HEADER: HeaderName = HeaderName::from_static("x-upload-session");
+const DEPLOYMENT_ID_HEADER: HeaderName = HeaderName::from_static("x-central-deployment-id");
+const BUNDLE_SHA256_HEADER: HeaderName = HeaderName::from_static("x-bundle-sha256");
+const CONTENT_MD5_HEADER: HeaderName = HeaderName::from_static("content-md5");
+
+/// Fail with [RepositoryNotFound], answered with `404 Not Found`, for a repository never started
+pub(crate) async fn require_repository(
+    repository: &dyn Repository,
+    repository_key: &RepositoryKey,
+) -> Result<(), RepositoryNotFound> {
+    if repository.exists(repository_key).await {
+        Ok(())
+    } else {
+        Err(RepositoryNotFound {
+            repository_id: repository_key.get_repository_id(),
+        })
+    }
+}
+
+/// Fail with [RepositoryNotOpen], answered with `409 Conflict`, unless the repository can be closed
+pub(crate) async fn require_open_repository(
+    repository: &dyn Repository,
+    repository_key: &RepositoryKey,
+) -> eyre::Result<()> {
+    let state = repository.get_state(repository_key).await?;
+    if matches!(state, RepositoryState::Open) {
+        Ok(())
+    } else {
+        Err(RepositoryNotOpen {
+            repository_id: repository_key.get_repository_id(),
+            state: state.to_string(),
+        }
+        .into())
+    }
+}
+
+/// Note a read from the repository as activity, so that it is not removed as idle while in use
+pub(crate) async fn touch_repository(repository: &dyn Repository, repository_key: &RepositoryKey) {
+    if let Err(e) = repository.touch(repository_key).await {
+        tracing::warn!("Failed to note the activity on repository {repository_key}: {e:#}");
+    }
+}
+
+/// A repository was addressed through a staging profile other than the one it was started in
+#[derive(Debug)]
+pub(crate) struct RepositoryNotInProfile {
+    pub(crate) repository_id: String,
+    pub(crate) profile_id: String,
+}
+
+impl Display for RepositoryNotInProfile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Repository {} does not belong to staging profile {}",
+            self.repository_id, self.profile_id
+        )
+    }
+}
+
+impl std::error::Error for RepositoryNotInProfile {}
+
+/// Fail with [RepositoryNotInProfile], answered with `403 Forbidden`, unless the repository was started in the namespace
+pub(crate) fn require_profile(
+    repository_key: &RepositoryKey,
+    namespace: &str,
+    profile_id: &str,
+) -> Result<(), RepositoryNotInProfile> {
+    if repository_key.get_profile_id() == namespace {
+        Ok(())
+    } else {
+        Err(RepositoryNotInProfile {
+            repository_id: repository_key.get_repository_id(),
+            profile_id: profile_id.to_string(),
+        })
+    }
+}
+
+/// The ID that clients are given for the staging profile of a namespace
+///
+/// The namespace itself, unless numeric profile IDs are configured.
+pub(crate) async fn profile_id_for_namespace(
+    app_state: &AppState,
+    namespace: &str,
+) -> eyre::Result<String> {
+    if !app_state.app_config.numeric_profile_ids {
+        return Ok(namespace.to_string());
+    }
+
+    let profile_id = app_state.repository.numeric_profile_id(namespace).await?;
+    Ok(profile_id.to_string())
+}
+
+/// The namespace of a profile ID provided by a client, which may also be the namespace itself
+pub(crate) async fn namespace_for_profile_id(
+    app_state: &AppState,
+    profile_id: &str,
+) -> eyre::Result<String> {
+    let numeric_profile_id = profile_id
+        .parse::<u64>()
+        .ok()
+        .filter(|_| app_state.app_config.numeric_profile_ids);
+    let Some(numeric_profile_id) = numeric_profile_id else {
+        return Ok(profile_id.to_string());
+    };
+
+    app_state
+        .repository
+        .namespace_for_profile_id(numeric_profile_id)
+        .await?
+        .ok_or_else(|| eyre::eyre!("Unknown staging profile {profile_id}"))
+}
+
+/// The upload session a request belongs to, if the client provided one
+pub(crate) fn upload_session(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(UPLOAD_SESSION_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|session_id| !session_id.is_empty())
+}
+
+/// The MD5 digest that the client sent a request body with, if any
+///
+/// `Content-MD5` holds the base64 of the 16 byte digest, as described by RFC 1864.
+pub(crate) fn content_md5(headers: &HeaderMap) -> eyre::Result<Option<Vec<u8>>> {
+    let Some(content_md5) = headers.get(CONTENT_MD5_HEADER) else {
+        return Ok(None);
+    };
+
+    let digest = content_md5
+        .to_str()
+        .ok()
+        .and_then(|content_md5| BASE64_STANDARD.decode(content_md5.trim()).ok())
+        .filter(|digest| digest.len() == 16)
+        .ok_or_else(|| eyre::eyre!("Malformed Content-MD5 header: {content_md5:?}"))?;
+    Ok(Some(digest))
+}
+
+/// Pass the body through, failing at its end if it does not match the expected MD5 digest
+pub(crate) fn verify_content_md5<S>(
+    body: S,
+    expected_digest: Option<Vec<u8>>,
+) -> impl Stream<Item = eyre::Result<Bytes>>
+where
+    S: Stream<Item = eyre::Result<Bytes>> + Unpin,
+{
+    let digest = expected_digest.map(|expected_digest| (Md5::new(), expected_digest));
+    futures::stream::unfold((body, digest), |(mut body, mut digest)| async move {
+        match body.next().await {
+            Some(Ok(chunk)) => {
+                if let Some((md5, _)) = &mut digest {
+                    md5.update(&chunk);
+                }
+                Some((Ok(chunk), (body, digest)))
+            }
+            Some(Err(e)) => Some((Err(e), (body, None))),
+            None => {
+                let (md5, expected_digest) = digest?;
+                let actual_digest = md5.finalize();
+                if actual_digest.as_slice() == expected_digest.as_slice() {
+                    None
+                } else {
+                    let error = eyre::eyre!(
+                        "Content-MD5 mismatch: the header was {} but the body was {}",
+                        BASE64_STANDARD.encode(&expected_digest),
+                        BASE64_STANDARD.encode(actual_digest)
+                    );
+                    Some((Err(error), (body, None)))
+                }
+            }
+        }
+    })
+}
+
+/// The response to a successful hand-off of repositories to Central
+///
+/// Automatic publishes continue at Central after the upload, so they can be reported as accepted
+/// rather than complete, pointing the client to the status of the repository when there is only one.
+/// The Central deployment id and bundle checksum of each publish are included as headers.
+pub(crate) fn published_response(
+    app_config: &AppConfig,
+    publishing_type: PublishingType,
+    repository_ids: &[String],
+    outcomes: &[PublishOutcome],
+) -> Response {
+    let mut response = status_response(app_config, publishing_type, repository_ids);
+
+    let headers = response.headers_mut();
+    for outcome in outcomes {
+        if let Some(deployment_id) = &outcome.deployment_id {
+            match HeaderValue::from_str(deployment_id) {
+                Ok(value) => {
+                    headers.append(DEPLOYMENT_ID_HEADER, value);
+                }
+                Err(e) => tracing::warn!("Unable to return deployment id {deployment_id:?}: {e}"),
+            }
+        }
+        if let Ok(value) = HeaderValue::from_str(&outcome.bundle_sha256) {
+            headers.append(BUNDLE_SHA256_HEADER, value);
+        }
+    }
+
+    response
+}
+
+/// Replace the body of a published response with the files in the bundles, for a dry run
+///
+/// Lets a client or CI check that a bundle is complete before publishing it for real. Only clients
+/// that ask for XML or JSON get the listing.
+pub(crate) fn with_dry_run_preview(
+    headers: &HeaderMap,
+    response: Response,
+    repository_ids: &[String],
+    outcomes: &[PublishOutcome],
+) -> Response {
+    let dry_run = !outcomes.is_empty()
+        && outcomes
+            .iter()
+            .all(|outcome| outcome.status == PublishStatus::DryRun);
+    if !dry_run
+        || !matches!(
+            accept_content_type(headers),
+            Ok(ContentType::Xml | ContentType::Json)
+        )
+    {
+        return response;
+    }
+
+    let (parts, _) = response.into_parts();
+    let mut preview_response = respond_to_accepts_header(
+        headers,
+        BundlePreviewResponse::new(repository_ids, outcomes),
+    );
+    *preview_response.status_mut() = parts.status;
+    preview_response.headers_mut().extend(parts.headers);
+    preview_response
+}
+
+#[derive(Debug, Serialize, ex_em_ell::ToXmlDocument)]
+#[ex_em_ell(rename = "bundlePreview")]
+pub(crate) struct BundlePreviewResponse {
+    data: Vec<BundlePreview>,
+}
+
+#[derive(Debug, Serialize, ex_em_ell::ToXmlElement, ex_em_ell::NamedXmlElement)]
+#[serde(rename_all = "camelCase")]
+#[ex_em_ell(name = "bundle")]
+struct BundlePreview {
+    repository_id: String,
+    entries: Vec<BundlePreviewEntry>,
+}
+
+#[derive(Debug, Serialize, ex_em_ell::ToXmlElement, ex_em_ell::NamedXmlElement)]
+#[ex_em_ell(name = "entry")]
+struct BundlePreviewEntry {
+    path: String,
+    /// The uncompressed size in bytes
+    size: EntrySize,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(transparent)]
+struct EntrySize(u64);
+
+impl ex_em_ell::ToXmlElement for EntrySize {
+    fn to_xml_element<W: std::io::Write>(
+        &self,
+        writer: &mut ex_em_ell::xml::EventWriter<W>,
+        tag: &str,
+    ) -> Result<(), ex_em_ell::errors::XmlWriteError> {
+        ex_em_ell::xml_utils::write_simple_tag(writer, tag, &self.0.to_string())
+    }
+}
+
+impl BundlePreviewResponse {
+    fn new(repository_ids: &[String], outcomes: &[PublishOutcome]) -> Self {
+        Self {
+            data: repository_ids
+                .iter()
+                .zip(outcomes)
+                .map(|(repository_id, outcome)| BundlePreview {
+                    repository_id: repository_id.clone(),
+                    entries: outcome
+                        .bundle_entries
+                        .iter()
+                        .map(|entry| BundlePreviewEntry {
+                            path: entry.path.clone(),
+                            size: EntrySize(entry.size),
+                        })
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+}
+
+fn status_response(
+    app_config: &AppConfig,
+    publishing_type: PublishingType,
+    repository_ids: &[String],
+) -> Response {
+    if publishing_type != PublishingType::Automatic || !app_config.accept_automatic_publishes {
+        return StatusCode::OK.into_response();
+    }
+
+    // without a public base URL, the location is relative to the host that the client used
+    let base_url = app_config.base_url("");
+    match repository_ids {
+        [repository_id] => (
+            StatusCode::ACCEPTED,
+            [(
+                LOCATION,
+                format!("{base_url}/service/local/staging/repository/{repository_id}"),
+            )],
+        )
+            .into_response(),
+        _ => StatusCode::ACCEPTED.into_response(),
+    }
+}
+
+/// The `Content-Type` to serve a repository file with, based on its extension
+pub(crate) fn content_type(file_path: &Path) -> &'static str {
+    let extension = file_path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_ascii_lowercase());
+
+    match extension.as_deref() {
+        Some("pom" | "xml") => "application/xml",
+        Some("jar" | "war" | "ear" | "aar") => "application/java-archive",
+        Some("module" | "json") => "application/json",
+        Some("asc" | "md5" | "sha1" | "sha256" | "sha512" | "txt") => "text/plain",
+        Some("zip") => "application/zip",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Serve a file from a repository, or `404 Not Found` if it is not there
+///
+/// The file is streamed, so that large artifacts are never held in memory.
+pub(crate) fn file_response(
+    file_path: &Path,
+    stored_file: Option<StoredFile>,
+    file_metadata: Option<&FileMetadata>,
+) -> Response {
+    match stored_file {
+        Some(stored_file) => {
+            let mut response = (
+                [
+                    (CONTENT_TYPE, content_type(file_path).to_string()),
+                    (CONTENT_LENGTH, stored_file.size.to_string()),
+                ],
+                Body::from_stream(ReaderStream::new(stored_file.reader)),
+            )
+                .into_response();
+            insert_etag(response.headers_mut(), file_metadata);
+            response
+        }
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+/// Describe a file in a repository without its contents, or `404 Not Found` if it is not there
+pub(crate) fn file_head_response(
+    file_path: &Path,
+    file_metadata: Option<FileMetadata>,
+) -> Response {
+    match file_metadata {
+        Some(file_metadata) => {
+            let mut response = (
+                [
+                    (CONTENT_TYPE, content_type(file_path).to_string()),
+                    (CONTENT_LENGTH, file_metadata.size.to_string()),
+                ],
+                (),
+            )
+                .into_response();
+            insert_etag(response.headers_mut(), Some(&file_metadata));
+            response
+        }
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+/// `304 Not Modified` if the client already has the file, as told by its `If-None-Match`
+pub(crate) fn not_modified_response(
+    headers: &HeaderMap,
+    file_metadata: Option<&FileMetadata>,
+) -> Option<Response> {
+    let file_metadata = file_metadata?;
+    let if_none_match = headers.get(IF_NONE_MATCH)?.to_str().ok()?;
+    if !etag_matches(if_none_match, &file_metadata.etag()) {
+        return None;
+    }
+
+    let mut response = StatusCode::NOT_MODIFIED.into_response();
+    insert_etag(response.headers_mut(), Some(file_metadata));
+    Some(response)
+}
+
+/// Whether an `If-None-Match` list has the ETag, compared weakly as RFC 9110 has it
+fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    if_none_match.trim() == "*"
+        || if_none_match
+            .split(',')
+            .map(|candidate| candidate.trim())
+            .any(|candidate| candidate.strip_prefix("W/").unwrap_or(candidate) == etag)
+}
+
+fn insert_etag(headers: &mut HeaderMap, file_metadata: Option<&FileMetadata>) {
+    if let Some(etag) =
+        file_metadata.and_then(|file_metadata| HeaderValue::from_str(&file_metadata.etag()).ok())
+    {
+        headers.insert(ETAG, etag);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn automatic_publish_accepted_with_location() -> eyre::Result<()> {
+        let app_config = AppConfig::with_overrides(&[("accept_automatic_publishes", "true")])?;
+
+        let response = published_response(
+            &app_config,
+            PublishingType::Automatic,
+            &["comexample-1".to_string()],
+            &[],
+        );
+
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        assert_eq!(
+            response.headers().get(LOCATION),
+            Some(&"/service/local/staging/repository/comexample-1".parse()?)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn automatic_publish_location_under_public_base_url() -> eyre::Result<()> {
+        let app_config = AppConfig::with_overrides(&[
+            ("accept_automatic_publishes", "true"),
+            ("public_base_url", "https://repo.example.com/"),
+            ("base_path", "/nexus"),
+        ])?;
+
+        let response = published_response(
+            &app_config,
+            PublishingType::Automatic,
+            &["comexample-1".to_string()],
+            &[],
+        );
+
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        assert_eq!(
+            response.headers().get(LOCATION),
+            Some(
+                &"https://repo.example.com/nexus/service/local/staging/repository/comexample-1"
+                    .parse()?
+            )
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn automatic_publish_ok_by_default() -> eyre::Result<()> {
+        let app_config = AppConfig::with_overrides(&[])?;
+
+        let response = published_response(
+            &app_config,
+            PublishingType::Automatic,
+            &["comexample-1".to_string()],
+            &[],
+        );
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().get(LOCATION).is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn user_managed_publish_ok_when_accepting() -> eyre::Result<()> {
+        let app_config = AppConfig::with_overrides(&[("accept_automatic_publishes", "true")])?;
+
+        let response = published_response(
+            &app_config,
+            PublishingType::UserManaged,
+            &["comexample-1".to_string()],
+            &[],
+        );
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        Ok(())
+    }
+
+    #[test]
+    fn outcome_headers() -> eyre::Result<()> {
+        let app_config = AppConfig::with_overrides(&[])?;
+        let outcomes = [
+            PublishOutcome {
+                deployment_id: Some("deployment-1".to_string()),
+                status: PublishStatus::Uploaded,
+                bundle_sha256: "aaaa".to_string(),
+                file_count: 1,
+                bundle_entries: Vec::new(),
+            },
+            PublishOutcome {
+                deployment_id: Some("deployment-2".to_string()),
+                status: PublishStatus::Uploaded,
+                bundle_sha256: "bbbb".to_string(),
+                file_count: 1,
+                bundle_entries: Vec::new(),
+            },
+        ];
+
+        let response = published_response(
+            &app_config,
+            PublishingType::Automatic,
+            &["comexample-1".to_string(), "comexample-2".to_string()],
+            &outcomes,
+        );
+
+        let deployment_ids = response
+            .headers()
+            .get_all(DEPLOYMENT_ID_HEADER)
+            .iter()
+            .map(|value| value.to_str())
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(deployment_ids, ["deployment-1", "deployment-2"]);
+        assert_eq!(
+            response
+                .headers()
+                .get_all(BUNDLE_SHA256_HEADER)
+                .iter()
+                .count(),
+            2
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn bundle_preview_xml() -> eyre::Result<()> {
+        let outcome = PublishOutcome {
+            deployment_id: None,
+            status: PublishStatus::DryRun,
+            bundle_sha256: "aaaa".to_string(),
+            file_count: 1,
+            bundle_entries: vec![repository::traits::ZipEntry {
+                path: "com/example/example/0.1.0/example-0.1.0.pom".to_string(),
+                size: 11,
+            }],
+        };
+
+        let actual_xml = ex_em_ell::to_string_pretty(&BundlePreviewResponse::new(
+            &["comexample-1".to_string()],
+            &[outcome],
+        ))?;
+        let expected_xml = r#"<?xml version="1.0" encoding="utf-8"?>
+<bundlePreview>
+  <data>
+    <bundle>
+      <repositoryId>comexample-1</repositoryId>
+      <entries>
+        <entry>
+          <path>com/example/example/0.1.0/example-0.1.0.pom</path>
+          <size>11</size>
+        </entry>
+      </entries>
+    </bundle>
+  </data>
+</bundlePreview>"#;
+
+        assert_eq!(actual_xml, expected_xml);
+
+        Ok(())
+    }
+
+    fn stored_file(contents: &'static [u8]) -> StoredFile {
+        StoredFile {
+            reader: Box::pin(contents),
+            size: contents.len() as u64,
+        }
+    }
+
+    #[test]
+    fn serve_pom_as_xml() {
+        let response = file_response(
+            Path::new("com/example/example/0.1.0/example-0.1.0.pom"),
+            Some(stored_file(b"<project />")),
+            None,
+        );
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers().get(CONTENT_TYPE),
+            Some(&HeaderValue::from_static("application/xml"))
+        );
+    }
+
+    #[test]
+    fn serve_jar_as_java_archive() {
+        let response = file_response(
+            Path::new("com/example/example/0.1.0/example-0.1.0.jar"),
+            Some(stored_file(b"")),
+            None,
+        );
+
+        assert_eq!(
+            response.headers().get(CONTENT_TYPE),
+            Some(&HeaderValue::from_static("application/java-archive"))
+        );
+    }
+
+    #[test]
+    fn unknown_extension_is_octet_stream() {
+        assert_eq!(
+            content_type(Path::new("com/example/example/0.1.0/example-0.1.0.tar.gz")),
+            "application/octet-stream"
+        );
+        assert_eq!(
+            file_response(Path::new("example.jar"), None, None).status(),
+            StatusCode::NOT_FOUND
+        );
+    }
+
+    #[test]
+    fn match_if_none_match() {
+        let etag = "\"67b983e13c778959f437eb8ddc785b82aed2ce30\"";
+
+        assert!(etag_matches(etag, etag));
+        assert!(etag_matches(
+            "\"other\", W/\"67b983e13c778959f437eb8ddc785b82aed2ce30\"",
+            etag
+        ));
+        assert!(etag_matches("*", etag));
+        assert!(!etag_matches("\"other\"", etag));
+        assert!(!etag_matches(
+            "67b983e13c778959f437eb8ddc785b82aed2ce30",
+            etag
+        ));
+    }
+
+    #[test]
+    fn read_upload_session() -> eyre::Result<()> {
+        let mut headers = HeaderMap::new();
+        assert_eq!(upload_session(&headers), None);
+
+        headers.insert(UPLOAD_SESSION_HEADER, " ".parse()?);
+        assert_eq!(upload_session(&headers), None);
+
+        headers.insert(UPLOAD_SESSION_HEADER, "build-1".parse()?);
+        assert_eq!(upload_session(&headers), Some("build-1"));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,266 @@
+use axum::extract::{Path, State};
+use axum::http::HeaderMap;
+use axum::response::Response;
+use axum::Extension;
+use portal_api::api_types::PublishingType;
+use repository::traits::{RepositoryKey, RepositoryNotFound};
+use serde::Serialize;
+use tracing::instrument;
+
+use crate::auth::UserToken;
+use crate::errors::ApiError;
+use crate::extract::{respond_to_accepts_header, ClientIp};
+use crate::publish::retry_pending_upload;
+use crate::state::AppState;
+
+/// The user's uploads to Central that were interrupted, such as by a crash, and can be retried
+#[instrument(skip(headers, app_state, user_token), fields(user = user_token.token_username))]
+pub(crate) async fn pending_uploads_endpoint(
+    headers: HeaderMap,
+    State(app_state): State<AppState>,
+    Extension(user_token): Extension<UserToken>,
+) -> Result<Response, ApiError> {
+    tracing::debug!("Request to list the pending uploads");
+
+    let mut data = Vec::new();
+    if let Some(pending_uploads) = &app_state.pending_uploads {
+        for pending_upload in pending_uploads.list().await? {
+            if pending_upload.user_id == user_token.token_username {
+                data.push(PendingUploadResponse {
+                    repository_id: pending_upload.repository_id,
+                    deployment_name: pending_upload.deployment_name,
+                    publishing_type: PendingPublishingType(pending_upload.publishing_type),
+                });
+            }
+        }
+    }
+
+    Ok(respond_to_accepts_header(
+        &headers,
+        PendingUploadsResponse { data },
+    ))
+}
+
+#[derive(Debug, Serialize, ex_em_ell::ToXmlDocument)]
+#[ex_em_ell(rename = "pendingUploads")]
+pub(crate) struct PendingUploadsResponse {
+    data: Vec<PendingUploadResponse>,
+}
+
+#[derive(Debug, Serialize, ex_em_ell::ToXmlElement, ex_em_ell::NamedXmlElement)]
+#[serde(rename_all = "camelCase")]
+#[ex_em_ell(name = "pendingUpload")]
+struct PendingUploadResponse {
+    repository_id: String,
+    deployment_name: String,
+    publishing_type: PendingPublishingType,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(transparent)]
+struct PendingPublishingType(PublishingType);
+
+impl ex_em_ell::ToXmlElement for PendingPublishingType {
+    fn to_xml_element<W: std::io::Write>(
+        &self,
+        writer: &mut ex_em_ell::xml::EventWriter<W>,
+        tag: &str,
+    ) -> Result<(), ex_em_ell::errors::XmlWriteError> {
+        let publishing_type = match self.0 {
+            PublishingType::UserManaged => "USER_MANAGED",
+            PublishingType::Automatic => "AUTOMATIC",
+        };
+        ex_em_ell::xml_utils::write_simple_tag(writer, tag, publishing_type)
+    }
+}
+
+/// Upload a repository whose upload was interrupted, with the credentials of the request
+#[instrument(skip(headers, app_state, user_token), fields(user = user_token.token_username))]
+pub(crate) async fn retry_pending_upload_endpoint(
+    ClientIp(client_ip): ClientIp,
+    headers: HeaderMap,
+    Path(repository_id): Path<String>,
+    State(app_state): State<AppState>,
+    Extension(user_token): Extension<UserToken>,
+) -> Result<Response, ApiError> {
+    tracing::debug!("Request to retry a pending upload");
+
+    let repository_key = RepositoryKey::from_user_context_and_repository_id(
+        &user_token.token_username,
+        &client_ip,
+        &repository_id,
+    )?;
+    let pending = match &app_state.pending_uploads {
+        Some(pending_uploads) => pending_uploads
+            .get(&repository_key)
+            .await?
+            .map(|pending_upload| (pending_uploads, pending_upload)),
+        None => None,
+    };
+    let Some((pending_uploads, pending_upload)) = pending else {
+        return Err(RepositoryNotFound { repository_id }.into());
+    };
+
+    let deployment_id = retry_pending_upload(
+        &app_state.portal_api_client,
+        app_state.repository.as_ref(),
+        pending_uploads,
+        &pending_upload,
+        &user_token.into_credentials(),
+    )
+    .await?;
+
+    Ok(respond_to_accepts_header(
+        &headers,
+        RetriedUploadResponse {
+            data: RetriedUploadResponseData {
+                repository_id,
+                deployment_id,
+            },
+        },
+    ))
+}
+
+#[derive(Debug, Serialize, ex_em_ell::ToXmlDocument)]
+#[ex_em_ell(rename = "retriedUpload")]
+pub(crate) struct RetriedUploadResponse {
+    data: RetriedUploadResponseData,
+}
+
+#[derive(Debug, Serialize, ex_em_ell::ToXmlElement)]
+#[serde(rename_all = "camelCase")]
+struct RetriedUploadResponseData {
+    repository_id: String,
+    deployment_id: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use axum::body::Body;
+    use axum::extract::connect_info::MockConnectInfo;
+    use axum::http::header::ACCEPT;
+    use axum::http::{Request, StatusCode};
+    use axum::routing::{get, post};
+    use axum::Router;
+    use base64::prelude::{Engine, BASE64_STANDARD};
+    use eyre::OptionExt;
+    use portal_api::PortalApiClient;
+    use repository::local_repository::LocalRepository;
+    use temp_dir::TempDir;
+    use tower::ServiceExt;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::*;
+    use crate::config::AppConfig;
+    use crate::publish::tests::stage_jar;
+
+    #[tokio::test]
+    async fn list_and_retry_pending_uploads() -> eyre::Result<()> {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/api/v1/publisher/upload"))
+            .respond_with(ResponseTemplate::new(201).set_body_string("test_deployment_id"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let pending_upload_dir = TempDir::new()?;
+        let app_state = AppState::new(
+            LocalRepository::new()?,
+            PortalApiClient::client(&mock_server.uri())?,
+            AppConfig::with_overrides(&[(
+                "pending_upload_dir",
+                &pending_upload_dir.path().to_string_lossy(),
+            )])?,
+        );
+        let pending_uploads = app_state
+            .pending_uploads
+            .clone()
+            .ok_or_eyre("pending uploads are configured")?;
+
+        // a repository that was closed, but whose upload never completed
+        let repository_key = stage_jar(app_state.repository.as_ref()).await?;
+        let bundle = app_state.repository.finish(&repository_key).await?;
+        pending_uploads
+            .record(
+                &repository_key,
+                "test_deployment",
+                PublishingType::Automatic,
+                &bundle.as_buffer()?,
+            )
+            .await?;
+
+        let addr = SocketAddr::from(([127, 0, 0, 1], 2727));
+        let user_token = UserToken::from_token(&BASE64_STANDARD.encode("test_user:test_password"))?;
+        let app = Router::new()
+            .route("/pending_uploads", get(pending_uploads_endpoint))
+            .route(
+                "/pending_uploads/:repository_id/retry",
+                post(retry_pending_upload_endpoint),
+            )
+            .with_state(app_state.clone())
+            .layer(Extension(user_token))
+            .layer(MockConnectInfo(addr));
+        let send = |request: axum::http::request::Builder, accept: &'static str| {
+            let app = app.clone();
+            async move {
+                let response = app
+                    .oneshot(request.header(ACCEPT, accept).body(Body::empty())?)
+                    .await?;
+                let status = response.status();
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX).await?;
+                eyre::Ok((status, String::from_utf8(body.to_vec())?))
+            }
+        };
+
+        let (status, body) = send(Request::get("/pending_uploads"), "application/json").await?;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&body)?,
+            serde_json::json!({"data": [{
+                "repositoryId": "comexample-0",
+                "deploymentName": "test_deployment",
+                "publishingType": "AUTOMATIC",
+            }]})
+        );
+
+        let (status, body) = send(Request::get("/pending_uploads"), "application/xml").await?;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("<pendingUploads>"), "{body}");
+        assert!(body.contains("<repositoryId>comexample-0</repositoryId>"));
+        assert!(body.contains("<publishingType>AUTOMATIC</publishingType>"));
+
+        let (status, _) = send(
+            Request::post("/pending_uploads/comexample-1/retry"),
+            "application/json",
+        )
+        .await?;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, body) = send(
+            Request::post("/pending_uploads/comexample-0/retry"),
+            "application/json",
+        )
+        .await?;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&body)?,
+            serde_json::json!({"data": {
+                "repositoryId": "comexample-0",
+                "deploymentId": "test_deployment_id",
+            }})
+        );
+
+        let (_, body) = send(Request::get("/pending_uploads"), "application/json").await?;
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&body)?,
+            serde_json::json!({"data": []})
+        );
+        mock_server.verify().await;
+
+        Ok(())
+    }
+}
@@ -1,7 +1,6 @@
-use std::net::SocketAddr;
 use std::ops::Deref;
 
-use axum::extract::{ConnectInfo, Host, Path, Query, Request, State};
+use axum::extract::{Host, Path, Query, Request, State};
 use axum::http::{HeaderMap, StatusCode};
 use axum::response::{IntoResponse, Response};
 use axum::Extension;
@@ -9,26 +8,53 @@ use axum_extra::headers::UserAgent;
 use axum_extra::TypedHeader;
 use futures::stream::TryStreamExt;
 use itertools::Itertools;
-use portal_api::api_types::PublishingType;
-use repository::traits::{Repository, RepositoryKey, RepositoryState};
+use portal_api::api_types::{DeploymentState, PublishingType};
+use repository::namespaces::NamespaceSet;
+use repository::traits::{sanitize_artifact_path, RepositoryKey, RepositoryState};
 use serde::{ser::SerializeMap, Deserialize, Serialize};
-use tracing::instrument;
+use time::{OffsetDateTime, UtcOffset};
+use tracing::{instrument, Span};
 
 use crate::auth::UserToken;
+use crate::config::AppConfig;
+use crate::endpoints::{
+    content_md5, file_head_response, file_response, namespace_for_profile_id,
+    not_modified_response, profile_id_for_namespace, published_response, require_open_repository,
+    require_profile, require_repository, touch_repository, upload_session, verify_content_md5,
+    with_dry_run_preview,
+};
 use crate::errors::ApiError;
-use crate::extract::{respond_to_accepts_header, XmlOrJson};
-use crate::publish::publish;
+use crate::extract::{respond_to_accepts_header, ClientIp, XmlOrJson};
+use crate::publish::{publish, refresh_deployment_state};
 use crate::state::AppState;
 
-#[instrument(skip(headers))]
+pub(crate) const DEFAULT_STAGING_PROFILE_MODE: &str = "BOTH";
+pub(crate) const DEFAULT_STAGING_PROFILE_POLICY: &str = "release";
+pub(crate) const DEFAULT_STAGING_PROFILE_REPOSITORY_TYPE: &str = "maven2";
+pub(crate) const DEFAULT_STAGING_PROFILE_RELEASE_REPOSITORY: &str = "releases";
+
+#[instrument(skip(headers, app_state))]
 pub(crate) async fn staging_profile_evaluate_endpoint(
     Host(host): Host,
     TypedHeader(_user_agent): TypedHeader<UserAgent>,
     headers: HeaderMap,
     Query(query): Query<StagingProfileEvaluateQueryParams>,
+    State(app_state): State<AppState>,
 ) -> Result<Response, ApiError> {
     tracing::debug!("Request to match staging profiles");
-    let staging_profile_evaluate = StagingProfilesEvaluateResponse::new(host, query.group);
+    let staging_profile_evaluate =
+        match profile_namespace(&query.group, &app_state.app_config.namespaces()) {
+            Some(namespace) => {
+                let profile_id = profile_id_for_namespace(&app_state, &namespace).await?;
+                StagingProfilesEvaluateResponse::new(
+                    app_state.app_config.base_url(&host),
+                    profile_id,
+                    namespace,
+                    &app_state.app_config,
+                )
+            }
+            None => StagingProfilesEvaluateResponse { data: Vec::new() },
+        };
 
     Ok(respond_to_accepts_header(
         &headers,
@@ -48,48 +74,79 @@ pub(crate) struct StagingProfileEvaluateQueryParams {
     group: String,
 }
 
-#[instrument(skip(headers))]
+#[instrument(skip(headers, app_state))]
 pub(crate) async fn staging_profiles_list_endpoint(
     Host(host): Host,
     TypedHeader(_user_agent): TypedHeader<UserAgent>,
     headers: HeaderMap,
+    State(app_state): State<AppState>,
 ) -> Result<Response, ApiError> {
-    tracing::debug!("Request to get staging profile");
-    let staging_profiles =
-        StagingProfilesEvaluateResponse::new(host, "io.github.amy-keibler".to_string()); // TODO: this is hardcoded
+    tracing::debug!("Request to list staging profiles");
+    let configured_namespaces = app_state.app_config.namespaces();
+    let namespaces = if configured_namespaces.is_empty() {
+        vec!["io.github.amy-keibler".to_string()] // TODO: this is hardcoded
+    } else {
+        configured_namespaces.iter().map(String::from).collect()
+    };
+    let mut profiles = Vec::with_capacity(namespaces.len());
+    for namespace in namespaces {
+        let profile_id = profile_id_for_namespace(&app_state, &namespace).await?;
+        profiles.push((profile_id, namespace));
+    }
+    let staging_profiles = StagingProfilesEvaluateResponse::from_profiles(
+        app_state.app_config.base_url(&host),
+        profiles,
+        &app_state.app_config,
+    );
 
     Ok(respond_to_accepts_header(&headers, staging_profiles))
 }
 
-#[instrument(skip(headers))]
+#[instrument(skip(headers, app_state))]
 pub(crate) async fn staging_profiles_endpoint(
     Host(host): Host,
     TypedHeader(_user_agent): TypedHeader<UserAgent>,
     headers: HeaderMap,
     Path(profile_id): Path<String>,
+    State(app_state): State<AppState>,
 ) -> Result<Response, ApiError> {
     tracing::debug!("Request to get staging profile");
-    let staging_profiles = StagingProfilesResponse::new(host, profile_id);
+    let namespace = namespace_for_profile_id(&app_state, &profile_id).await?;
+    let staging_profiles = StagingProfilesResponse::new(
+        app_state.app_config.base_url(&host),
+        profile_id,
+        namespace,
+        &app_state.app_config,
+    );
 
     Ok(respond_to_accepts_header(&headers, staging_profiles))
 }
 
+#[allow(clippy::too_many_arguments)]
 #[instrument(skip(headers, app_state, user_token, staging_profiles_start_request))]
-pub(crate) async fn staging_profiles_start_endpoint<R: Repository>(
+pub(crate) async fn staging_profiles_start_endpoint(
     Host(host): Host,
-    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    ClientIp(client_ip): ClientIp,
     TypedHeader(_user_agent): TypedHeader<UserAgent>,
     headers: HeaderMap,
     Path(profile_id): Path<String>,
-    State(app_state): State<AppState<R>>,
+    State(app_state): State<AppState>,
     Extension(user_token): Extension<UserToken>,
     XmlOrJson(staging_profiles_start_request): XmlOrJson<StagingProfilesStartRequest>,
 ) -> Result<Response, ApiError> {
     tracing::debug!("Request to start staging profile");
 
+    let namespace = namespace_for_profile_id(&app_state, &profile_id).await?;
+    let namespaces = app_state.app_config.namespaces();
+    if !namespaces.is_empty() && !namespaces.authorizes(&namespace) {
+        return Err(ApiError(eyre::eyre!(
+            "Not authorized to publish to namespace {namespace}"
+        )));
+    }
+
     let repository = app_state
         .repository
-        .start(&user_token.token_username, &addr.ip(), &profile_id)
+        .start(&user_token.token_username, &client_ip, &namespace)
         .await?;
 
     let staging_profiles_start_response = StagingProfilesPromoteResponse::new(
@@ -117,11 +174,11 @@ pub(crate) struct StagingProfilesStartRequestData {
 }
 
 #[instrument(skip(app_state, user_token, request))]
-pub(crate) async fn staging_deploy_by_repository_id<R: Repository>(
+pub(crate) async fn staging_deploy_by_repository_id(
     TypedHeader(_user_agent): TypedHeader<UserAgent>,
-    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    ClientIp(client_ip): ClientIp,
     Path((repository_id, file_path)): Path<(String, String)>,
-    State(app_state): State<AppState<R>>,
+    State(app_state): State<AppState>,
     Extension(user_token): Extension<UserToken>,
     request: Request,
 ) -> Result<impl IntoResponse, ApiError> {
@@ -134,65 +191,189 @@ pub(crate) async fn staging_deploy_by_repository_id<R: Repository>(
 
     let repository_key = RepositoryKey::from_user_context_and_repository_id(
         &user_token.token_username,
-        &addr.ip(),
+        &client_ip,
         &repository_id,
     )?;
 
+    let file_path = sanitize_artifact_path(&file_path)?;
+    require_repository(app_state.repository.deref(), &repository_key).await?;
+    let content_md5 = content_md5(request.headers())?;
+
     app_state
         .repository
         .add_file(
             &repository_key,
-            file_path,
-            request
-                .into_body()
-                .into_data_stream()
-                .map_err(|e| eyre::eyre!("Issue with the request body: {e}")),
+            &file_path,
+            Box::pin(verify_content_md5(
+                request
+                    .into_body()
+                    .into_data_stream()
+                    .map_err(|e| eyre::eyre!("Issue with the request body: {e}")),
+                content_md5,
+            )),
         )
         .await?;
 
     Ok(StatusCode::CREATED)
 }
 
-#[instrument]
+#[instrument(skip(headers, app_state, user_token))]
 pub(crate) async fn staging_deploy_by_repository_id_get(
     TypedHeader(_user_agent): TypedHeader<UserAgent>,
+    ClientIp(client_ip): ClientIp,
+    headers: HeaderMap,
     Path((repository_id, file_path)): Path<(String, String)>,
-) -> Result<impl IntoResponse, ApiError> {
+    State(app_state): State<AppState>,
+    Extension(user_token): Extension<UserToken>,
+) -> Result<Response, ApiError> {
     tracing::debug!("Request to get file from a staging repository");
 
-    Ok(StatusCode::NOT_FOUND)
+    let repository_key = RepositoryKey::from_user_context_and_repository_id(
+        &user_token.token_username,
+        &client_ip,
+        &repository_id,
+    )?;
+
+    let file_path = sanitize_artifact_path(&file_path)?;
+    let file_metadata = app_state
+        .repository
+        .file_metadata(&repository_key, &file_path)
+        .await?;
+    if file_metadata.is_some() {
+        touch_repository(app_state.repository.deref(), &repository_key).await;
+    }
+    if let Some(response) = not_modified_response(&headers, file_metadata.as_ref()) {
+        return Ok(response);
+    }
+    let stored_file = app_state
+        .repository
+        .open_file(&repository_key, &file_path)
+        .await?;
+
+    Ok(file_response(
+        &file_path,
+        stored_file,
+        file_metadata.as_ref(),
+    ))
+}
+
+#[instrument(skip(headers, app_state, user_token))]
+pub(crate) async fn staging_deploy_by_repository_id_head(
+    TypedHeader(_user_agent): TypedHeader<UserAgent>,
+    ClientIp(client_ip): ClientIp,
+    headers: HeaderMap,
+    Path((repository_id, file_path)): Path<(String, String)>,
+    State(app_state): State<AppState>,
+    Extension(user_token): Extension<UserToken>,
+) -> Result<Response, ApiError> {
+    tracing::debug!("Request to check for a file in a staging repository");
+
+    let repository_key = RepositoryKey::from_user_context_and_repository_id(
+        &user_token.token_username,
+        &client_ip,
+        &repository_id,
+    )?;
+
+    let file_path = sanitize_artifact_path(&file_path)?;
+    let file_metadata = app_state
+        .repository
+        .file_metadata(&repository_key, &file_path)
+        .await?;
+    if file_metadata.is_some() {
+        touch_repository(app_state.repository.deref(), &repository_key).await;
+    }
+    if let Some(response) = not_modified_response(&headers, file_metadata.as_ref()) {
+        return Ok(response);
+    }
+
+    Ok(file_head_response(&file_path, file_metadata))
+}
+
+/// Discard an open repository, such as after a build fails partway through publishing
+#[instrument(skip(app_state, user_token))]
+pub(crate) async fn staging_deploy_by_repository_id_delete(
+    TypedHeader(_user_agent): TypedHeader<UserAgent>,
+    ClientIp(client_ip): ClientIp,
+    Path(repository_id): Path<String>,
+    State(app_state): State<AppState>,
+    Extension(user_token): Extension<UserToken>,
+) -> Result<StatusCode, ApiError> {
+    tracing::debug!("Request to drop a staging repository");
+
+    let repository_key = RepositoryKey::from_user_context_and_repository_id(
+        &user_token.token_username,
+        &client_ip,
+        &repository_id,
+    )?;
+    require_repository(app_state.repository.deref(), &repository_key).await?;
+
+    app_state.repository.abort(&repository_key).await?;
+
+    Ok(StatusCode::NO_CONTENT)
 }
 
-#[instrument(skip(app_state, user_token, staging_profiles_finish_request))]
-pub(crate) async fn staging_profiles_finish_endpoint<R: Repository>(
+#[allow(clippy::too_many_arguments)]
+#[instrument(
+    skip(headers, app_state, user_token, staging_profiles_finish_request),
+    fields(
+        repository_id = staging_profiles_finish_request.data.staged_repository_id,
+        deployment_id
+    )
+)]
+pub(crate) async fn staging_profiles_finish_endpoint(
     Host(host): Host,
-    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    ClientIp(client_ip): ClientIp,
     TypedHeader(_user_agent): TypedHeader<UserAgent>,
+    headers: HeaderMap,
     Path(profile_id): Path<String>,
-    State(app_state): State<AppState<R>>,
+    State(app_state): State<AppState>,
     Extension(user_token): Extension<UserToken>,
     XmlOrJson(staging_profiles_finish_request): XmlOrJson<StagingProfilesFinishRequest>,
-) -> Result<StatusCode, ApiError> {
-    tracing::debug!("Request to finish profile");
+) -> Result<Response, ApiError> {
+    tracing::debug!(
+        target_repository_id = staging_profiles_finish_request.data.target_repository_id,
+        "Request to finish profile"
+    );
 
     let repository_key = RepositoryKey::from_user_context_and_repository_id(
         &user_token.token_username,
-        &addr.ip(),
+        &client_ip,
         &staging_profiles_finish_request.data.staged_repository_id,
     )?;
+    require_repository(app_state.repository.deref(), &repository_key).await?;
+    let namespace = namespace_for_profile_id(&app_state, &profile_id).await?;
+    require_profile(&repository_key, &namespace, &profile_id)?;
 
-    let credentials = user_token.as_credentials();
+    let credentials = user_token.into_credentials();
 
-    publish(
+    let outcome = publish(
         &app_state.portal_api_client,
         app_state.repository.deref(),
         &credentials,
         &repository_key,
         PublishingType::Automatic,
+        &app_state.publish_options(),
     )
     .await?;
+    if let Some(deployment_id) = &outcome.deployment_id {
+        Span::current().record("deployment_id", deployment_id);
+    }
 
-    Ok(StatusCode::OK)
+    let repository_ids = [repository_key.get_repository_id()];
+    let outcomes = [outcome];
+    let response = published_response(
+        &app_state.app_config,
+        PublishingType::Automatic,
+        &repository_ids,
+        &outcomes,
+    );
+
+    Ok(with_dry_run_preview(
+        &headers,
+        response,
+        &repository_ids,
+        &outcomes,
+    ))
 }
 
 #[derive(Debug, PartialEq, Deserialize, ex_em_ell::FromXmlDocument)]
@@ -202,44 +383,281 @@ pub(crate) struct StagingProfilesFinishRequest {
     data: StagingProfilesFinishRequestData,
 }
 
-#[derive(Debug, PartialEq, Deserialize, ex_em_ell::FromXmlElement)]
+/// Fields beyond these, which some clients send, are ignored
+#[derive(Debug, PartialEq, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct StagingProfilesFinishRequestData {
     staged_repository_id: String,
     description: String,
+    /// Where NXRM2 would release the repository to, which is always Central here
+    #[serde(default)]
+    target_repository_id: Option<String>,
+    /// Only honored when promoting, since finishing keeps the repository for its status to be polled
+    #[serde(default)]
+    auto_drop_after_release: bool,
+}
+
+impl ex_em_ell::FromXmlElement for StagingProfilesFinishRequestData {
+    fn from_xml_element<R: std::io::Read>(
+        reader: &mut ex_em_ell::xml::EventReader<R>,
+        element_name: &ex_em_ell::xml::name::OwnedName,
+        _element_attributes: &[ex_em_ell::xml::attribute::OwnedAttribute],
+        _element_namespace: &ex_em_ell::xml::namespace::Namespace,
+    ) -> Result<Self, ex_em_ell::errors::XmlReadError>
+    where
+        Self: Sized,
+    {
+        let mut staged_repository_id = None;
+        let mut description = None;
+        let mut target_repository_id = None;
+        let mut auto_drop_after_release = false;
+
+        loop {
+            let next_element = reader
+                .next()
+                .map_err(ex_em_ell::xml_utils::to_xml_read_error(
+                    element_name.to_string(),
+                ))?;
+            match next_element {
+                ex_em_ell::xml::reader::XmlEvent::StartElement {
+                    name,
+                    attributes,
+                    namespace,
+                } => match name.local_name.as_str() {
+                    "stagedRepositoryId" => {
+                        staged_repository_id =
+                            Some(ex_em_ell::xml_utils::read_simple_tag(reader, &name)?)
+                    }
+                    "description" => {
+                        description = Some(ex_em_ell::xml_utils::read_simple_tag(reader, &name)?)
+                    }
+                    "targetRepositoryId" => {
+                        target_repository_id =
+                            Some(ex_em_ell::xml_utils::read_simple_tag(reader, &name)?)
+                    }
+                    "autoDropAfterRelease" => {
+                        auto_drop_after_release = ex_em_ell::FromXmlElement::from_xml_element(
+                            reader,
+                            &name,
+                            &attributes,
+                            &namespace,
+                        )?
+                    }
+                    _ => skip_xml_element(reader, &name)?,
+                },
+                ex_em_ell::xml::reader::XmlEvent::EndElement { name } if &name == element_name => {
+                    break
+                }
+                unexpected => {
+                    return Err(ex_em_ell::xml_utils::unexpected_element_error(
+                        element_name,
+                        unexpected,
+                    ))
+                }
+            }
+        }
+
+        let required = |field: Option<String>, required_field: &str| {
+            field.ok_or_else(|| ex_em_ell::errors::XmlReadError::RequiredDataMissing {
+                required_field: required_field.to_string(),
+                element: element_name.to_string(),
+            })
+        };
+
+        Ok(Self {
+            staged_repository_id: required(staged_repository_id, "stagedRepositoryId")?,
+            description: required(description, "description")?,
+            target_repository_id,
+            auto_drop_after_release,
+        })
+    }
+}
+
+/// Read past an element that has just been started, along with everything in it
+fn skip_xml_element<R: std::io::Read>(
+    reader: &mut ex_em_ell::xml::EventReader<R>,
+    element_name: &ex_em_ell::xml::name::OwnedName,
+) -> Result<(), ex_em_ell::errors::XmlReadError> {
+    let mut depth = 1;
+    while depth > 0 {
+        let next_element = reader
+            .next()
+            .map_err(ex_em_ell::xml_utils::to_xml_read_error(
+                element_name.to_string(),
+            ))?;
+        match next_element {
+            ex_em_ell::xml::reader::XmlEvent::StartElement { .. } => depth += 1,
+            ex_em_ell::xml::reader::XmlEvent::EndElement { .. } => depth -= 1,
+            _ => {}
+        }
+    }
+
+    Ok(())
 }
 
 #[instrument(skip(headers, app_state, user_token))]
-pub(crate) async fn staging_repository<R: Repository>(
+pub(crate) async fn staging_repository(
     Host(host): Host,
-    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    ClientIp(client_ip): ClientIp,
     TypedHeader(_user_agent): TypedHeader<UserAgent>,
     headers: HeaderMap,
     Path(repository_id): Path<String>,
-    State(app_state): State<AppState<R>>,
+    State(app_state): State<AppState>,
     Extension(user_token): Extension<UserToken>,
 ) -> Result<Response, ApiError> {
     tracing::debug!("Request to get repository");
 
     let repository_key = RepositoryKey::from_user_context_and_repository_id(
         &user_token.token_username,
-        &addr.ip(),
+        &client_ip,
         &repository_id,
     )?;
 
     let repository_state = app_state.repository.get_state(&repository_key).await?;
 
-    let response = StagingRepositoryResponse::new(&host, &repository_id, repository_state);
+    let mut repository_errors = match repository_state {
+        RepositoryState::NotFound => Vec::new(),
+        _ => app_state.repository.get_errors(&repository_key).await?,
+    };
+
+    // follow the deployment at Central until it has settled or reported problems
+    let mut transitioning = false;
+    if matches!(
+        repository_state,
+        RepositoryState::Closed | RepositoryState::Released
+    ) && repository_errors.is_empty()
+    {
+        let credentials = user_token.into_credentials();
+        match refresh_deployment_state(
+            &app_state.portal_api_client,
+            app_state.repository.deref(),
+            &credentials,
+            &repository_key,
+        )
+        .await
+        {
+            Ok(Some(DeploymentState::Failed)) => {
+                repository_errors = app_state.repository.get_errors(&repository_key).await?;
+            }
+            Ok(Some(deployment_state)) => {
+                transitioning = matches!(
+                    deployment_state,
+                    DeploymentState::Pending
+                        | DeploymentState::Validating
+                        | DeploymentState::Publishing
+                );
+            }
+            Ok(None) => {}
+            Err(e) => tracing::warn!("Unable to check the deployment at Central: {e:#}"),
+        }
+    }
+
+    let deployment_id = match repository_state {
+        RepositoryState::NotFound => None,
+        _ => {
+            app_state
+                .repository
+                .get_deployment_id(&repository_key)
+                .await?
+        }
+    };
+
+    let response = StagingRepositoryResponse::new(
+        &app_state.app_config.base_url(&host),
+        &repository_id,
+        repository_state,
+        repository_errors,
+        transitioning,
+        deployment_id.as_deref(),
+    );
+
+    Ok(respond_to_accepts_header(&headers, response))
+}
+
+#[instrument(skip(headers, app_state, user_token))]
+pub(crate) async fn staging_profile_repositories(
+    Host(host): Host,
+    ClientIp(client_ip): ClientIp,
+    TypedHeader(_user_agent): TypedHeader<UserAgent>,
+    headers: HeaderMap,
+    Path(profile_id): Path<String>,
+    State(app_state): State<AppState>,
+    Extension(user_token): Extension<UserToken>,
+) -> Result<Response, ApiError> {
+    tracing::debug!("Request to list the profile's repositories");
+
+    let repositories = app_state
+        .repository
+        .list_repositories(&user_token.token_username, &client_ip)
+        .await?;
+
+    let namespace = namespace_for_profile_id(&app_state, &profile_id).await?;
+    let mut staging_repositories = Vec::new();
+    for (repository_key, repository_state) in repositories {
+        if repository_key.get_profile_id() != namespace {
+            continue;
+        }
+
+        let repository_errors = app_state.repository.get_errors(&repository_key).await?;
+        let deployment_id = app_state
+            .repository
+            .get_deployment_id(&repository_key)
+            .await?;
+        staging_repositories.push(StagingRepositoryResponse::new(
+            &app_state.app_config.base_url(&host),
+            &repository_key.get_repository_id(),
+            repository_state,
+            repository_errors,
+            false,
+            deployment_id.as_deref(),
+        ));
+    }
+
+    let response = StagingRepositoriesResponse {
+        data: staging_repositories,
+    };
+
+    Ok(respond_to_accepts_header(&headers, response))
+}
+
+#[instrument(skip(headers, app_state, user_token))]
+pub(crate) async fn staging_repository_activity(
+    ClientIp(client_ip): ClientIp,
+    TypedHeader(_user_agent): TypedHeader<UserAgent>,
+    headers: HeaderMap,
+    Path(repository_id): Path<String>,
+    State(app_state): State<AppState>,
+    Extension(user_token): Extension<UserToken>,
+) -> Result<Response, ApiError> {
+    tracing::debug!("Request to get repository activity");
+
+    let repository_key = RepositoryKey::from_user_context_and_repository_id(
+        &user_token.token_username,
+        &client_ip,
+        &repository_id,
+    )?;
+    require_repository(app_state.repository.deref(), &repository_key).await?;
+
+    let activity = app_state.repository.get_activity(&repository_key).await?;
+    let response = StagingActivitiesResponse::new(&repository_id, activity);
 
     Ok(respond_to_accepts_header(&headers, response))
 }
 
-#[instrument(skip(app_state, user_token, staging_bulk_promote_request))]
-pub(crate) async fn staging_bulk_promote<R: Repository>(
+#[instrument(
+    skip(app_state, user_token, staging_bulk_promote_request),
+    fields(repository_ids = staging_bulk_promote_request
+        .data
+        .staged_repository_ids
+        .iter()
+        .map(|ws| &ws.0)
+        .join(","))
+)]
+pub(crate) async fn staging_bulk_promote(
     Host(host): Host,
-    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    ClientIp(client_ip): ClientIp,
     TypedHeader(_user_agent): TypedHeader<UserAgent>,
-    State(app_state): State<AppState<R>>,
+    State(app_state): State<AppState>,
     Extension(user_token): Extension<UserToken>,
 
     XmlOrJson(staging_bulk_promote_request): XmlOrJson<StagingBulkPromoteRequest>,
@@ -254,14 +672,25 @@ pub(crate) async fn staging_bulk_promote<R: Repository>(
             .join(", ")
     );
 
+    let mut repository_keys = Vec::new();
     for repository_id in staging_bulk_promote_request.data.staged_repository_ids {
         let repository_key = RepositoryKey::from_user_context_and_repository_id(
             &user_token.token_username,
-            &addr.ip(),
+            &client_ip,
             &repository_id.0,
         )?;
+        require_repository(app_state.repository.deref(), &repository_key).await?;
+        repository_keys.push(repository_key);
+    }
 
+    for repository_key in repository_keys {
         app_state.repository.release(&repository_key).await?;
+        if staging_bulk_promote_request.data.auto_drop_after_release {
+            app_state
+                .repository
+                .drop_repository(&repository_key)
+                .await?;
+        }
     }
 
     Ok(StatusCode::OK)
@@ -282,15 +711,24 @@ pub(crate) struct StagingBulkPromoteRequestData {
     auto_drop_after_release: bool,
 }
 
-#[instrument(skip(app_state, user_token, staging_bulk_close_request))]
-pub(crate) async fn staging_bulk_close<R: Repository>(
+#[instrument(
+    skip(headers, app_state, user_token, staging_bulk_close_request),
+    fields(repository_ids = staging_bulk_close_request
+        .data
+        .staged_repository_ids
+        .iter()
+        .map(|ws| &ws.0)
+        .join(","))
+)]
+pub(crate) async fn staging_bulk_close(
     Host(host): Host,
-    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    ClientIp(client_ip): ClientIp,
     TypedHeader(_user_agent): TypedHeader<UserAgent>,
-    State(app_state): State<AppState<R>>,
+    headers: HeaderMap,
+    State(app_state): State<AppState>,
     Extension(user_token): Extension<UserToken>,
     XmlOrJson(staging_bulk_close_request): XmlOrJson<StagingBulkPromoteRequest>,
-) -> Result<StatusCode, ApiError> {
+) -> Result<Response, ApiError> {
     tracing::debug!(
         "Request to bulk close repositories: {}",
         staging_bulk_close_request
@@ -303,69 +741,185 @@ pub(crate) async fn staging_bulk_close<R: Repository>(
 
     let username = user_token.token_username.clone();
 
-    let credentials = user_token.as_credentials();
+    let credentials = user_token.into_credentials();
+    let publish_options = app_state.publish_options();
 
-    for repository_id in staging_bulk_close_request.data.staged_repository_ids {
+    let repository_ids = staging_bulk_close_request
+        .data
+        .staged_repository_ids
+        .into_iter()
+        .map(|ws| ws.0)
+        .collect::<Vec<_>>();
+
+    // check every repository before publishing any of them
+    let mut repository_keys = Vec::with_capacity(repository_ids.len());
+    for repository_id in &repository_ids {
         let repository_key = RepositoryKey::from_user_context_and_repository_id(
             &username,
-            &addr.ip(),
-            &repository_id.0,
+            &client_ip,
+            repository_id,
         )?;
+        require_repository(app_state.repository.deref(), &repository_key).await?;
+        require_open_repository(app_state.repository.deref(), &repository_key).await?;
+        repository_keys.push(repository_key);
+    }
 
-        publish(
+    let mut outcomes = Vec::with_capacity(repository_ids.len());
+    for repository_key in &repository_keys {
+        let outcome = publish(
             &app_state.portal_api_client,
             app_state.repository.deref(),
             &credentials,
-            &repository_key,
+            repository_key,
             PublishingType::Automatic,
+            &publish_options,
         )
         .await?;
+        outcomes.push(outcome);
     }
 
-    Ok(StatusCode::OK)
+    let response = published_response(
+        &app_state.app_config,
+        PublishingType::Automatic,
+        &repository_ids,
+        &outcomes,
+    );
+
+    Ok(with_dry_run_preview(
+        &headers,
+        response,
+        &repository_ids,
+        &outcomes,
+    ))
 }
 
 #[instrument(skip(app_state, user_token, request))]
-pub(crate) async fn staging_deploy_maven2<R: Repository>(
+pub(crate) async fn staging_deploy_maven2(
     TypedHeader(_user_agent): TypedHeader<UserAgent>,
-    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    ClientIp(client_ip): ClientIp,
     Path(file_path): Path<String>,
-    State(app_state): State<AppState<R>>,
+    State(app_state): State<AppState>,
     Extension(user_token): Extension<UserToken>,
     request: Request,
 ) -> Result<impl IntoResponse, ApiError> {
     tracing::debug!("Request to upload file to staging repository");
 
+    let file_path = sanitize_artifact_path(&file_path)?;
+
     let repository_key = app_state
         .repository
-        .open_no_profile_repository(&user_token.token_username, &addr.ip())
+        .open_no_profile_repository(
+            &user_token.token_username,
+            &client_ip,
+            upload_session(request.headers()),
+        )
         .await?;
+    let content_md5 = content_md5(request.headers())?;
 
     app_state
         .repository
         .add_file(
             &repository_key,
-            file_path,
-            request
-                .into_body()
-                .into_data_stream()
-                .map_err(|e| eyre::eyre!("Issue with the request body: {e}")),
+            &file_path,
+            Box::pin(verify_content_md5(
+                request
+                    .into_body()
+                    .into_data_stream()
+                    .map_err(|e| eyre::eyre!("Issue with the request body: {e}")),
+                content_md5,
+            )),
         )
         .await?;
 
     Ok(StatusCode::CREATED)
 }
 
-#[instrument(skip(_app_state, _user_token))]
-pub(crate) async fn staging_deploy_maven2_get<R: Repository>(
+#[instrument(skip(headers, app_state, user_token))]
+pub(crate) async fn staging_deploy_maven2_get(
     TypedHeader(_user_agent): TypedHeader<UserAgent>,
+    ClientIp(client_ip): ClientIp,
+    headers: HeaderMap,
     Path(file_path): Path<String>,
-    State(_app_state): State<AppState<R>>,
-    Extension(_user_token): Extension<UserToken>,
-) -> Result<impl IntoResponse, ApiError> {
+    State(app_state): State<AppState>,
+    Extension(user_token): Extension<UserToken>,
+) -> Result<Response, ApiError> {
     tracing::debug!("Request to get a file from a staging repository");
 
-    Ok(StatusCode::NOT_FOUND)
+    let file_path = sanitize_artifact_path(&file_path)?;
+
+    // reading never opens a repository, nor reopens one that has been closed
+    let Some(repository_key) = app_state
+        .repository
+        .find_no_profile_repository(
+            &user_token.token_username,
+            &client_ip,
+            upload_session(&headers),
+        )
+        .await
+    else {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    };
+
+    let file_metadata = app_state
+        .repository
+        .file_metadata(&repository_key, &file_path)
+        .await?;
+    if file_metadata.is_some() {
+        touch_repository(app_state.repository.deref(), &repository_key).await;
+    }
+    if let Some(response) = not_modified_response(&headers, file_metadata.as_ref()) {
+        return Ok(response);
+    }
+    let stored_file = app_state
+        .repository
+        .open_file(&repository_key, &file_path)
+        .await?;
+
+    Ok(file_response(
+        &file_path,
+        stored_file,
+        file_metadata.as_ref(),
+    ))
+}
+
+#[instrument(skip(headers, app_state, user_token))]
+pub(crate) async fn staging_deploy_maven2_head(
+    TypedHeader(_user_agent): TypedHeader<UserAgent>,
+    ClientIp(client_ip): ClientIp,
+    headers: HeaderMap,
+    Path(file_path): Path<String>,
+    State(app_state): State<AppState>,
+    Extension(user_token): Extension<UserToken>,
+) -> Result<Response, ApiError> {
+    tracing::debug!("Request to check for a file in a staging repository");
+
+    let file_path = sanitize_artifact_path(&file_path)?;
+
+    // reading never opens a repository, nor reopens one that has been closed
+    let Some(repository_key) = app_state
+        .repository
+        .find_no_profile_repository(
+            &user_token.token_username,
+            &client_ip,
+            upload_session(&headers),
+        )
+        .await
+    else {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    };
+
+    let file_metadata = app_state
+        .repository
+        .file_metadata(&repository_key, &file_path)
+        .await?;
+    if file_metadata.is_some() {
+        touch_repository(app_state.repository.deref(), &repository_key).await;
+    }
+    if let Some(response) = not_modified_response(&headers, file_metadata.as_ref()) {
+        return Ok(response);
+    }
+
+    Ok(file_head_response(&file_path, file_metadata))
 }
 
 #[derive(Debug, Serialize, ex_em_ell::ToXmlDocument)]
@@ -376,17 +930,51 @@ pub(crate) struct StagingProfilesEvaluateResponse {
 }
 
 impl StagingProfilesEvaluateResponse {
-    fn new(base_url: String, namespace: String) -> Self {
+    fn new(
+        base_url: String,
+        profile_id: String,
+        namespace: String,
+        app_config: &AppConfig,
+    ) -> Self {
+        Self::from_profiles(base_url, vec![(profile_id, namespace)], app_config)
+    }
+
+    /// A staging profile for each pair of profile ID and namespace
+    fn from_profiles(
+        base_url: String,
+        profiles: Vec<(String, String)>,
+        app_config: &AppConfig,
+    ) -> Self {
         Self {
-            data: vec![StagingProfile::new(
-                &base_url,
-                &namespace,
-                format!("{base_url}/service/local/staging/profile_evaluate/{namespace}"),
-            )],
+            data: profiles
+                .into_iter()
+                .map(|(profile_id, namespace)| {
+                    StagingProfile::new(
+                        &base_url,
+                        &profile_id,
+                        &namespace,
+                        format!("{base_url}/service/local/staging/profile_evaluate/{profile_id}"),
+                        app_config,
+                    )
+                })
+                .collect(),
         }
     }
 }
 
+/// The namespace of the profile that the group belongs to, if any
+///
+/// Without any authorized namespaces, every group is its own profile.
+fn profile_namespace(group: &str, authorized_namespaces: &NamespaceSet) -> Option<String> {
+    if authorized_namespaces.is_empty() {
+        return Some(group.to_string());
+    }
+
+    authorized_namespaces
+        .matching_namespace(group)
+        .map(String::from)
+}
+
 #[derive(Debug, Serialize, ex_em_ell::ToXmlDocument)]
 #[serde(rename_all = "camelCase")]
 #[ex_em_ell(rename = "profileResponse")]
@@ -395,12 +983,19 @@ pub(crate) struct StagingProfilesResponse {
 }
 
 impl StagingProfilesResponse {
-    fn new(base_url: String, profile_id: String) -> Self {
+    fn new(
+        base_url: String,
+        profile_id: String,
+        namespace: String,
+        app_config: &AppConfig,
+    ) -> Self {
         Self {
             data: StagingProfile::new(
                 &base_url,
                 &profile_id,
+                &namespace,
                 format!("{base_url}/service/local/staging/profiles/{profile_id}/{profile_id}"),
+                app_config,
             ),
         }
     }
@@ -464,13 +1059,22 @@ struct StagingProfile {
 }
 
 impl StagingProfile {
-    fn new(base_url: &str, namespace: &str, resource_uri: String) -> Self {
+    fn new(
+        base_url: &str,
+        profile_id: &str,
+        namespace: &str,
+        resource_uri: String,
+        app_config: &AppConfig,
+    ) -> Self {
         Self {
             resource_uri,
-            id: namespace.to_string(), // TODO: does this need to be numeric? The XSD says String
+            id: profile_id.to_string(),
             name: namespace.to_string(),
-            repository_type: "maven2".to_string(),
-            repository_template_id: "default_hosted_release".to_string(),
+            repository_type: app_config.staging_profile_repository_type.clone(),
+            repository_template_id: format!(
+                "default_hosted_{}",
+                app_config.staging_profile_policy.to_lowercase()
+            ),
             repository_target_id: "repository_target_id".to_string(),
             in_progress: false,
             order: 12345,
@@ -481,8 +1085,8 @@ impl StagingProfile {
             drop_notify_roles: Vec::new(),
             close_rule_sets: vec![WrappedString("close_rule_set".to_string())],
             promote_rule_sets: Vec::new(),
-            promotion_target_repository: "releases".to_string(),
-            mode: "BOTH".to_string(),
+            promotion_target_repository: app_config.staging_profile_release_repository.clone(),
+            mode: app_config.staging_profile_mode.clone(),
             finish_notify_creator: true,
             promotion_notify_creator: true,
             drop_notify_creator: true,
@@ -499,7 +1103,7 @@ struct WrappedString(String);
 
 impl ex_em_ell::ToXmlElement for WrappedString {
     fn to_xml_element<W: std::io::Write>(
-        self: &Self,
+        &self,
         writer: &mut ex_em_ell::xml::EventWriter<W>,
         tag: &str,
     ) -> Result<(), ex_em_ell::errors::XmlWriteError> {
@@ -539,7 +1143,7 @@ impl Serialize for Properties {
 
 impl ex_em_ell::ToXmlElement for Properties {
     fn to_xml_element<W: std::io::Write>(
-        self: &Self,
+        &self,
         writer: &mut ex_em_ell::xml::EventWriter<W>,
         tag: &str,
     ) -> Result<(), ex_em_ell::errors::XmlWriteError> {
@@ -559,7 +1163,17 @@ impl ex_em_ell::ToXmlElement for Properties {
 
 #[derive(Debug, Serialize, ex_em_ell::ToXmlDocument)]
 #[serde(rename_all = "camelCase")]
+#[ex_em_ell(rename = "stagingRepositories")]
+pub(crate) struct StagingRepositoriesResponse {
+    data: Vec<StagingRepositoryResponse>,
+}
+
+#[derive(
+    Debug, Serialize, ex_em_ell::ToXmlDocument, ex_em_ell::ToXmlElement, ex_em_ell::NamedXmlElement,
+)]
+#[serde(rename_all = "camelCase")]
 #[ex_em_ell(rename = "stagingProfileRepository")]
+#[ex_em_ell(name = "stagingProfileRepository")]
 pub(crate) struct StagingRepositoryResponse {
     profile_id: String,
     profile_name: String,
@@ -587,10 +1201,25 @@ pub(crate) struct StagingRepositoryResponse {
     release_repository_name: String,
     notifications: u32,
     transitioning: bool,
+    #[serde(skip_serializing_if = "ValidationErrors::is_empty")]
+    validation_errors: ValidationErrors,
 }
 
 impl StagingRepositoryResponse {
-    fn new(base_url: &str, repository_id: &str, repository_state: RepositoryState) -> Self {
+    fn new(
+        base_url: &str,
+        repository_id: &str,
+        repository_state: RepositoryState,
+        repository_errors: Vec<String>,
+        transitioning: bool,
+        deployment_id: Option<&str>,
+    ) -> Self {
+        // the closest NXRM2 has to somewhere for clients to find the deployment at Central
+        let description = match deployment_id {
+            Some(deployment_id) => format!("Central deployment {deployment_id}"),
+            None => "description".to_string(),
+        };
+
         Self {
             profile_id: "profile_id".to_string(), // TODO: do we need this to be persisted?
             profile_name: "profile_name".to_string(),
@@ -608,25 +1237,168 @@ impl StagingRepositoryResponse {
             updated: "1970-01-01T00:00:00.000Z".to_string(),
             updated_date: "Thu Jan 1 00:00:00 UTC 1970".to_string(),
             updated_timestamp: 0,
-            description: "description".to_string(),
+            description,
             provider: "maven2".to_string(),
             release_repository_id: "releases".to_string(),
             release_repository_name: "Releases".to_string(),
-            notifications: 0,
-            transitioning: false,
+            notifications: repository_errors.len() as u32,
+            transitioning,
+            validation_errors: ValidationErrors(
+                repository_errors.into_iter().map(WrappedString).collect(),
+            ),
+        }
+    }
+}
+
+/// The errors reported by Central for a failed publish
+///
+/// Omitted entirely when empty, so that clients unaware of the field see the standard NXRM2 document.
+#[derive(Debug, Serialize)]
+#[serde(transparent)]
+struct ValidationErrors(Vec<WrappedString>);
+
+impl ValidationErrors {
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl ex_em_ell::ToXmlElement for ValidationErrors {
+    fn to_xml_element<W: std::io::Write>(
+        &self,
+        writer: &mut ex_em_ell::xml::EventWriter<W>,
+        tag: &str,
+    ) -> Result<(), ex_em_ell::errors::XmlWriteError> {
+        if self.is_empty() {
+            return Ok(());
         }
+        self.0.to_xml_element(writer, tag)
+    }
+
+    fn will_write(&self) -> bool {
+        !self.is_empty()
+    }
+}
+
+/// The activities of a repository, serialized as a bare list like NXRM2 does
+#[derive(Debug, Serialize)]
+#[serde(transparent)]
+pub(crate) struct StagingActivitiesResponse(Vec<StagingActivity>);
+
+impl StagingActivitiesResponse {
+    /// One activity per state transition, each starting and stopping when the state was entered
+    fn new(repository_id: &str, activity: Vec<(RepositoryState, OffsetDateTime)>) -> Self {
+        let activities = activity
+            .into_iter()
+            .filter_map(|(repository_state, timestamp)| {
+                let (activity_name, event_name) = match repository_state {
+                    RepositoryState::Open => ("open", "repositoryCreated"),
+                    RepositoryState::Closed => ("close", "repositoryClosed"),
+                    RepositoryState::Released => ("release", "repositoryReleased"),
+                    RepositoryState::Dropped => ("drop", "repositoryDropped"),
+                    RepositoryState::Unknown | RepositoryState::NotFound => return None,
+                };
+                let timestamp = format_timestamp(timestamp);
+
+                Some(StagingActivity {
+                    name: activity_name.to_string(),
+                    events: vec![StagingActivityEvent {
+                        timestamp: timestamp.clone(),
+                        name: event_name.to_string(),
+                        severity: 0,
+                        properties: vec![StagingProperty {
+                            name: "id".to_string(),
+                            value: repository_id.to_string(),
+                        }],
+                    }],
+                    started: timestamp.clone(),
+                    stopped: timestamp,
+                })
+            })
+            .collect();
+
+        Self(activities)
+    }
+}
+
+impl ex_em_ell::ToXmlDocument for StagingActivitiesResponse {
+    fn to_xml_document<W: std::io::Write>(
+        &self,
+        writer: &mut ex_em_ell::xml::EventWriter<W>,
+    ) -> Result<(), ex_em_ell::errors::XmlWriteError> {
+        ex_em_ell::ToXmlElement::to_xml_element(&self.0, writer, "list")
     }
 }
 
+#[derive(Debug, Serialize, ex_em_ell::ToXmlElement, ex_em_ell::NamedXmlElement)]
+#[ex_em_ell(name = "stagingActivity")]
+struct StagingActivity {
+    name: String,
+    events: Vec<StagingActivityEvent>,
+    started: String,
+    stopped: String,
+}
+
+#[derive(Debug, Serialize, ex_em_ell::ToXmlElement, ex_em_ell::NamedXmlElement)]
+#[ex_em_ell(name = "stagingActivityEvent")]
+struct StagingActivityEvent {
+    timestamp: String,
+    name: String,
+    severity: u32,
+    properties: Vec<StagingProperty>,
+}
+
+#[derive(Debug, Serialize, ex_em_ell::ToXmlElement, ex_em_ell::NamedXmlElement)]
+#[ex_em_ell(name = "stagingProperty")]
+struct StagingProperty {
+    name: String,
+    value: String,
+}
+
+/// Format a timestamp the way NXRM2 does, such as `2024-06-01T12:30:00.000Z`
+fn format_timestamp(timestamp: OffsetDateTime) -> String {
+    let timestamp = timestamp.to_offset(UtcOffset::UTC);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        timestamp.year(),
+        u8::from(timestamp.month()),
+        timestamp.day(),
+        timestamp.hour(),
+        timestamp.minute(),
+        timestamp.second(),
+        timestamp.millisecond()
+    )
+}
+
 #[cfg(test)]
 mod tests {
+    use std::net::SocketAddr;
+
+    use axum::body::Body;
+    use axum::extract::connect_info::MockConnectInfo;
+    use axum::http::header::{
+        ACCEPT, CONTENT_LENGTH, CONTENT_TYPE, ETAG, HOST, IF_NONE_MATCH, USER_AGENT,
+    };
+    use axum::routing::{delete, get, head, post, put};
+    use axum::Router;
+    use base64::prelude::{Engine, BASE64_STANDARD};
+    use portal_api::PortalApiClient;
+    use repository::local_repository::LocalRepository;
+    use repository::options::RepositoryOptions;
+    use repository::traits::Repository;
+    use tower::ServiceExt;
+
     use super::*;
+    use crate::config::AppConfig;
+    use crate::publish::tests::stage_jar;
 
     #[test]
     fn test_xml_serialization_staging_profiles_evaluate_response() -> eyre::Result<()> {
         let staging_profiles_evaluate_response = StagingProfilesEvaluateResponse::new(
             "https://s01.oss.sonatype.org".to_string(),
             "com.example".to_string(),
+            "com.example".to_string(),
+            &AppConfig::with_overrides(&[])?,
         );
         let actual_xml = ex_em_ell::to_string_pretty(&staging_profiles_evaluate_response)?;
         let expected_xml = r#"<?xml version="1.0" encoding="utf-8"?>
@@ -675,6 +1447,8 @@ mod tests {
         let staging_profiles_evaluate_response = StagingProfilesEvaluateResponse::new(
             "https://s01.oss.sonatype.org".to_string(),
             "com.example".to_string(),
+            "com.example".to_string(),
+            &AppConfig::with_overrides(&[])?,
         );
         let actual_json = serde_json::to_string_pretty(&staging_profiles_evaluate_response)?;
         let expected_json = r#"{
@@ -725,6 +1499,8 @@ mod tests {
         let staging_profiles_evaluate_response = StagingProfilesResponse::new(
             "https://s01.oss.sonatype.org".to_string(),
             "com.example".to_string(),
+            "com.example".to_string(),
+            &AppConfig::with_overrides(&[])?,
         );
         let actual_xml = ex_em_ell::to_string_pretty(&staging_profiles_evaluate_response)?;
         let expected_xml = r#"<?xml version="1.0" encoding="utf-8"?>
@@ -767,10 +1543,43 @@ mod tests {
         Ok(())
     }
     #[test]
-    fn test_json_serialization_staging_profiles_response() -> eyre::Result<()> {
+    fn configured_staging_profile() -> eyre::Result<()> {
+        let app_config = AppConfig::with_overrides(&[
+            ("staging_profile_mode", "DEPLOY"),
+            ("staging_profile_policy", "SNAPSHOT"),
+            ("staging_profile_repository_type", "maven1"),
+            ("staging_profile_release_repository", "central-releases"),
+        ])?;
+
+        let staging_profiles_response = StagingProfilesResponse::new(
+            "https://s01.oss.sonatype.org".to_string(),
+            "com.example".to_string(),
+            "com.example".to_string(),
+            &app_config,
+        );
+        let actual_xml = ex_em_ell::to_string_pretty(&staging_profiles_response)?;
+
+        assert!(actual_xml.contains("<mode>DEPLOY</mode>"));
+        assert!(actual_xml
+            .contains("<repositoryTemplateId>default_hosted_snapshot</repositoryTemplateId>"));
+        assert!(actual_xml.contains("<repositoryType>maven1</repositoryType>"));
+        assert!(actual_xml
+            .contains("<promotionTargetRepository>central-releases</promotionTargetRepository>"));
+
+        let actual_json = serde_json::to_value(&staging_profiles_response)?;
+        assert_eq!(actual_json["data"]["mode"], "DEPLOY");
+        assert_eq!(actual_json["data"]["repositoryType"], "maven1");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_json_serialization_staging_profiles_response() -> eyre::Result<()> {
         let staging_profiles_evaluate_response = StagingProfilesResponse::new(
             "https://s01.oss.sonatype.org".to_string(),
             "com.example".to_string(),
+            "com.example".to_string(),
+            &AppConfig::with_overrides(&[])?,
         );
         let actual_json = serde_json::to_string_pretty(&staging_profiles_evaluate_response)?;
         let expected_json = r#"{
@@ -900,6 +1709,8 @@ mod tests {
             data: StagingProfilesFinishRequestData {
                 staged_repository_id: "comexample-1".to_string(),
                 description: "com.example:example:0.1.0".to_string(),
+                target_repository_id: None,
+                auto_drop_after_release: false,
             },
         };
 
@@ -920,6 +1731,53 @@ mod tests {
             data: StagingProfilesFinishRequestData {
                 staged_repository_id: "comexample-1".to_string(),
                 description: "com.example:example:0.1.0".to_string(),
+                target_repository_id: None,
+                auto_drop_after_release: false,
+            },
+        };
+
+        assert_eq!(
+            actual_staging_profile_request,
+            expected_staging_profile_request
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_xml_deserialization_staging_profiles_finish_request_extra_fields() -> eyre::Result<()> {
+        let actual_xml = "<promoteRequest><data><stagedRepositoryId>comexample-1</stagedRepositoryId><description>com.example:example:0.1.0</description><targetRepositoryId>releases</targetRepositoryId><autoDropAfterRelease>true</autoDropAfterRelease><notifyRoles><string>admin</string></notifyRoles></data></promoteRequest>";
+        let actual_staging_profile_request: StagingProfilesFinishRequest =
+            ex_em_ell::from_reader(actual_xml.as_bytes())?;
+        let expected_staging_profile_request = StagingProfilesFinishRequest {
+            data: StagingProfilesFinishRequestData {
+                staged_repository_id: "comexample-1".to_string(),
+                description: "com.example:example:0.1.0".to_string(),
+                target_repository_id: Some("releases".to_string()),
+                auto_drop_after_release: true,
+            },
+        };
+
+        assert_eq!(
+            actual_staging_profile_request,
+            expected_staging_profile_request
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_json_deserialization_staging_profiles_finish_request_extra_fields() -> eyre::Result<()>
+    {
+        let actual_json = r#"{ "data": { "stagedRepositoryId": "comexample-1", "description": "com.example:example:0.1.0", "targetRepositoryId": "releases", "autoDropAfterRelease": true, "notifyRoles": ["admin"] } }"#;
+        let actual_staging_profile_request: StagingProfilesFinishRequest =
+            serde_json::from_reader(actual_json.as_bytes())?;
+        let expected_staging_profile_request = StagingProfilesFinishRequest {
+            data: StagingProfilesFinishRequestData {
+                staged_repository_id: "comexample-1".to_string(),
+                description: "com.example:example:0.1.0".to_string(),
+                target_repository_id: Some("releases".to_string()),
+                auto_drop_after_release: true,
             },
         };
 
@@ -937,6 +1795,9 @@ mod tests {
             "https://s01.oss.sonatype.org",
             "comexample-1",
             RepositoryState::Closed,
+            Vec::new(),
+            false,
+            None,
         );
         let actual_xml = ex_em_ell::to_string_pretty(&repository_response)?;
         let expected_xml = r#"<?xml version="1.0" encoding="utf-8"?>
@@ -976,6 +1837,9 @@ mod tests {
             "https://s01.oss.sonatype.org",
             "comexample-1",
             RepositoryState::Closed,
+            Vec::new(),
+            false,
+            None,
         );
         let actual_json = serde_json::to_string_pretty(&repository_response)?;
         let expected_json = r#"{
@@ -1049,4 +1913,1090 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_xml_serialization_repository_response_with_errors() -> eyre::Result<()> {
+        let repository_response = StagingRepositoryResponse::new(
+            "https://s01.oss.sonatype.org",
+            "comexample-1",
+            RepositoryState::Closed,
+            vec!["Upload request failed (400 Bad Request): missing signature".to_string()],
+            false,
+            None,
+        );
+        let actual_xml = ex_em_ell::to_string_pretty(&repository_response)?;
+
+        assert!(actual_xml.contains("<notifications>1</notifications>"));
+        assert!(actual_xml.contains(
+            r#"<validationErrors>
+    <string>Upload request failed (400 Bad Request): missing signature</string>
+  </validationErrors>"#
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_json_serialization_repository_response_with_errors() -> eyre::Result<()> {
+        let repository_response = StagingRepositoryResponse::new(
+            "https://s01.oss.sonatype.org",
+            "comexample-1",
+            RepositoryState::Closed,
+            vec!["Upload request failed (400 Bad Request): missing signature".to_string()],
+            false,
+            None,
+        );
+        let actual_json = serde_json::to_value(&repository_response)?;
+
+        assert_eq!(actual_json["notifications"], 1);
+        assert_eq!(
+            actual_json["validationErrors"],
+            serde_json::json!(["Upload request failed (400 Bad Request): missing signature"])
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn head_existing_and_missing_artifacts() -> eyre::Result<()> {
+        let addr = SocketAddr::from(([127, 0, 0, 1], 2727));
+        let local_repository = LocalRepository::new()?;
+        let repository_key = local_repository
+            .start("test_user", &addr.ip(), "comexample")
+            .await?;
+        local_repository
+            .add_file(
+                &repository_key,
+                std::path::Path::new("com/example/example/0.1.0/example-0.1.0.pom"),
+                Box::pin(futures::stream::once(async {
+                    Ok(bytes::Bytes::from("<project />"))
+                })),
+            )
+            .await?;
+
+        let app_state = AppState::new(
+            local_repository,
+            PortalApiClient::client("http://localhost")?,
+            AppConfig::with_overrides(&[])?,
+        );
+        let user_token = UserToken::from_token(&BASE64_STANDARD.encode("test_user:test_password"))?;
+        let app = Router::new()
+            .route(
+                "/deployByRepositoryId/:staging_repository_id/*file_path",
+                head(staging_deploy_by_repository_id_head),
+            )
+            .with_state(app_state)
+            .layer(Extension(user_token))
+            .layer(MockConnectInfo(addr));
+        let head_request = |file_path: &str| {
+            axum::http::Request::head(format!(
+                "/deployByRepositoryId/{}/{file_path}",
+                repository_key.get_repository_id()
+            ))
+            .header(USER_AGENT, "test")
+            .body(Body::empty())
+        };
+
+        let response = app
+            .clone()
+            .oneshot(head_request("com/example/example/0.1.0/example-0.1.0.pom")?)
+            .await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers().get(CONTENT_LENGTH), Some(&"11".parse()?));
+        assert_eq!(
+            response.headers().get(CONTENT_TYPE),
+            Some(&"application/xml".parse()?)
+        );
+        assert_eq!(
+            response.headers().get(ETAG),
+            Some(&"\"91b398cc2f1ce9ed51d234976bcef90222d1e626\"".parse()?)
+        );
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await?;
+        assert!(body.is_empty());
+
+        let response = app
+            .oneshot(head_request("com/example/example/0.1.0/example-0.1.0.jar")?)
+            .await?;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn conditional_artifact_get() -> eyre::Result<()> {
+        let addr = SocketAddr::from(([127, 0, 0, 1], 2727));
+        let local_repository = LocalRepository::new()?;
+        let repository_key = local_repository
+            .start("test_user", &addr.ip(), "comexample")
+            .await?;
+        local_repository
+            .add_file(
+                &repository_key,
+                std::path::Path::new("com/example/example/0.1.0/example-0.1.0.pom"),
+                Box::pin(futures::stream::once(async {
+                    Ok(bytes::Bytes::from("<project />"))
+                })),
+            )
+            .await?;
+
+        let app_state = AppState::new(
+            local_repository,
+            PortalApiClient::client("http://localhost")?,
+            AppConfig::with_overrides(&[])?,
+        );
+        let user_token = UserToken::from_token(&BASE64_STANDARD.encode("test_user:test_password"))?;
+        let app = Router::new()
+            .route(
+                "/deployByRepositoryId/:staging_repository_id/*file_path",
+                get(staging_deploy_by_repository_id_get),
+            )
+            .with_state(app_state)
+            .layer(Extension(user_token))
+            .layer(MockConnectInfo(addr));
+        let get_request = |if_none_match: Option<&str>| {
+            let mut request = axum::http::Request::get(format!(
+                "/deployByRepositoryId/{}/com/example/example/0.1.0/example-0.1.0.pom",
+                repository_key.get_repository_id()
+            ))
+            .header(USER_AGENT, "test");
+            if let Some(if_none_match) = if_none_match {
+                request = request.header(IF_NONE_MATCH, if_none_match);
+            }
+            request.body(Body::empty())
+        };
+        let etag = "\"91b398cc2f1ce9ed51d234976bcef90222d1e626\"";
+
+        // a miss serves the file with its ETag
+        for if_none_match in [None, Some("\"stale\"")] {
+            let response = app.clone().oneshot(get_request(if_none_match)?).await?;
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.headers().get(ETAG), Some(&etag.parse()?));
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX).await?;
+            assert_eq!(body, "<project />");
+        }
+
+        // a hit leaves the client with the copy it has
+        let response = app.oneshot(get_request(Some(etag))?).await?;
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers().get(ETAG), Some(&etag.parse()?));
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await?;
+        assert!(body.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn stream_large_artifact() -> eyre::Result<()> {
+        let addr = SocketAddr::from(([127, 0, 0, 1], 2727));
+        let local_repository = LocalRepository::new()?;
+        let repository_key = local_repository
+            .start("test_user", &addr.ip(), "comexample")
+            .await?;
+        let contents = (0..5 * 1024 * 1024)
+            .map(|i: u32| (i % 251) as u8)
+            .collect::<Vec<_>>();
+        local_repository
+            .add_file(
+                &repository_key,
+                std::path::Path::new("com/example/example/0.1.0/example-0.1.0.jar"),
+                Box::pin(futures::stream::iter(
+                    contents
+                        .chunks(64 * 1024)
+                        .map(|chunk| Ok(bytes::Bytes::copy_from_slice(chunk)))
+                        .collect::<Vec<_>>(),
+                )),
+            )
+            .await?;
+
+        let app_state = AppState::new(
+            local_repository,
+            PortalApiClient::client("http://localhost")?,
+            AppConfig::with_overrides(&[])?,
+        );
+        let user_token = UserToken::from_token(&BASE64_STANDARD.encode("test_user:test_password"))?;
+        let app = Router::new()
+            .route(
+                "/deployByRepositoryId/:staging_repository_id/*file_path",
+                get(staging_deploy_by_repository_id_get),
+            )
+            .with_state(app_state)
+            .layer(Extension(user_token))
+            .layer(MockConnectInfo(addr));
+
+        let response = app
+            .oneshot(
+                axum::http::Request::get(format!(
+                    "/deployByRepositoryId/{}/com/example/example/0.1.0/example-0.1.0.jar",
+                    repository_key.get_repository_id()
+                ))
+                .header(USER_AGENT, "test")
+                .body(Body::empty())?,
+            )
+            .await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers().get(CONTENT_LENGTH),
+            Some(&contents.len().to_string().parse()?)
+        );
+
+        // read back a piece at a time, rather than as one buffer
+        let chunks = response
+            .into_body()
+            .into_data_stream()
+            .try_collect::<Vec<_>>()
+            .await?;
+        assert!(chunks.len() > 1);
+        assert_eq!(chunks.concat(), contents);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn drop_open_repository() -> eyre::Result<()> {
+        let addr = SocketAddr::from(([127, 0, 0, 1], 2727));
+        let local_repository = LocalRepository::new()?;
+        let repository_key = local_repository
+            .start("test_user", &addr.ip(), "comexample")
+            .await?;
+
+        let app_state = AppState::new(
+            local_repository,
+            PortalApiClient::client("http://localhost")?,
+            AppConfig::with_overrides(&[])?,
+        );
+        let repository = app_state.repository.clone();
+        let user_token = UserToken::from_token(&BASE64_STANDARD.encode("test_user:test_password"))?;
+        let app = Router::new()
+            .route(
+                "/deployByRepositoryId/:staging_repository_id",
+                delete(staging_deploy_by_repository_id_delete),
+            )
+            .route(
+                "/deployByRepositoryId/:staging_repository_id/*file_path",
+                put(staging_deploy_by_repository_id),
+            )
+            .with_state(app_state)
+            .layer(Extension(user_token))
+            .layer(MockConnectInfo(addr));
+        let repository_uri = format!(
+            "/deployByRepositoryId/{}",
+            repository_key.get_repository_id()
+        );
+        let put_request = || {
+            axum::http::Request::put(format!(
+                "{repository_uri}/com/example/example/0.1.0/example-0.1.0.pom"
+            ))
+            .header(USER_AGENT, "test")
+            .body(Body::from("<project />"))
+        };
+        let delete_request = || {
+            axum::http::Request::delete(&repository_uri)
+                .header(USER_AGENT, "test")
+                .body(Body::empty())
+        };
+
+        let response = app.clone().oneshot(put_request()?).await?;
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let response = app.clone().oneshot(delete_request()?).await?;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert!(matches!(
+            repository.get_state(&repository_key).await?,
+            RepositoryState::Dropped
+        ));
+
+        // the repository is gone to clients
+        let response = app.clone().oneshot(put_request()?).await?;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let response = app.oneshot(delete_request()?).await?;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn verify_content_md5_header() -> eyre::Result<()> {
+        let addr = SocketAddr::from(([127, 0, 0, 1], 2727));
+        let local_repository = LocalRepository::new()?;
+        let repository_key = local_repository
+            .start("test_user", &addr.ip(), "comexample")
+            .await?;
+
+        let app_state = AppState::new(
+            local_repository,
+            PortalApiClient::client("http://localhost")?,
+            AppConfig::with_overrides(&[])?,
+        );
+        let repository = app_state.repository.clone();
+        let user_token = UserToken::from_token(&BASE64_STANDARD.encode("test_user:test_password"))?;
+        let app = Router::new()
+            .route(
+                "/deployByRepositoryId/:staging_repository_id/*file_path",
+                put(staging_deploy_by_repository_id),
+            )
+            .with_state(app_state)
+            .layer(Extension(user_token))
+            .layer(MockConnectInfo(addr));
+        let put_request = |file_path: &str, content_md5: &str| {
+            axum::http::Request::put(format!(
+                "/deployByRepositoryId/{}/{file_path}",
+                repository_key.get_repository_id()
+            ))
+            .header(USER_AGENT, "test")
+            .header("content-md5", content_md5)
+            .body(Body::from("<project />"))
+        };
+
+        // the base64 MD5 of `<project />`
+        let response = app
+            .clone()
+            .oneshot(put_request(
+                "com/example/example/0.1.0/example-0.1.0.pom",
+                "8HRnscvUbxaDpV++rvSXnw==",
+            )?)
+            .await?;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert!(repository
+            .get_file(
+                &repository_key,
+                std::path::Path::new("com/example/example/0.1.0/example-0.1.0.pom")
+            )
+            .await?
+            .is_some());
+
+        let response = app
+            .oneshot(put_request(
+                "com/example/example/0.1.0/example-0.1.0.jar",
+                "1B2M2Y8AsgTpgAmY7PhCfg==",
+            )?)
+            .await?;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await?;
+        assert!(String::from_utf8(body.to_vec())?.contains("Content-MD5 mismatch"));
+        assert!(repository
+            .get_file(
+                &repository_key,
+                std::path::Path::new("com/example/example/0.1.0/example-0.1.0.jar")
+            )
+            .await?
+            .is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn list_profile_repositories() -> eyre::Result<()> {
+        let addr = SocketAddr::from(([127, 0, 0, 1], 2727));
+        let local_repository = LocalRepository::with_options(RepositoryOptions {
+            allow_empty_bundles: true,
+            ..Default::default()
+        })?;
+        let open_key = local_repository
+            .start("test_user", &addr.ip(), "comexample")
+            .await?;
+        let closed_key = local_repository
+            .start("test_user", &addr.ip(), "comexample")
+            .await?;
+        local_repository.finish(&closed_key).await?;
+        let released_key = local_repository
+            .start("test_user", &addr.ip(), "comexample")
+            .await?;
+        local_repository.finish(&released_key).await?;
+        local_repository.release(&released_key).await?;
+        local_repository
+            .start("test_user", &addr.ip(), "orgexample")
+            .await?;
+        local_repository
+            .start("other_user", &addr.ip(), "comexample")
+            .await?;
+
+        let app_state = AppState::new(
+            local_repository,
+            PortalApiClient::client("http://localhost")?,
+            AppConfig::with_overrides(&[])?,
+        );
+        let user_token = UserToken::from_token(&BASE64_STANDARD.encode("test_user:test_password"))?;
+        let app = Router::new()
+            .route(
+                "/profile_repositories/:profile_id",
+                get(staging_profile_repositories),
+            )
+            .with_state(app_state)
+            .layer(Extension(user_token))
+            .layer(MockConnectInfo(addr));
+
+        let response = app
+            .oneshot(
+                axum::http::Request::get("/profile_repositories/comexample")
+                    .header(HOST, "localhost")
+                    .header(USER_AGENT, "test")
+                    .header(ACCEPT, "application/json")
+                    .body(Body::empty())?,
+            )
+            .await?;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await?;
+        let body: serde_json::Value = serde_json::from_slice(&body)?;
+        let repositories = body["data"]
+            .as_array()
+            .ok_or_else(|| eyre::eyre!("Expected a list of repositories: {body}"))?
+            .iter()
+            .map(|repository| {
+                (
+                    repository["repositoryId"].as_str().unwrap_or_default(),
+                    repository["type"].as_str().unwrap_or_default(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            repositories,
+            vec![
+                (open_key.get_repository_id().as_str(), "open"),
+                (closed_key.get_repository_id().as_str(), "closed"),
+                (released_key.get_repository_id().as_str(), "released"),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_xml_serialization_repositories_response() -> eyre::Result<()> {
+        let response = StagingRepositoriesResponse {
+            data: vec![
+                StagingRepositoryResponse::new(
+                    "https://s01.oss.sonatype.org",
+                    "comexample-0",
+                    RepositoryState::Open,
+                    Vec::new(),
+                    false,
+                    None,
+                ),
+                StagingRepositoryResponse::new(
+                    "https://s01.oss.sonatype.org",
+                    "comexample-1",
+                    RepositoryState::Closed,
+                    Vec::new(),
+                    false,
+                    None,
+                ),
+            ],
+        };
+        let actual_xml = ex_em_ell::to_string_pretty(&response)?;
+
+        assert!(actual_xml.starts_with(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<stagingRepositories>\n  <data>\n    <stagingProfileRepository>"
+        ));
+        assert!(actual_xml.contains("<repositoryId>comexample-0</repositoryId>"));
+        assert!(actual_xml.contains("<repositoryId>comexample-1</repositoryId>"));
+        assert!(actual_xml.contains("<type>closed</type>"));
+
+        Ok(())
+    }
+
+    fn closed_then_released_activity() -> eyre::Result<StagingActivitiesResponse> {
+        let opened = OffsetDateTime::from_unix_timestamp(1_717_245_000)?;
+        Ok(StagingActivitiesResponse::new(
+            "comexample-1",
+            vec![
+                (RepositoryState::Open, opened),
+                (
+                    RepositoryState::Closed,
+                    opened + time::Duration::milliseconds(1_500),
+                ),
+                (
+                    RepositoryState::Released,
+                    opened + time::Duration::minutes(2),
+                ),
+            ],
+        ))
+    }
+
+    #[test]
+    fn test_xml_serialization_activity_response() -> eyre::Result<()> {
+        let actual_xml = ex_em_ell::to_string_pretty(&closed_then_released_activity()?)?;
+
+        assert!(actual_xml.starts_with(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<list>\n  <stagingActivity>\n    <name>open</name>"
+        ));
+        assert!(actual_xml.contains(
+            r#"  <stagingActivity>
+    <name>close</name>
+    <events>
+      <stagingActivityEvent>
+        <timestamp>2024-06-01T12:30:01.500Z</timestamp>
+        <name>repositoryClosed</name>
+        <severity>0</severity>
+        <properties>
+          <stagingProperty>
+            <name>id</name>
+            <value>comexample-1</value>
+          </stagingProperty>
+        </properties>
+      </stagingActivityEvent>
+    </events>
+    <started>2024-06-01T12:30:01.500Z</started>
+    <stopped>2024-06-01T12:30:01.500Z</stopped>
+  </stagingActivity>"#
+        ));
+        assert!(actual_xml.contains("<name>repositoryReleased</name>"));
+        assert!(actual_xml.contains("<started>2024-06-01T12:32:00.000Z</started>"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_json_serialization_activity_response() -> eyre::Result<()> {
+        let actual_json = serde_json::to_value(closed_then_released_activity()?)?;
+
+        let names = actual_json
+            .as_array()
+            .ok_or_else(|| eyre::eyre!("Activity is not a list: {actual_json}"))?
+            .iter()
+            .map(|activity| activity["name"].as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec![Some("open"), Some("close"), Some("release")]);
+        assert_eq!(
+            actual_json[2]["events"][0],
+            serde_json::json!({
+                "timestamp": "2024-06-01T12:32:00.000Z",
+                "name": "repositoryReleased",
+                "severity": 0,
+                "properties": [{"name": "id", "value": "comexample-1"}],
+            })
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn dry_run_close_previews_bundle() -> eyre::Result<()> {
+        let addr = SocketAddr::from(([127, 0, 0, 1], 2727));
+        let local_repository = LocalRepository::new()?;
+        let repository_key = local_repository
+            .start("test_user", &addr.ip(), "comexample")
+            .await?;
+        let files = [
+            (
+                "com/example/example/0.1.0/example-0.1.0.jar",
+                "test_jar_content",
+            ),
+            ("com/example/example/0.1.0/example-0.1.0.pom", "<project />"),
+        ];
+        for (file_path, file_contents) in files {
+            local_repository
+                .add_file(
+                    &repository_key,
+                    std::path::Path::new(file_path),
+                    Box::pin(futures::stream::once(async move {
+                        Ok(bytes::Bytes::from(file_contents))
+                    })),
+                )
+                .await?;
+        }
+
+        let app_state = AppState::new(
+            local_repository,
+            PortalApiClient::client("http://localhost")?,
+            AppConfig::with_overrides(&[("dry_run", "true")])?,
+        );
+        let user_token = UserToken::from_token(&BASE64_STANDARD.encode("test_user:test_password"))?;
+        let app = Router::new()
+            .route("/bulk/close", post(staging_bulk_close))
+            .with_state(app_state)
+            .layer(Extension(user_token))
+            .layer(MockConnectInfo(addr));
+
+        let response = app
+            .oneshot(
+                axum::http::Request::post("/bulk/close")
+                    .header(HOST, "localhost")
+                    .header(USER_AGENT, "test")
+                    .header(ACCEPT, "application/json")
+                    .header(CONTENT_TYPE, "application/json")
+                    .body(Body::from(format!(
+                        r#"{{"data": {{"stagedRepositoryIds": ["{}"], "description": "", "autoDropAfterRelease": true}}}}"#,
+                        repository_key.get_repository_id()
+                    )))?,
+            )
+            .await?;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await?;
+        let preview: serde_json::Value = serde_json::from_slice(&body)?;
+        assert_eq!(
+            preview["data"][0]["repositoryId"],
+            repository_key.get_repository_id()
+        );
+        let mut entries = preview["data"][0]["entries"]
+            .as_array()
+            .ok_or_else(|| eyre::eyre!("No entries in {preview}"))?
+            .iter()
+            .map(|entry| (entry["path"].to_string(), entry["size"].clone()))
+            .collect::<Vec<_>>();
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        let mut expected_entries = files
+            .iter()
+            .map(|(file_path, file_contents)| {
+                (
+                    serde_json::json!(file_path).to_string(),
+                    serde_json::json!(file_contents.len()),
+                )
+            })
+            .collect::<Vec<_>>();
+        expected_entries.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(entries, expected_entries);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn unknown_repositories_not_found() -> eyre::Result<()> {
+        let addr = SocketAddr::from(([127, 0, 0, 1], 2727));
+        let app_state = AppState::new(
+            LocalRepository::new()?,
+            PortalApiClient::client("http://localhost")?,
+            AppConfig::with_overrides(&[])?,
+        );
+        let user_token = UserToken::from_token(&BASE64_STANDARD.encode("test_user:test_password"))?;
+        let app = Router::new()
+            .route(
+                "/deployByRepositoryId/:staging_repository_id/*file_path",
+                put(staging_deploy_by_repository_id),
+            )
+            .route(
+                "/profiles/:profile_id/finish",
+                post(staging_profiles_finish_endpoint),
+            )
+            .route("/bulk/close", post(staging_bulk_close))
+            .route("/bulk/promote", post(staging_bulk_promote))
+            .with_state(app_state)
+            .layer(Extension(user_token))
+            .layer(MockConnectInfo(addr));
+
+        let staging_action = r#"{"data": {"stagedRepositoryIds": ["comexample-0"], "description": "", "autoDropAfterRelease": true}}"#;
+        for (method, uri, body) in [
+            (
+                "PUT",
+                "/deployByRepositoryId/comexample-0/com/example/example/0.1.0/example-0.1.0.jar",
+                "test_file_content",
+            ),
+            (
+                "POST",
+                "/profiles/comexample/finish",
+                r#"{"data": {"stagedRepositoryId": "comexample-0", "description": ""}}"#,
+            ),
+            ("POST", "/bulk/close", staging_action),
+            ("POST", "/bulk/promote", staging_action),
+        ] {
+            let response = app
+                .clone()
+                .oneshot(
+                    axum::http::Request::builder()
+                        .method(method)
+                        .uri(uri)
+                        .header(HOST, "localhost")
+                        .header(USER_AGENT, "test")
+                        .header(CONTENT_TYPE, "application/json")
+                        .body(Body::from(body))?,
+                )
+                .await?;
+            assert_eq!(response.status(), StatusCode::NOT_FOUND, "{method} {uri}");
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn bulk_close_only_open_repositories() -> eyre::Result<()> {
+        let mock_server = wiremock::MockServer::start().await;
+        wiremock::Mock::given(wiremock::matchers::method("POST"))
+            .and(wiremock::matchers::path("/api/v1/publisher/upload"))
+            .respond_with(
+                wiremock::ResponseTemplate::new(201).set_body_string("test_deployment_id"),
+            )
+            .expect(0)
+            .mount(&mock_server)
+            .await;
+
+        let addr = SocketAddr::from(([127, 0, 0, 1], 2727));
+        let local_repository = LocalRepository::new()?;
+        let mut repository_keys = Vec::new();
+        for _ in 0..2 {
+            let repository_key = stage_jar(&local_repository).await?;
+            repository_keys.push(repository_key);
+        }
+        local_repository.finish(&repository_keys[1]).await?;
+
+        let app_state = AppState::new(
+            local_repository,
+            PortalApiClient::client(&mock_server.uri())?,
+            AppConfig::with_overrides(&[])?,
+        );
+        let user_token = UserToken::from_token(&BASE64_STANDARD.encode("test_user:test_password"))?;
+        let app = Router::new()
+            .route("/bulk/close", post(staging_bulk_close))
+            .with_state(app_state.clone())
+            .layer(Extension(user_token))
+            .layer(MockConnectInfo(addr));
+
+        let response = app
+            .oneshot(
+                axum::http::Request::post("/bulk/close")
+                    .header(HOST, "localhost")
+                    .header(USER_AGENT, "test")
+                    .header(CONTENT_TYPE, "application/json")
+                    .body(Body::from(format!(
+                        r#"{{"data": {{"stagedRepositoryIds": ["{}", "{}"], "description": "", "autoDropAfterRelease": false}}}}"#,
+                        repository_keys[0].get_repository_id(),
+                        repository_keys[1].get_repository_id()
+                    )))?,
+            )
+            .await?;
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        assert!(matches!(
+            app_state.repository.get_state(&repository_keys[0]).await?,
+            RepositoryState::Open
+        ));
+        assert!(matches!(
+            app_state.repository.get_state(&repository_keys[1]).await?,
+            RepositoryState::Closed
+        ));
+
+        mock_server.verify().await;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn finish_only_owned_repositories() -> eyre::Result<()> {
+        let mock_server = wiremock::MockServer::start().await;
+        wiremock::Mock::given(wiremock::matchers::method("POST"))
+            .and(wiremock::matchers::path("/api/v1/publisher/upload"))
+            .respond_with(
+                wiremock::ResponseTemplate::new(201).set_body_string("test_deployment_id"),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let addr = SocketAddr::from(([127, 0, 0, 1], 2727));
+        let local_repository = LocalRepository::new()?;
+        stage_jar(&local_repository).await?;
+        for _ in 0..2 {
+            local_repository
+                .start("other_user", &addr.ip(), "comexample")
+                .await?;
+        }
+
+        let app_state = AppState::new(
+            local_repository,
+            PortalApiClient::client(&mock_server.uri())?,
+            AppConfig::with_overrides(&[])?,
+        );
+        let user_token = UserToken::from_token(&BASE64_STANDARD.encode("test_user:test_password"))?;
+        let app = Router::new()
+            .route(
+                "/profiles/:profile_id/finish",
+                post(staging_profiles_finish_endpoint),
+            )
+            .with_state(app_state)
+            .layer(Extension(user_token))
+            .layer(MockConnectInfo(addr));
+        let finish_request = |profile_id: &str, repository_id: &str| {
+            axum::http::Request::post(format!("/profiles/{profile_id}/finish"))
+                .header(HOST, "localhost")
+                .header(USER_AGENT, "test")
+                .header(CONTENT_TYPE, "application/json")
+                .body(Body::from(format!(
+                    r#"{{"data": {{"stagedRepositoryId": "{repository_id}", "description": ""}}}}"#
+                )))
+        };
+
+        // only other_user has started a second repository in the profile
+        let response = app
+            .clone()
+            .oneshot(finish_request("comexample", "comexample-1")?)
+            .await?;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let response = app
+            .clone()
+            .oneshot(finish_request("orgexample", "comexample-0")?)
+            .await?;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let response = app
+            .oneshot(finish_request("comexample", "comexample-0")?)
+            .await?;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        mock_server.verify().await;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn bulk_promote_drops_after_release() -> eyre::Result<()> {
+        let addr = SocketAddr::from(([127, 0, 0, 1], 2727));
+        let local_repository = LocalRepository::new()?;
+        let mut repository_keys = Vec::new();
+        for _ in 0..2 {
+            repository_keys.push(
+                local_repository
+                    .start("test_user", &addr.ip(), "comexample")
+                    .await?,
+            );
+        }
+
+        let app_state = AppState::new(
+            local_repository,
+            PortalApiClient::client("http://localhost")?,
+            AppConfig::with_overrides(&[])?,
+        );
+        let user_token = UserToken::from_token(&BASE64_STANDARD.encode("test_user:test_password"))?;
+        let app = Router::new()
+            .route("/bulk/promote", post(staging_bulk_promote))
+            .with_state(app_state.clone())
+            .layer(Extension(user_token))
+            .layer(MockConnectInfo(addr));
+        let promote_request = |repository_key: &RepositoryKey, auto_drop_after_release: bool| {
+            axum::http::Request::post("/bulk/promote")
+                .header(HOST, "localhost")
+                .header(USER_AGENT, "test")
+                .header(CONTENT_TYPE, "application/json")
+                .body(Body::from(format!(
+                    r#"{{"data": {{"stagedRepositoryIds": ["{}"], "description": "", "autoDropAfterRelease": {auto_drop_after_release}}}}}"#,
+                    repository_key.get_repository_id()
+                )))
+        };
+
+        let response = app
+            .clone()
+            .oneshot(promote_request(&repository_keys[0], true)?)
+            .await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(!app_state.repository.exists(&repository_keys[0]).await);
+
+        let response = app
+            .oneshot(promote_request(&repository_keys[1], false)?)
+            .await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(matches!(
+            app_state.repository.get_state(&repository_keys[1]).await?,
+            RepositoryState::Released
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn finish_keeps_repository_for_status_polls() -> eyre::Result<()> {
+        let mock_server = wiremock::MockServer::start().await;
+        wiremock::Mock::given(wiremock::matchers::method("POST"))
+            .and(wiremock::matchers::path("/api/v1/publisher/upload"))
+            .respond_with(
+                wiremock::ResponseTemplate::new(201).set_body_string("test_deployment_id"),
+            )
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+
+        let addr = SocketAddr::from(([127, 0, 0, 1], 2727));
+        let local_repository = LocalRepository::new()?;
+        let mut repository_keys = Vec::new();
+        for _ in 0..2 {
+            let repository_key = stage_jar(&local_repository).await?;
+            repository_keys.push(repository_key);
+        }
+
+        let app_state = AppState::new(
+            local_repository,
+            PortalApiClient::client(&mock_server.uri())?,
+            AppConfig::with_overrides(&[])?,
+        );
+        let user_token = UserToken::from_token(&BASE64_STANDARD.encode("test_user:test_password"))?;
+        let app = Router::new()
+            .route(
+                "/profiles/:profile_id/finish",
+                post(staging_profiles_finish_endpoint),
+            )
+            .with_state(app_state.clone())
+            .layer(Extension(user_token))
+            .layer(MockConnectInfo(addr));
+
+        let response = app
+            .clone()
+            .oneshot(
+                axum::http::Request::post("/profiles/comexample/finish")
+                    .header(HOST, "localhost")
+                    .header(USER_AGENT, "test")
+                    .header(CONTENT_TYPE, "application/xml")
+                    .body(Body::from(
+                        "<promoteRequest><data><stagedRepositoryId>comexample-0</stagedRepositoryId><description>com.example:example:0.1.0</description><autoDropAfterRelease>true</autoDropAfterRelease></data></promoteRequest>",
+                    ))?,
+            )
+            .await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(app_state.repository.exists(&repository_keys[0]).await);
+
+        let response = app
+            .oneshot(
+                axum::http::Request::post("/profiles/comexample/finish")
+                    .header(HOST, "localhost")
+                    .header(USER_AGENT, "test")
+                    .header(CONTENT_TYPE, "application/json")
+                    .body(Body::from(
+                        r#"{"data": {"stagedRepositoryId": "comexample-1", "description": "", "targetRepositoryId": "releases"}}"#,
+                    ))?,
+            )
+            .await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(app_state.repository.exists(&repository_keys[1]).await);
+
+        mock_server.verify().await;
+
+        Ok(())
+    }
+
+    #[test]
+    fn evaluate_subgroup_profiles() {
+        let namespaces = NamespaceSet::new(["com.example"]);
+
+        assert_eq!(
+            profile_namespace("com.example", &namespaces),
+            Some("com.example".to_string())
+        );
+        assert_eq!(
+            profile_namespace("com.example.sub", &namespaces),
+            Some("com.example".to_string())
+        );
+        assert_eq!(profile_namespace("com.example2", &namespaces), None);
+
+        // without configured namespaces, each group is its own profile
+        assert_eq!(
+            profile_namespace("com.example2", &NamespaceSet::default()),
+            Some("com.example2".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn list_configured_namespaces() -> eyre::Result<()> {
+        let app_state = AppState::new(
+            LocalRepository::new()?,
+            PortalApiClient::client("http://localhost")?,
+            AppConfig::with_overrides(&[("namespaces", "com.example, org.example")])?,
+        );
+        let app = Router::new()
+            .route("/profiles", get(staging_profiles_list_endpoint))
+            .with_state(app_state);
+
+        let response = app
+            .oneshot(
+                axum::http::Request::get("/profiles")
+                    .header(HOST, "localhost")
+                    .header(USER_AGENT, "test")
+                    .header(ACCEPT, "application/json")
+                    .body(Body::empty())?,
+            )
+            .await?;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await?;
+        let body: serde_json::Value = serde_json::from_slice(&body)?;
+        let profiles = body["data"]
+            .as_array()
+            .ok_or_else(|| eyre::eyre!("Expected a list of profiles: {body}"))?
+            .iter()
+            .map(|profile| {
+                (
+                    profile["id"].as_str().unwrap_or_default(),
+                    profile["name"].as_str().unwrap_or_default(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            profiles,
+            vec![
+                ("com.example", "com.example"),
+                ("org.example", "org.example")
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn numeric_profile_ids_round_trip() -> eyre::Result<()> {
+        let addr = SocketAddr::from(([127, 0, 0, 1], 2727));
+        let app_state = AppState::new(
+            LocalRepository::new()?,
+            PortalApiClient::client("http://localhost")?,
+            AppConfig::with_overrides(&[("numeric_profile_ids", "true")])?,
+        );
+        let user_token = UserToken::from_token(&BASE64_STANDARD.encode("test_user:test_password"))?;
+        let app = Router::new()
+            .route("/profile_evaluate", get(staging_profile_evaluate_endpoint))
+            .route("/profiles/:profile_id", get(staging_profiles_endpoint))
+            .route(
+                "/profiles/:profile_id/start",
+                post(staging_profiles_start_endpoint),
+            )
+            .with_state(app_state.clone())
+            .layer(Extension(user_token))
+            .layer(MockConnectInfo(addr));
+        let send = |request: axum::http::request::Builder, body: &'static str| {
+            let app = app.clone();
+            async move {
+                let response = app
+                    .oneshot(
+                        request
+                            .header(HOST, "localhost")
+                            .header(USER_AGENT, "test")
+                            .header(ACCEPT, "application/json")
+                            .header(CONTENT_TYPE, "application/json")
+                            .body(Body::from(body))?,
+                    )
+                    .await?;
+                assert_eq!(response.status(), StatusCode::OK);
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX).await?;
+                Ok::<_, eyre::Report>(serde_json::from_slice::<serde_json::Value>(&body)?)
+            }
+        };
+
+        let evaluated = send(
+            axum::http::Request::get("/profile_evaluate?a=example&t=maven2&v=0.1.0&g=com.example"),
+            "",
+        )
+        .await?;
+        let profile_id = evaluated["data"][0]["id"]
+            .as_str()
+            .ok_or_else(|| eyre::eyre!("No profile in {evaluated}"))?
+            .to_string();
+        assert!(profile_id.parse::<u64>().is_ok(), "{profile_id}");
+        assert_eq!(evaluated["data"][0]["name"], "com.example");
+
+        let profile = send(
+            axum::http::Request::get(format!("/profiles/{profile_id}")),
+            "",
+        )
+        .await?;
+        assert_eq!(profile["data"]["id"], profile_id.as_str());
+        assert_eq!(profile["data"]["name"], "com.example");
+
+        send(
+            axum::http::Request::post(format!("/profiles/{profile_id}/start")),
+            r#"{"data": {"description": "test"}}"#,
+        )
+        .await?;
+        let repositories = app_state
+            .repository
+            .list_repositories("test_user", &addr.ip())
+            .await?;
+        assert_eq!(repositories.len(), 1);
+        assert_eq!(repositories[0].0.get_profile_id(), "com.example");
+
+        Ok(())
+    }
 }
@@ -1,29 +1,48 @@
-use axum::extract::Host;
+use axum::extract::{Host, State};
+use axum::http::HeaderMap;
+use axum::response::{IntoResponse, Response};
 use axum_extra::headers::UserAgent;
 use axum_extra::TypedHeader;
+use serde::Serialize;
 use tracing::instrument;
 
-use crate::errors::ApiError;
-use crate::extract::Xml;
+use crate::extract::{accept_content_type, respond_to_accepts_header, ContentType, Xml};
+use crate::state::AppState;
 
-#[instrument]
+pub(crate) const DEFAULT_NEXUS_VERSION: &str = "2.15.1-02";
+pub(crate) const DEFAULT_NEXUS_EDITION: &str = "PRO";
+
+/// The status of the proxy as an NXRM2 server, as XML unless JSON is accepted
+#[instrument(skip(app_state, headers))]
 pub(crate) async fn status_endpoint(
     Host(host): Host,
     TypedHeader(_user_agent): TypedHeader<UserAgent>,
-) -> Result<Xml<StatusResponse>, ApiError> {
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+) -> Response {
     tracing::debug!("Request to get status");
-    let status = StatusResponse::new(host);
+    let status = StatusResponse::new(
+        app_state.app_config.base_url(&host),
+        &app_state.app_config.nexus_version,
+        &app_state.app_config.nexus_edition,
+    );
 
-    Ok(Xml(status))
+    // older clients check the status without an Accept header, expecting XML
+    match accept_content_type(&headers) {
+        Ok(ContentType::Xml | ContentType::Json) => respond_to_accepts_header(&headers, status),
+        _ => Xml(status).into_response(),
+    }
 }
 
-#[derive(Debug, ex_em_ell::ToXmlDocument)]
+#[derive(Debug, Serialize, ex_em_ell::ToXmlDocument)]
+#[serde(rename_all = "camelCase")]
 #[ex_em_ell(rename = "status")]
 pub(crate) struct StatusResponse {
     data: Data,
 }
 
-#[derive(Debug, ex_em_ell::ToXmlElement)]
+#[derive(Debug, Serialize, ex_em_ell::ToXmlElement)]
+#[serde(rename_all = "camelCase")]
 struct Data {
     app_name: String,
     formatted_app_name: String,
@@ -31,10 +50,13 @@ struct Data {
     api_version: String,
     edition_long: String,
     edition_short: String,
+    #[serde(rename = "attributionsURL")]
     #[ex_em_ell(rename = "attributionsURL")]
     attributions_url: String,
+    #[serde(rename = "purchaseURL")]
     #[ex_em_ell(rename = "purchaseURL")]
     purchase_url: String,
+    #[serde(rename = "userLicenseURL")]
     #[ex_em_ell(rename = "userLicenseURL")]
     user_license_url: String,
     state: String,
@@ -51,15 +73,22 @@ struct Data {
 }
 
 impl StatusResponse {
-    fn new(base_url: String) -> Self {
+    /// The status of an NXRM2 server, reporting the provided version and short edition name
+    fn new(base_url: String, version: &str, edition: &str) -> Self {
+        let edition_long = match edition {
+            "PRO" => "Professional",
+            "OSS" => "Open Source",
+            edition => edition,
+        };
+
         Self {
             data: Data {
                 app_name: "Nexus Repository Manager".to_string(),
                 formatted_app_name: "Nexus Repository Manager".to_string(),
-                version: "2.15.1-02".to_string(),
-                api_version: "2.15.1-02".to_string(),
-                edition_long: "Professional".to_string(),
-                edition_short: "PRO".to_string(),
+                version: version.to_string(),
+                api_version: version.to_string(),
+                edition_long: edition_long.to_string(),
+                edition_short: edition.to_string(),
                 attributions_url: "http://links.sonatype.com/products/nexus/pro/attributions"
                     .to_string(),
                 purchase_url: "http://links.sonatype.com/products/nexus/pro/store".to_string(),
@@ -83,10 +112,15 @@ impl StatusResponse {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::AppConfig;
 
     #[test]
     fn test_xml_serialization() -> eyre::Result<()> {
-        let status_result = StatusResponse::new("https://s01.oss.sonatype.org".to_string());
+        let status_result = StatusResponse::new(
+            "https://s01.oss.sonatype.org".to_string(),
+            DEFAULT_NEXUS_VERSION,
+            DEFAULT_NEXUS_EDITION,
+        );
         let actual_state_xml = ex_em_ell::to_string_pretty(&status_result)?;
         let expected_state_xml = r#"<?xml version="1.0" encoding="utf-8"?>
 <status>
@@ -118,4 +152,64 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_json_serialization() -> eyre::Result<()> {
+        let status_result = StatusResponse::new(
+            "https://s01.oss.sonatype.org".to_string(),
+            DEFAULT_NEXUS_VERSION,
+            DEFAULT_NEXUS_EDITION,
+        );
+        let actual_state_json = serde_json::to_string_pretty(&status_result)?;
+        let expected_state_json = r#"{
+  "data": {
+    "appName": "Nexus Repository Manager",
+    "formattedAppName": "Nexus Repository Manager",
+    "version": "2.15.1-02",
+    "apiVersion": "2.15.1-02",
+    "editionLong": "Professional",
+    "editionShort": "PRO",
+    "attributionsURL": "http://links.sonatype.com/products/nexus/pro/attributions",
+    "purchaseURL": "http://links.sonatype.com/products/nexus/pro/store",
+    "userLicenseURL": "http://links.sonatype.com/products/nexus/pro/eula",
+    "state": "STARTED",
+    "initializedAt": "1970-01-01 00:00:00.000 UTC",
+    "startedAt": "1970-01-01 00:00:00.000 UTC",
+    "lastConfigChange": "1970-01-01 00:00:00.000 UTC",
+    "firstStart": false,
+    "instanceUpgraded": false,
+    "configurationUpgraded": false,
+    "baseUrl": "https://s01.oss.sonatype.org",
+    "licenseInstalled": true,
+    "licenseExpired": false,
+    "trialLicense": false
+  }
+}"#;
+
+        assert_eq!(actual_state_json, expected_state_json);
+
+        Ok(())
+    }
+
+    #[test]
+    fn configured_version() -> eyre::Result<()> {
+        let app_config = AppConfig::with_overrides(&[
+            ("nexus_version", "2.14.20-02"),
+            ("nexus_edition", "OSS"),
+        ])?;
+
+        let status_result = StatusResponse::new(
+            "https://s01.oss.sonatype.org".to_string(),
+            &app_config.nexus_version,
+            &app_config.nexus_edition,
+        );
+        let actual_state_xml = ex_em_ell::to_string_pretty(&status_result)?;
+
+        assert!(actual_state_xml.contains("<version>2.14.20-02</version>"));
+        assert!(actual_state_xml.contains("<apiVersion>2.14.20-02</apiVersion>"));
+        assert!(actual_state_xml.contains("<editionLong>Open Source</editionLong>"));
+        assert!(actual_state_xml.contains("<editionShort>OSS</editionShort>"));
+
+        Ok(())
+    }
 }
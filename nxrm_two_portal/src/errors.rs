@@ -1,15 +1,50 @@
-use axum::{http::StatusCode, response::IntoResponse};
+use axum::{
+    extract::Request,
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use repository::traits::{FileLimitExceeded, QuotaExceeded, RepositoryNotFound, RepositoryNotOpen};
+use serde::Serialize;
+
+use crate::endpoints::RepositoryNotInProfile;
+use crate::extract::{accept_content_type, respond_to_accepts_header, BodyRejection, ContentType};
+use crate::publish::{BundleTooLarge, PublishInProgress, PublishTimedOut};
 
 pub(crate) struct ApiError(pub(crate) eyre::Error);
 
 impl IntoResponse for ApiError {
     fn into_response(self) -> axum::response::Response {
         tracing::debug!("Returning error to client: {}", self.0);
-        (
-            StatusCode::BAD_REQUEST,
-            format!("Failed to process request: {}", self.0),
-        )
-            .into_response()
+        let status = if self.0.downcast_ref::<QuotaExceeded>().is_some()
+            || self.0.downcast_ref::<FileLimitExceeded>().is_some()
+            || self.0.downcast_ref::<BundleTooLarge>().is_some()
+        {
+            StatusCode::PAYLOAD_TOO_LARGE
+        } else if self.0.downcast_ref::<PublishTimedOut>().is_some() {
+            StatusCode::GATEWAY_TIMEOUT
+        } else if self.0.downcast_ref::<PublishInProgress>().is_some()
+            || self.0.downcast_ref::<RepositoryNotOpen>().is_some()
+        {
+            StatusCode::CONFLICT
+        } else if self.0.downcast_ref::<RepositoryNotFound>().is_some() {
+            StatusCode::NOT_FOUND
+        } else if self.0.downcast_ref::<RepositoryNotInProfile>().is_some() {
+            StatusCode::FORBIDDEN
+        } else if let Some(body_rejection) = self.0.downcast_ref::<BodyRejection>() {
+            body_rejection.status()
+        } else {
+            StatusCode::BAD_REQUEST
+        };
+        let mut response =
+            (status, format!("Failed to process request: {}", self.0)).into_response();
+
+        // the request is not available here, so leave the details for `negotiate_errors`
+        response.extensions_mut().insert(ErrorDetails {
+            status,
+            message: format!("{:#}", self.0),
+        });
+        response
     }
 }
 
@@ -21,3 +56,185 @@ where
         ApiError(value.into())
     }
 }
+
+/// The details of an [ApiError], attached to its plain text response
+#[derive(Clone, Debug)]
+struct ErrorDetails {
+    status: StatusCode,
+    message: String,
+}
+
+/// Replace the plain text body of an [ApiError] with an NXRM2 error document, when requested
+///
+/// Clients that ask for XML or JSON get the same structured errors that NXRM2 returns.
+pub(crate) async fn negotiate_errors(req: Request, next: Next) -> Response {
+    let headers = req.headers().clone();
+    let response = next.run(req).await;
+
+    let Some(error_details) = response.extensions().get::<ErrorDetails>().cloned() else {
+        return response;
+    };
+
+    match accept_content_type(&headers) {
+        Ok(ContentType::Xml | ContentType::Json) => {
+            let mut negotiated_response =
+                respond_to_accepts_header(&headers, NexusErrorResponse::new(error_details.message));
+            *negotiated_response.status_mut() = error_details.status;
+            negotiated_response
+        }
+        _ => response,
+    }
+}
+
+#[derive(Debug, Serialize, ex_em_ell::ToXmlDocument)]
+#[ex_em_ell(rename = "nexus-error")]
+pub(crate) struct NexusErrorResponse {
+    errors: Vec<NexusError>,
+}
+
+#[derive(Debug, Serialize, ex_em_ell::ToXmlElement, ex_em_ell::NamedXmlElement)]
+#[ex_em_ell(name = "error")]
+struct NexusError {
+    id: String,
+    msg: String,
+}
+
+impl NexusErrorResponse {
+    pub(crate) fn new(message: String) -> Self {
+        Self {
+            errors: vec![NexusError {
+                id: "*".to_string(),
+                msg: message,
+            }],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::header::{ACCEPT, CONTENT_TYPE},
+        middleware,
+        routing::get,
+        Router,
+    };
+    use tower::ServiceExt;
+
+    use super::*;
+
+    fn app() -> Router {
+        Router::new()
+            .route(
+                "/error",
+                get(|| async { Err::<(), _>(ApiError(eyre::eyre!("example failure"))) }),
+            )
+            .layer(middleware::from_fn(negotiate_errors))
+    }
+
+    async fn request_error(accept: Option<&str>) -> eyre::Result<(StatusCode, String, String)> {
+        let mut request = Request::get("/error");
+        if let Some(accept) = accept {
+            request = request.header(ACCEPT, accept);
+        }
+
+        let response = app().oneshot(request.body(Body::empty())?).await?;
+        let status = response.status();
+        let content_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .map(|content_type| content_type.to_str().map(String::from))
+            .transpose()?
+            .unwrap_or_default();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await?;
+
+        Ok((status, content_type, String::from_utf8(body.to_vec())?))
+    }
+
+    #[tokio::test]
+    async fn xml_error() -> eyre::Result<()> {
+        let (status, content_type, body) = request_error(Some("application/xml")).await?;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(content_type, "application/xml");
+        let expected_body = r#"<?xml version="1.0" encoding="utf-8"?>
+<nexus-error>
+  <errors>
+    <error>
+      <id>*</id>
+      <msg>example failure</msg>
+    </error>
+  </errors>
+</nexus-error>"#;
+        assert_eq!(body, expected_body);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn json_error() -> eyre::Result<()> {
+        let (status, content_type, body) = request_error(Some("application/json")).await?;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(content_type, "application/json");
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&body)?,
+            serde_json::json!({"errors": [{"id": "*", "msg": "example failure"}]})
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn quota_exceeded_is_payload_too_large() {
+        let response = ApiError(
+            QuotaExceeded {
+                repository_id: "comexample-1".to_string(),
+                max_size: 1024,
+            }
+            .into(),
+        )
+        .into_response();
+
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[test]
+    fn file_limit_exceeded_is_payload_too_large() {
+        let response = ApiError(
+            FileLimitExceeded {
+                repository_id: "comexample-1".to_string(),
+                max_files: 2,
+            }
+            .into(),
+        )
+        .into_response();
+
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[test]
+    fn publish_timed_out_is_gateway_timeout() {
+        let response = ApiError(
+            PublishTimedOut {
+                repository_id: "comexample-1".to_string(),
+                timeout: std::time::Duration::from_secs(600),
+            }
+            .into(),
+        )
+        .into_response();
+
+        assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+    }
+
+    #[tokio::test]
+    async fn plain_text_error() -> eyre::Result<()> {
+        let (status, content_type, body) = request_error(None).await?;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(content_type.starts_with("text/plain"));
+        assert_eq!(body, "Failed to process request: example failure");
+
+        Ok(())
+    }
+}
@@ -1,9 +1,10 @@
 use async_trait::async_trait;
 use axum::body::Body;
 use axum::body::Bytes;
-use axum::extract::FromRequest;
+use axum::extract::{ConnectInfo, FromRef, FromRequest, FromRequestParts};
 use axum::http::header;
 use axum::http::header::CONTENT_TYPE;
+use axum::http::request::Parts;
 use axum::http::HeaderMap;
 use axum::http::HeaderName;
 use axum::http::Request;
@@ -11,12 +12,18 @@ use axum::http::StatusCode;
 use axum::response::IntoResponse;
 use axum::response::Response;
 use axum::Json;
+use ex_em_ell::errors::XmlReadError;
 use eyre::Context;
+use futures::StreamExt;
 use mime::Mime;
+use std::fmt::Display;
+use std::net::{IpAddr, SocketAddr};
 use tracing::instrument;
 
 use crate::errors::ApiError;
 
+const X_FORWARDED_FOR_HEADER: HeaderName = HeaderName::from_static("x-forwarded-for");
+
 pub(crate) struct Xml<T>(pub(crate) T);
 
 impl<T: ex_em_ell::ToXmlDocument> Xml<T> {
@@ -71,7 +78,7 @@ impl From<Mime> for ContentType {
             }
         }
 
-        return ContentType::Unknown;
+        ContentType::Unknown
     }
 }
 
@@ -83,51 +90,244 @@ impl<T, S> FromRequest<S> for XmlOrJson<T>
 where
     T: ex_em_ell::FromXmlDocument + serde::de::DeserializeOwned,
     S: Send + Sync,
+    BodyLogLimit: FromRef<S>,
 {
     type Rejection = ApiError;
 
     #[instrument(skip(req, state))]
     async fn from_request(req: Request<Body>, state: &S) -> Result<Self, Self::Rejection> {
-        let content_type = content_type(req.headers())?;
+        let body_log_limit = BodyLogLimit::from_ref(state);
+        let content_type = content_type(req.headers()).unwrap_or(ContentType::Unknown);
         match content_type {
             ContentType::Xml => {
                 let bytes = Bytes::from_request(req, state).await?;
 
                 if tracing::enabled!(tracing::Level::TRACE) {
-                    match std::str::from_utf8(&bytes) {
-                        Ok(request) => tracing::trace!("Got request: {request}"),
-                        Err(e) => tracing::trace!("Could not parse request as UTF-8: {e}"),
-                    }
+                    tracing::trace!("Got request: {}", LoggedBody::new(&bytes, body_log_limit));
                 }
 
-                let response: T = ex_em_ell::from_reader(bytes.as_ref())?;
+                let response: T = ex_em_ell::from_reader(bytes.as_ref()).map_err(|e| match e {
+                    XmlReadError::ElementReadError { .. } => BodyRejection::Malformed {
+                        format: "XML",
+                        error: e.to_string(),
+                    },
+                    _ => BodyRejection::UnexpectedShape {
+                        format: "XML",
+                        error: e.to_string(),
+                    },
+                })?;
                 Ok(XmlOrJson(response))
             }
             ContentType::Json => {
                 let bytes = Bytes::from_request(req, state).await?;
 
                 if tracing::enabled!(tracing::Level::TRACE) {
-                    match std::str::from_utf8(&bytes) {
-                        Ok(request) => tracing::trace!("Got request: {request}"),
-                        Err(e) => tracing::trace!("Could not parse request as UTF-8: {e}"),
-                    }
+                    tracing::trace!("Got request: {}", LoggedBody::new(&bytes, body_log_limit));
                 }
 
-                let response: T = serde_json::from_reader(bytes.as_ref())?;
+                let response: T = serde_json::from_slice(bytes.as_ref()).map_err(|e| {
+                    if e.classify() == serde_json::error::Category::Data {
+                        BodyRejection::UnexpectedShape {
+                            format: "JSON",
+                            error: e.to_string(),
+                        }
+                    } else {
+                        BodyRejection::Malformed {
+                            format: "JSON",
+                            error: e.to_string(),
+                        }
+                    }
+                })?;
                 Ok(XmlOrJson(response))
             }
-            ContentType::Unknown => Err(ApiError(eyre::eyre!(
-                "Expected a header with application/xml"
-            ))),
+            ContentType::Unknown => {
+                let received = req
+                    .headers()
+                    .get(CONTENT_TYPE)
+                    .map(|content_type| String::from_utf8_lossy(content_type.as_bytes()).into());
+                Err(BodyRejection::UnsupportedContentType(received).into())
+            }
+        }
+    }
+}
+
+/// Why a request body could not be read as the expected request
+#[derive(Debug)]
+pub(crate) enum BodyRejection {
+    /// The `Content-Type` of the body, if any, is neither XML nor JSON
+    UnsupportedContentType(Option<String>),
+    /// The body is not well-formed in the format of its `Content-Type`
+    Malformed { format: &'static str, error: String },
+    /// The body is well-formed, but is not the request that was expected
+    UnexpectedShape { format: &'static str, error: String },
+}
+
+impl BodyRejection {
+    pub(crate) fn status(&self) -> StatusCode {
+        match self {
+            BodyRejection::UnsupportedContentType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            BodyRejection::Malformed { .. } => StatusCode::BAD_REQUEST,
+            BodyRejection::UnexpectedShape { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+        }
+    }
+}
+
+impl Display for BodyRejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BodyRejection::UnsupportedContentType(Some(content_type)) => write!(
+                f,
+                "Expected a Content-Type of application/xml or application/json, but got {content_type:?}"
+            ),
+            BodyRejection::UnsupportedContentType(None) => write!(
+                f,
+                "Expected a Content-Type of application/xml or application/json, but none was provided"
+            ),
+            BodyRejection::Malformed { format, error } => {
+                write!(f, "The {format} request body is malformed: {error}")
+            }
+            BodyRejection::UnexpectedShape { format, error } => write!(
+                f,
+                "The {format} request body is not the expected request: {error}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BodyRejection {}
+
+/// The most bytes of a request body that are written to the logs
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct BodyLogLimit(pub(crate) usize);
+
+/// The start of a request body, kept to be logged
+pub(crate) struct LoggedBody {
+    prefix: Vec<u8>,
+    truncated: Option<usize>,
+}
+
+impl LoggedBody {
+    /// Keep the start of a body that has already been buffered
+    pub(crate) fn new(body: &[u8], BodyLogLimit(limit): BodyLogLimit) -> Self {
+        Self {
+            prefix: body[..body.len().min(limit)].to_vec(),
+            truncated: (body.len() > limit).then_some(limit),
+        }
+    }
+
+    /// Read the start of a body, without buffering any more of it than is logged
+    pub(crate) async fn read(
+        body: Body,
+        BodyLogLimit(limit): BodyLogLimit,
+    ) -> Result<Self, axum::Error> {
+        let mut stream = body.into_data_stream();
+        let mut prefix = Vec::new();
+        // read a byte past the limit to tell a body that fits exactly from one that is too large
+        while prefix.len() <= limit {
+            let Some(chunk) = stream.next().await else {
+                break;
+            };
+            let chunk = chunk?;
+            let wanted = (limit + 1 - prefix.len()).min(chunk.len());
+            prefix.extend_from_slice(&chunk[..wanted]);
+        }
+
+        Ok(Self::new(&prefix, BodyLogLimit(limit)))
+    }
+}
+
+impl Display for LoggedBody {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", String::from_utf8_lossy(&self.prefix))?;
+        if let Some(limit) = self.truncated {
+            write!(f, "... (truncated to {limit} bytes)")?;
+        }
+        Ok(())
+    }
+}
+
+/// Whether to take the client IP from the `Forwarded`/`X-Forwarded-For` headers of a proxy in front
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct TrustForwardedHeaders(pub(crate) bool);
+
+/// The IP address of the client, as seen by a trusted proxy in front or from the connection
+///
+/// Forwarded headers are only trusted when configured, as clients can set them to anything. The
+/// last address in them is used, as that is the one added by the proxy in front.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ClientIp(pub(crate) IpAddr);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ClientIp
+where
+    S: Send + Sync,
+    TrustForwardedHeaders: FromRef<S>,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let ConnectInfo(addr) = ConnectInfo::<SocketAddr>::from_request_parts(parts, state).await?;
+
+        let TrustForwardedHeaders(trust_forwarded_headers) = TrustForwardedHeaders::from_ref(state);
+        if trust_forwarded_headers {
+            if let Some(forwarded_ip) = forwarded_ip(&parts.headers) {
+                return Ok(ClientIp(forwarded_ip));
+            }
+            tracing::debug!("No forwarded client IP, using the address of the connection");
         }
+
+        Ok(ClientIp(addr.ip()))
     }
 }
 
-fn accept_content_type(headers: &HeaderMap) -> eyre::Result<ContentType> {
+/// The last client address in the `Forwarded` header, or else in `X-Forwarded-For`
+///
+/// Earlier addresses are not considered when the last is unusable, as they may come from the client.
+fn forwarded_ip(headers: &HeaderMap) -> Option<IpAddr> {
+    let forwarded = headers
+        .get_all(header::FORWARDED)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .next_back();
+    if let Some(forwarded) = forwarded {
+        return forwarded.split(';').find_map(|pair| {
+            let (name, value) = pair.split_once('=')?;
+            name.trim()
+                .eq_ignore_ascii_case("for")
+                .then(|| parse_forwarded_node(value))
+                .flatten()
+        });
+    }
+
+    headers
+        .get_all(X_FORWARDED_FOR_HEADER)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .next_back()
+        .and_then(|node| node.trim().parse().ok())
+}
+
+/// The IP of a `Forwarded` node, such as `192.0.2.60`, `"192.0.2.60:4711"`, or `"[2001:db8::17]:4711"`
+fn parse_forwarded_node(node: &str) -> Option<IpAddr> {
+    let node = node.trim().trim_matches('"');
+    if let Some(bracketed) = node.strip_prefix('[') {
+        let (ip, _port) = bracketed.split_once(']')?;
+        return ip.parse().ok();
+    }
+
+    node.parse().ok().or_else(|| {
+        let (ip, _port) = node.rsplit_once(':')?;
+        ip.parse().ok()
+    })
+}
+
+pub(crate) fn accept_content_type(headers: &HeaderMap) -> eyre::Result<ContentType> {
     let accept = mime_type_from_header(header::ACCEPT, headers).map(ContentType::from);
 
     match accept {
-        Ok(accept @ (ContentType::Xml | ContentType::Json)) => return Ok(accept),
+        Ok(accept @ (ContentType::Xml | ContentType::Json)) => Ok(accept),
         _ => content_type(headers),
     }
 }
@@ -157,5 +357,196 @@ fn mime_type_from_header(header: HeaderName, headers: &HeaderMap) -> eyre::Resul
 }
 
 fn is_mime_type(expected: &str, mime: &Mime) -> bool {
-    mime.subtype() == expected || mime.suffix().map_or(false, |name| name == expected)
+    mime.subtype() == expected || mime.suffix().is_some_and(|name| name == expected)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Debug, Deserialize, ex_em_ell::FromXmlDocument)]
+    #[ex_em_ell(rename = "promoteRequest")]
+    struct TestRequest {
+        #[allow(dead_code)]
+        data: TestRequestData,
+    }
+
+    #[derive(Debug, Deserialize, ex_em_ell::FromXmlElement)]
+    struct TestRequestData {
+        #[allow(dead_code)]
+        description: String,
+    }
+
+    /// The status and message that a request body is rejected with, if it is
+    async fn rejection(content_type: Option<&str>, body: &str) -> Option<(StatusCode, String)> {
+        let mut request = Request::post("/");
+        if let Some(content_type) = content_type {
+            request = request.header(CONTENT_TYPE, content_type);
+        }
+        let request = request.body(Body::from(body.to_string())).ok()?;
+
+        let Err(ApiError(e)) =
+            XmlOrJson::<TestRequest>::from_request(request, &BodyLogLimit(4096)).await
+        else {
+            return None;
+        };
+        let rejection = e.downcast_ref::<BodyRejection>()?;
+        Some((rejection.status(), rejection.to_string()))
+    }
+
+    #[tokio::test]
+    async fn read_well_formed_bodies() {
+        assert!(rejection(
+            Some("application/xml"),
+            "<promoteRequest><data><description>test</description></data></promoteRequest>",
+        )
+        .await
+        .is_none());
+        assert!(rejection(
+            Some("application/json"),
+            r#"{"data": {"description": "test"}}"#
+        )
+        .await
+        .is_none());
+    }
+
+    #[tokio::test]
+    async fn reject_malformed_bodies() {
+        let (status, message) = rejection(
+            Some("application/xml"),
+            "<promoteRequest><data><description>te",
+        )
+        .await
+        .expect("Accepted truncated XML");
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(message.starts_with("The XML request body is malformed"));
+
+        let (status, message) = rejection(Some("application/json"), r#"{"data": {"descr"#)
+            .await
+            .expect("Accepted truncated JSON");
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(message.starts_with("The JSON request body is malformed"));
+    }
+
+    #[tokio::test]
+    async fn reject_unexpected_shapes() {
+        let (status, message) = rejection(Some("application/json"), r#"{"data": "test"}"#)
+            .await
+            .expect("Accepted the wrong structure");
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(message.starts_with("The JSON request body is not the expected request"));
+
+        let (status, message) = rejection(
+            Some("application/xml"),
+            "<promoteRequest><data></data></promoteRequest>",
+        )
+        .await
+        .expect("Accepted the wrong structure");
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(message.starts_with("The XML request body is not the expected request"));
+    }
+
+    /// The client IP of a request from 10.0.0.1, such as a load balancer, with the headers provided
+    async fn client_ip(trusted: bool, headers: &[(&str, &str)]) -> eyre::Result<IpAddr> {
+        let mut request =
+            Request::get("/").extension(ConnectInfo(SocketAddr::from(([10, 0, 0, 1], 2727))));
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        let (mut parts, _body) = request.body(())?.into_parts();
+
+        let ClientIp(client_ip) =
+            ClientIp::from_request_parts(&mut parts, &TrustForwardedHeaders(trusted))
+                .await
+                .map_err(|ApiError(e)| e)?;
+        Ok(client_ip)
+    }
+
+    #[tokio::test]
+    async fn trusted_forwarded_headers() -> eyre::Result<()> {
+        assert_eq!(
+            client_ip(true, &[("x-forwarded-for", "203.0.113.7, 192.0.2.60")]).await?,
+            "192.0.2.60".parse::<IpAddr>()?
+        );
+        assert_eq!(
+            client_ip(
+                true,
+                &[(
+                    "forwarded",
+                    r#"for=203.0.113.7, for="[2001:db8::17]:4711";proto=https"#
+                )]
+            )
+            .await?,
+            "2001:db8::17".parse::<IpAddr>()?
+        );
+        assert_eq!(
+            client_ip(true, &[("forwarded", "For=\"192.0.2.60:4711\"")]).await?,
+            "192.0.2.60".parse::<IpAddr>()?
+        );
+        // the connection is all there is to go on without forwarded headers
+        assert_eq!(
+            client_ip(true, &[("x-forwarded-for", "unknown")]).await?,
+            "10.0.0.1".parse::<IpAddr>()?
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn untrusted_forwarded_headers() -> eyre::Result<()> {
+        assert_eq!(
+            client_ip(
+                false,
+                &[
+                    ("x-forwarded-for", "192.0.2.60"),
+                    ("forwarded", "for=192.0.2.60")
+                ]
+            )
+            .await?,
+            "10.0.0.1".parse::<IpAddr>()?
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reject_unsupported_content_types() {
+        let (status, message) = rejection(Some("text/plain"), "description=test")
+            .await
+            .expect("Accepted plain text");
+        assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        assert!(message.contains(r#"but got "text/plain""#));
+
+        let (status, message) = rejection(None, "description=test")
+            .await
+            .expect("Accepted a body without a Content-Type");
+        assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        assert!(message.ends_with("but none was provided"));
+    }
+
+    #[test]
+    fn log_small_body() {
+        let logged_body = LoggedBody::new(b"<promoteRequest />", BodyLogLimit(18));
+
+        assert_eq!(logged_body.to_string(), "<promoteRequest />");
+    }
+
+    #[tokio::test]
+    async fn log_large_body_truncated() -> eyre::Result<()> {
+        // a body that never ends can only be logged if it is not buffered in full
+        let endless_body = Body::from_stream(futures::stream::repeat_with(|| {
+            Ok::<_, std::io::Error>(Bytes::from_static(&[b'a'; 1024]))
+        }));
+
+        let logged_body = LoggedBody::read(endless_body, BodyLogLimit(2000)).await?;
+
+        assert_eq!(
+            logged_body.to_string(),
+            format!("{}... (truncated to 2000 bytes)", "a".repeat(2000))
+        );
+
+        Ok(())
+    }
 }
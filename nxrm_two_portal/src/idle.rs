@@ -0,0 +1,30 @@
+use std::time::Duration;
+
+use tracing::instrument;
+
+use crate::state::AppState;
+
+/// Longest wait between checks for idle repositories
+const MAX_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Remove the open repositories that have gone without activity for longer than `max_idle`, until the proxy stops
+///
+/// Closed repositories are left to their publish, which removes their files once Central accepts them.
+#[instrument(skip(app_state))]
+pub async fn remove_idle_repositories(app_state: AppState, max_idle: Duration) {
+    let mut interval = tokio::time::interval(max_idle.min(MAX_CHECK_INTERVAL));
+    loop {
+        interval.tick().await;
+        match app_state
+            .repository
+            .remove_idle_repositories(max_idle)
+            .await
+        {
+            Ok(removed) if !removed.is_empty() => {
+                tracing::info!("Removed {} idle repositories", removed.len())
+            }
+            Ok(_) => {}
+            Err(e) => tracing::error!("Failed to remove the idle repositories: {e:#}"),
+        }
+    }
+}
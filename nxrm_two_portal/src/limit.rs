@@ -0,0 +1,225 @@
+use std::collections::HashMap;
+use std::num::NonZeroU32;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use axum::{
+    extract::{Request, State},
+    http::{header::RETRY_AFTER, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use tokio::time::Instant;
+use tracing::instrument;
+
+use crate::state::AppState;
+
+/// How long clients are asked to wait before retrying a rejected upload
+const RETRY_AFTER_SECONDS: &str = "5";
+
+/// Reject uploads beyond the configured number in progress with `503 Service Unavailable`
+#[instrument(skip(app_state, req, next))]
+pub async fn limit_uploads(
+    State(app_state): State<AppState>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let Ok(_permit) = app_state.upload_permits.try_acquire() else {
+        tracing::warn!("Too many concurrent uploads, rejecting the request");
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            [(RETRY_AFTER, RETRY_AFTER_SECONDS)],
+        )
+            .into_response();
+    };
+
+    next.run(req).await
+}
+
+/// Spaces out uploads to Central, allowing bursts of up to a minute's worth for each namespace
+///
+/// Only namespaces with uploads in the last minute are kept track of.
+#[derive(Debug)]
+pub struct UploadRateLimiter {
+    uploads_per_minute: NonZeroU32,
+    buckets: Mutex<HashMap<String, TokenBucket>>,
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    /// Uploads that may start without waiting, negative when uploads are already waiting
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl UploadRateLimiter {
+    pub fn new(uploads_per_minute: NonZeroU32) -> Self {
+        Self {
+            uploads_per_minute,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Wait until an upload to the namespace is allowed
+    ///
+    /// Uploads that have to wait are let through in the order that they arrived.
+    pub async fn acquire(&self, namespace: &str) {
+        let capacity = f64::from(self.uploads_per_minute.get());
+        let tokens_per_second = capacity / 60.0;
+
+        let wait = {
+            let mut buckets = self.buckets.lock().expect("rate limiter lock poisoned");
+            let now = Instant::now();
+            for bucket in buckets.values_mut() {
+                bucket.refill(now, capacity, tokens_per_second);
+            }
+            // a full bucket is no different from a new one
+            buckets.retain(|_, bucket| bucket.tokens < capacity);
+
+            let bucket = buckets.entry(namespace.to_string()).or_insert(TokenBucket {
+                tokens: capacity,
+                refilled_at: now,
+            });
+
+            // take the token now, even if it has yet to be refilled, to hold a place in line
+            bucket.tokens -= 1.0;
+            (bucket.tokens < 0.0)
+                .then(|| Duration::from_secs_f64(-bucket.tokens / tokens_per_second))
+        };
+
+        if let Some(wait) = wait {
+            tracing::info!(
+                "Waiting {} seconds to upload to {namespace}, to stay within {} uploads per minute",
+                wait.as_secs_f64(),
+                self.uploads_per_minute
+            );
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+impl TokenBucket {
+    fn refill(&mut self, now: Instant, capacity: f64, tokens_per_second: f64) {
+        let elapsed = now.duration_since(self.refilled_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * tokens_per_second).min(capacity);
+        self.refilled_at = now;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, middleware, routing::put, Router};
+    use eyre::OptionExt;
+    use portal_api::PortalApiClient;
+    use repository::local_repository::LocalRepository;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::config::AppConfig;
+
+    #[tokio::test]
+    async fn enforce_upload_limit() -> eyre::Result<()> {
+        let app_config = AppConfig::with_overrides(&[("max_concurrent_uploads", "1")])?;
+        let app_state = AppState::new(
+            LocalRepository::new()?,
+            PortalApiClient::client("http://localhost")?,
+            app_config,
+        );
+        let app = Router::new()
+            .route(
+                "/upload",
+                put(|| async { StatusCode::CREATED }).route_layer(middleware::from_fn_with_state(
+                    app_state.clone(),
+                    limit_uploads,
+                )),
+            )
+            .with_state(app_state.clone());
+        let upload = || Request::put("/upload").body(Body::empty());
+
+        // an upload in progress uses the only permit
+        let permit = app_state.upload_permits.try_acquire()?;
+        let response = app.clone().oneshot(upload()?).await?;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(
+            response.headers().get(RETRY_AFTER),
+            Some(&RETRY_AFTER_SECONDS.parse()?)
+        );
+
+        drop(permit);
+        let response = app.oneshot(upload()?).await?;
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn throttle_upload_bursts() -> eyre::Result<()> {
+        let rate_limiter = UploadRateLimiter::new(NonZeroU32::new(2).ok_or_eyre("zero rate")?);
+        let start = Instant::now();
+
+        let mut started = Vec::new();
+        for _ in 0..4 {
+            rate_limiter.acquire("com.example").await;
+            started.push(start.elapsed());
+        }
+        assert_eq!(
+            started,
+            vec![
+                Duration::ZERO,
+                Duration::ZERO,
+                Duration::from_secs(30),
+                Duration::from_secs(60)
+            ]
+        );
+
+        // each namespace has its own limit
+        rate_limiter.acquire("org.example").await;
+        assert_eq!(start.elapsed(), Duration::from_secs(60));
+
+        // waiting uploads are spaced out too
+        let waiting = futures::future::join_all((0..2).map(|_| async {
+            rate_limiter.acquire("com.example").await;
+            start.elapsed()
+        }))
+        .await;
+        assert_eq!(
+            waiting,
+            vec![Duration::from_secs(90), Duration::from_secs(120)]
+        );
+
+        Ok(())
+    }
+    #[tokio::test(start_paused = true)]
+    async fn forget_refilled_namespaces() -> eyre::Result<()> {
+        let rate_limiter = UploadRateLimiter::new(NonZeroU32::new(2).ok_or_eyre("zero rate")?);
+        let namespaces = |rate_limiter: &UploadRateLimiter| {
+            let mut namespaces = rate_limiter
+                .buckets
+                .lock()
+                .expect("rate limiter lock poisoned")
+                .keys()
+                .cloned()
+                .collect::<Vec<_>>();
+            namespaces.sort();
+            namespaces
+        };
+
+        rate_limiter.acquire("com.example").await;
+        tokio::time::advance(Duration::from_secs(20)).await;
+        rate_limiter.acquire("org.example").await;
+        assert_eq!(
+            namespaces(&rate_limiter),
+            vec!["com.example", "org.example"]
+        );
+
+        // a token comes back every 30 seconds, so only com.example is full again
+        tokio::time::advance(Duration::from_secs(20)).await;
+        rate_limiter.acquire("net.example").await;
+        assert_eq!(
+            namespaces(&rate_limiter),
+            vec!["net.example", "org.example"]
+        );
+
+        Ok(())
+    }
+}
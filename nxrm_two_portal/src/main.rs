@@ -1,38 +1,28 @@
 use std::net::SocketAddr;
+use std::time::Duration;
 
-use auth::auth;
-use axum::{
-    middleware,
-    routing::{get, post, put},
-    Router,
-};
 use portal_api::PortalApiClient;
 use tokio::net::TcpListener;
 use tracing_subscriber::{fmt, prelude::*, EnvFilter};
 
 use repository::local_repository::LocalRepository;
 
+mod app;
 mod auth;
+mod compression;
 mod config;
 mod endpoints;
 mod errors;
 mod extract;
+mod idle;
+mod limit;
+mod pending;
 mod publish;
 mod state;
+mod user_agent;
 
-use config::AppConfig;
-use endpoints::{
-    fallback::fallback,
-    manual::manual_upload_default_repository,
-    staging::{
-        staging_bulk_close, staging_bulk_promote, staging_deploy_by_repository_id,
-        staging_deploy_by_repository_id_get, staging_deploy_maven2, staging_deploy_maven2_get,
-        staging_profile_evaluate_endpoint, staging_profiles_endpoint,
-        staging_profiles_finish_endpoint, staging_profiles_list_endpoint,
-        staging_profiles_start_endpoint, staging_repository,
-    },
-    status::status_endpoint,
-};
+use app::build_app;
+use config::{AppConfig, RepositoryBackend};
 use state::AppState;
 
 #[tokio::main]
@@ -45,54 +35,41 @@ async fn main() -> eyre::Result<()> {
     let app_config = AppConfig::load()?;
     tracing::debug!("Loaded configuration: {app_config:?}");
 
-    let local_repository = LocalRepository::new()?;
+    let local_repository = match app_config.repository_backend {
+        RepositoryBackend::Local => match &app_config.repository_root {
+            Some(repository_root) => {
+                LocalRepository::with_root(repository_root, app_config.repository_options()?)?
+            }
+            None => LocalRepository::with_options(app_config.repository_options()?)?,
+        },
+    };
     tracing::debug!("Initialized a local repository");
 
-    let portal_api_client = PortalApiClient::client(&app_config.central_url)?;
+    let mut portal_api_client_builder = PortalApiClient::builder()
+        .host(&app_config.central_url)
+        .auth_scheme(app_config.central_auth_scheme);
+    if let Some(allowed_central_hosts) = app_config.allowed_central_hosts() {
+        portal_api_client_builder = portal_api_client_builder.allowed_hosts(allowed_central_hosts);
+    }
+    let portal_api_client = portal_api_client_builder.build()?;
     tracing::debug!("Initialized a Portal API client");
 
-    let app_state = AppState::new(local_repository, portal_api_client);
+    let app_port = app_config.app_port;
+    let app_state = AppState::new(local_repository, portal_api_client, app_config);
 
-    let staging_endpoints = Router::new()
-        .route("/profile_evaluate", get(staging_profile_evaluate_endpoint))
-        .route("/profiles", get(staging_profiles_list_endpoint))
-        .route("/profiles/:profile_id", get(staging_profiles_endpoint))
-        .route(
-            "/profiles/:profile_id/start",
-            post(staging_profiles_start_endpoint),
-        )
-        .route(
-            "/deployByRepositoryId/:staging_repository_id/*file_path",
-            put(staging_deploy_by_repository_id).get(staging_deploy_by_repository_id_get),
-        )
-        .route(
-            "/profiles/:profile_id/finish",
-            post(staging_profiles_finish_endpoint),
-        )
-        .route("/repository/:repository_id", get(staging_repository))
-        .route("/bulk/close", post(staging_bulk_close))
-        .route("/bulk/promote", post(staging_bulk_promote))
-        // required for Gradle maven-publish plugin
-        .route(
-            "/deploy/maven2/*file_path",
-            put(staging_deploy_maven2).get(staging_deploy_maven2_get),
-        )
-        .route_layer(middleware::from_fn(auth));
+    pending::log_pending_uploads(&app_state).await;
 
-    let manual_endpoints = Router::new()
-        .route("/upload", post(manual_upload_default_repository))
-        .route_layer(middleware::from_fn(auth));
+    if let Some(idle_repository_ttl_secs) = app_state.app_config.idle_repository_ttl_secs {
+        let max_idle = Duration::from_secs(idle_repository_ttl_secs.get());
+        tokio::spawn(idle::remove_idle_repositories(app_state.clone(), max_idle));
+    }
 
-    let app = Router::new()
-        .route("/service/local/status", get(status_endpoint))
-        .nest("/service/local/staging", staging_endpoints)
-        .nest("/manual", manual_endpoints)
-        .with_state(app_state)
-        .fallback(fallback);
+    let app = build_app(app_state);
 
-    tracing::info!("Listening on port: {}", app_config.app_port);
-    let listener = TcpListener::bind(format!("0.0.0.0:{}", app_config.app_port)).await?;
+    tracing::info!("Listening on port: {app_port}");
+    let listener = TcpListener::bind(format!("0.0.0.0:{app_port}")).await?;
 
+    // answers `Expect: 100-continue` with `100 Continue` once a handler starts reading the body
     axum::serve(
         listener,
         app.into_make_service_with_connect_info::<SocketAddr>(),
@@ -0,0 +1,289 @@
+use std::net::IpAddr;
+use std::path::PathBuf;
+
+use eyre::WrapErr;
+use portal_api::api_types::PublishingType;
+use repository::checksums::ChecksumAlgorithm;
+use repository::traits::RepositoryKey;
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+
+use crate::state::AppState;
+
+/// Uploads to Central that have yet to complete, kept on disk so that they survive a crash
+///
+/// Each is a `.json` record with the `.zip` bundle next to it, named after the repository. User
+/// tokens are never kept, so an upload is retried with the credentials of whoever retries it.
+#[derive(Debug)]
+pub struct PendingUploads {
+    dir: PathBuf,
+}
+
+/// An upload to Central that was started, but not seen to complete
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingUpload {
+    pub user_id: String,
+    pub ip_addr: IpAddr,
+    pub repository_id: String,
+    pub deployment_name: String,
+    pub publishing_type: PublishingType,
+}
+
+impl PendingUpload {
+    pub fn repository_key(&self) -> eyre::Result<RepositoryKey> {
+        Ok(RepositoryKey::from_user_context_and_repository_id(
+            &self.user_id,
+            &self.ip_addr,
+            &self.repository_id,
+        )?)
+    }
+}
+
+impl PendingUploads {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Keep the bundle of a repository that is about to be uploaded, until it is removed
+    #[instrument(skip(self, bundle))]
+    pub async fn record(
+        &self,
+        repository_key: &RepositoryKey,
+        deployment_name: &str,
+        publishing_type: PublishingType,
+        bundle: &[u8],
+    ) -> eyre::Result<()> {
+        tokio::fs::create_dir_all(&self.dir)
+            .await
+            .wrap_err_with(|| format!("Failed to create {:?}", self.dir))?;
+
+        let name = record_name(repository_key);
+        tokio::fs::write(self.dir.join(format!("{name}.zip")), bundle).await?;
+
+        let pending_upload = PendingUpload {
+            user_id: repository_key.user_id.clone(),
+            ip_addr: repository_key.ip_addr,
+            repository_id: repository_key.get_repository_id(),
+            deployment_name: deployment_name.to_string(),
+            publishing_type,
+        };
+
+        // the record is written last, and never partially, so that its bundle is always complete
+        let record_path = self.dir.join(format!("{name}.json"));
+        let partial_record_path = self.dir.join(format!("{name}.json.partial"));
+        tokio::fs::write(&partial_record_path, serde_json::to_vec(&pending_upload)?).await?;
+        tokio::fs::rename(&partial_record_path, &record_path).await?;
+        tracing::debug!("Recorded the pending upload: {record_path:?}");
+
+        Ok(())
+    }
+
+    /// Forget the upload of a repository, once it has completed or been rolled back
+    #[instrument(skip(self))]
+    pub async fn remove(&self, repository_key: &RepositoryKey) -> eyre::Result<()> {
+        let name = record_name(repository_key);
+        for file_name in [format!("{name}.json"), format!("{name}.zip")] {
+            match tokio::fs::remove_file(self.dir.join(file_name)).await {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The upload of a repository, if it has yet to complete
+    pub async fn get(&self, repository_key: &RepositoryKey) -> eyre::Result<Option<PendingUpload>> {
+        let record_path = self
+            .dir
+            .join(format!("{}.json", record_name(repository_key)));
+        match tokio::fs::read(&record_path).await {
+            Ok(record) => Ok(Some(serde_json::from_slice(&record)?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Every upload that has yet to complete, ordered by repository
+    pub async fn list(&self) -> eyre::Result<Vec<PendingUpload>> {
+        let mut entries = match tokio::fs::read_dir(&self.dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut pending_uploads = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path
+                .extension()
+                .is_some_and(|extension| extension == "json")
+            {
+                match serde_json::from_slice(&tokio::fs::read(&path).await?) {
+                    Ok(pending_upload) => pending_uploads.push(pending_upload),
+                    Err(e) => tracing::warn!("Skipping unreadable pending upload {path:?}: {e}"),
+                }
+            }
+        }
+        pending_uploads.sort_by(|a: &PendingUpload, b: &PendingUpload| {
+            (&a.user_id, &a.repository_id).cmp(&(&b.user_id, &b.repository_id))
+        });
+
+        Ok(pending_uploads)
+    }
+
+    /// Where the bundle of a pending upload is kept
+    pub fn bundle_path(&self, repository_key: &RepositoryKey) -> PathBuf {
+        self.dir
+            .join(format!("{}.zip", record_name(repository_key)))
+    }
+}
+
+/// Log the uploads left pending by a previous run of the proxy
+///
+/// Without the user tokens they need, the uploads wait for their users to retry them through the API.
+#[instrument(skip(app_state))]
+pub async fn log_pending_uploads(app_state: &AppState) {
+    let Some(pending_uploads) = &app_state.pending_uploads else {
+        return;
+    };
+
+    match pending_uploads.list().await {
+        Ok(records) => {
+            for pending_upload in records {
+                tracing::warn!(
+                    "The upload of repository {} by {} is pending until it is retried",
+                    pending_upload.repository_id,
+                    pending_upload.user_id
+                );
+            }
+        }
+        Err(e) => tracing::error!("Failed to list the pending uploads: {e:#}"),
+    }
+}
+
+/// Names the files of a repository's record without anything that could escape the directory
+fn record_name(repository_key: &RepositoryKey) -> String {
+    ChecksumAlgorithm::Sha256.digest(repository_key.to_string().as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use eyre::OptionExt;
+    use portal_api::{Credentials, PortalApiClient};
+    use repository::local_repository::LocalRepository;
+    use repository::options::RepositoryOptions;
+    use repository::traits::RepositoryState;
+    use temp_dir::TempDir;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::*;
+    use crate::config::AppConfig;
+    use crate::publish::tests::stage_jar;
+    use crate::publish::{publish, retry_pending_upload};
+
+    #[tokio::test]
+    async fn retry_interrupted_upload_after_restart() -> eyre::Result<()> {
+        let repository_root = TempDir::new()?;
+        let pending_upload_dir = TempDir::new()?;
+        let app_config = || {
+            AppConfig::with_overrides(&[(
+                "pending_upload_dir",
+                &pending_upload_dir.path().to_string_lossy(),
+            )])
+        };
+
+        // Central never answers the first upload, as if the proxy crashed while waiting on it
+        let unresponsive_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/api/v1/publisher/upload"))
+            .respond_with(ResponseTemplate::new(201).set_delay(Duration::from_secs(60)))
+            .mount(&unresponsive_server)
+            .await;
+
+        let app_state = AppState::new(
+            LocalRepository::with_root(repository_root.path(), RepositoryOptions::default())?,
+            PortalApiClient::client(&unresponsive_server.uri())?,
+            app_config()?,
+        );
+        let repository_key = stage_jar(app_state.repository.as_ref()).await?;
+
+        let interrupted = tokio::time::timeout(
+            Duration::from_millis(500),
+            publish(
+                &app_state.portal_api_client,
+                app_state.repository.as_ref(),
+                &Credentials::new("test_user".to_string(), "test_password".to_string()),
+                &repository_key,
+                PublishingType::Automatic,
+                &app_state.publish_options(),
+            ),
+        )
+        .await;
+        assert!(interrupted.is_err());
+        drop(app_state);
+
+        // after a restart, the upload goes through
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/api/v1/publisher/upload"))
+            .respond_with(ResponseTemplate::new(201).set_body_string("test_deployment_id"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let app_state = AppState::new(
+            LocalRepository::with_root(repository_root.path(), RepositoryOptions::default())?,
+            PortalApiClient::client(&mock_server.uri())?,
+            app_config()?,
+        );
+        let pending_uploads = app_state
+            .pending_uploads
+            .clone()
+            .ok_or_eyre("pending uploads are configured")?;
+        let records = pending_uploads.list().await?;
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].repository_id, repository_key.get_repository_id());
+        assert!(matches!(
+            app_state.repository.get_state(&repository_key).await?,
+            RepositoryState::Closed
+        ));
+        let mut pending_upload_files = tokio::fs::read_dir(pending_upload_dir.path()).await?;
+        while let Some(entry) = pending_upload_files.next_entry().await? {
+            let contents = tokio::fs::read(entry.path()).await?;
+            assert!(
+                !String::from_utf8_lossy(&contents).contains("test_password"),
+                "Kept the user token in {:?}",
+                entry.path()
+            );
+        }
+
+        let deployment_id = retry_pending_upload(
+            &app_state.portal_api_client,
+            app_state.repository.as_ref(),
+            &pending_uploads,
+            &records[0],
+            &Credentials::new("test_user".to_string(), "test_password".to_string()),
+        )
+        .await?;
+        assert_eq!(deployment_id, "test_deployment_id");
+
+        mock_server.verify().await;
+        assert_eq!(
+            app_state
+                .repository
+                .get_deployment_id(&repository_key)
+                .await?,
+            Some("test_deployment_id".to_string())
+        );
+        assert!(pending_uploads.list().await?.is_empty());
+        assert!(!pending_uploads.bundle_path(&repository_key).exists());
+
+        Ok(())
+    }
+}
@@ -1,28 +1,1683 @@
-use portal_api::{api_types::PublishingType, Credentials, PortalApiClient};
-use repository::traits::{Repository, RepositoryKey};
-use tracing::instrument;
+use std::collections::HashSet;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
-#[instrument(skip(portal_api_client, repository, credentials))]
-pub async fn publish<R: Repository>(
+use eyre::WrapErr;
+use portal_api::api_types::{DeploymentState, PublishingType};
+use portal_api::{Credentials, PortalApiClient};
+use repository::checksums::ChecksumAlgorithm;
+use repository::traits::{Repository, RepositoryKey, RepositoryNotOpen, RepositoryState, ZipEntry};
+use serde::{Deserialize, Serialize};
+use tracing::{instrument, Span};
+
+use crate::limit::UploadRateLimiter;
+use crate::pending::{PendingUpload, PendingUploads};
+
+pub const DEFAULT_DEPLOYMENT_NAME_TEMPLATE: &str = "{repository_id}";
+
+/// Marks the deployments created by the proxy in the Central UI
+const DEPLOYMENT_NAME_PROXY_SUFFIX: &str = " (via OSSRH API Proxy)";
+
+const DEPLOYMENT_NAME_PLACEHOLDERS: [&str; 3] = ["repository_id", "user", "namespace"];
+
+/// Longest deployment name sent to Central, in characters
+pub const DEFAULT_MAX_DEPLOYMENT_NAME_LENGTH: usize = 255;
+
+pub const DEFAULT_VALIDATION_POLL_INTERVAL: Duration = Duration::from_secs(5);
+pub const DEFAULT_VALIDATION_TIMEOUT: Duration = Duration::from_secs(300);
+pub const DEFAULT_PUBLISH_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Settings that control how repositories are sent to Central
+#[derive(Debug, Clone)]
+pub struct PublishOptions {
+    /// Build the bundle, but skip uploading it to Central
+    pub dry_run: bool,
+    /// Where to write the bundles built during a dry run, if anywhere
+    pub dry_run_dir: Option<PathBuf>,
+    /// How to name the deployments created at Central
+    pub deployment_name_template: DeploymentNameTemplate,
+    /// Append ` (via OSSRH API Proxy)` to the names of deployments
+    pub append_proxy_suffix: bool,
+    /// Longest deployment name in characters, with longer names truncated before the suffix
+    pub max_deployment_name_length: usize,
+    /// Have Central validate the bundle, then drop the deployment instead of publishing it
+    pub validate_only: bool,
+    /// How often to check on a deployment that is being validated
+    pub validation_poll_interval: Duration,
+    /// How long to wait for Central to validate a deployment
+    pub validation_timeout: Duration,
+    /// How long to wait for Central to accept an upload
+    pub publish_timeout: Duration,
+    /// Largest bundle in bytes that will be sent to Central, unlimited if unset
+    pub max_bundle_size: Option<u64>,
+    /// Where to keep a copy of each bundle uploaded to Central, if anywhere
+    pub bundle_archive_dir: Option<PathBuf>,
+    /// Shared by all publishes, to keep the uploads to each namespace under a rate, if any
+    pub upload_rate_limiter: Option<Arc<UploadRateLimiter>>,
+    /// Where to keep bundles while they are uploaded, so that uploads interrupted by a crash can be retried
+    pub pending_uploads: Option<Arc<PendingUploads>>,
+    /// Shared by all publishes, to reject publishing a repository that is already being published
+    pub publishes_in_flight: Option<Arc<PublishesInFlight>>,
+}
+
+impl Default for PublishOptions {
+    fn default() -> Self {
+        Self {
+            dry_run: false,
+            dry_run_dir: None,
+            deployment_name_template: DeploymentNameTemplate::default(),
+            append_proxy_suffix: true,
+            max_deployment_name_length: DEFAULT_MAX_DEPLOYMENT_NAME_LENGTH,
+            validate_only: false,
+            validation_poll_interval: DEFAULT_VALIDATION_POLL_INTERVAL,
+            validation_timeout: DEFAULT_VALIDATION_TIMEOUT,
+            publish_timeout: DEFAULT_PUBLISH_TIMEOUT,
+            max_bundle_size: None,
+            bundle_archive_dir: None,
+            upload_rate_limiter: None,
+            pending_uploads: None,
+            publishes_in_flight: None,
+        }
+    }
+}
+
+impl PublishOptions {
+    /// The name to give the deployment of a repository at Central
+    ///
+    /// Control characters are removed, and names over the maximum length are truncated, keeping
+    /// the proxy suffix.
+    pub fn deployment_name(&self, repository_key: &RepositoryKey) -> String {
+        let deployment_name: String = self
+            .deployment_name_template
+            .render(repository_key)
+            .chars()
+            .filter(|c| !c.is_control())
+            .collect();
+        let suffix = if self.append_proxy_suffix {
+            DEPLOYMENT_NAME_PROXY_SUFFIX
+        } else {
+            ""
+        };
+
+        let max_length = self
+            .max_deployment_name_length
+            .saturating_sub(suffix.chars().count());
+        let deployment_name = match deployment_name.char_indices().nth(max_length) {
+            Some((truncated_at, _)) => {
+                tracing::warn!(
+                    "Truncating the deployment name {deployment_name:?} to {} characters",
+                    self.max_deployment_name_length
+                );
+                &deployment_name[..truncated_at]
+            }
+            None => &deployment_name,
+        };
+
+        format!("{deployment_name}{suffix}")
+    }
+}
+
+/// The name given to a deployment at Central
+///
+/// Supports the `{repository_id}`, `{user}`, and `{namespace}` placeholders, where the namespace
+/// is the staging profile the repository was started in. The template is parsed once, so that a
+/// placeholder in a substituted value is left as it is.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct DeploymentNameTemplate {
+    template: String,
+    parts: Vec<TemplatePart>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TemplatePart {
+    Text(String),
+    RepositoryId,
+    User,
+    Namespace,
+}
+
+impl DeploymentNameTemplate {
+    pub fn parse(template: &str) -> eyre::Result<Self> {
+        let mut parts = Vec::new();
+        let mut remaining = template;
+        while let Some(start) = remaining.find('{') {
+            if start > 0 {
+                parts.push(TemplatePart::Text(remaining[..start].to_string()));
+            }
+            let after_start = &remaining[start + 1..];
+            let end = after_start.find('}').ok_or_else(|| {
+                eyre::eyre!("Unclosed placeholder in deployment name template: {template:?}")
+            })?;
+            let placeholder = &after_start[..end];
+            parts.push(match placeholder {
+                "repository_id" => TemplatePart::RepositoryId,
+                "user" => TemplatePart::User,
+                "namespace" => TemplatePart::Namespace,
+                _ => eyre::bail!(
+                    "Unknown placeholder {{{placeholder}}} in deployment name template, expected one of: {}",
+                    DEPLOYMENT_NAME_PLACEHOLDERS.join(", ")
+                ),
+            });
+            remaining = &after_start[end + 1..];
+        }
+        if !remaining.is_empty() {
+            parts.push(TemplatePart::Text(remaining.to_string()));
+        }
+
+        Ok(Self {
+            template: template.to_string(),
+            parts,
+        })
+    }
+
+    pub fn render(&self, repository_key: &RepositoryKey) -> String {
+        self.parts
+            .iter()
+            .map(|part| match part {
+                TemplatePart::Text(text) => text.clone(),
+                TemplatePart::RepositoryId => repository_key.get_repository_id(),
+                TemplatePart::User => repository_key.user_id.clone(),
+                TemplatePart::Namespace => repository_key.get_profile_id(),
+            })
+            .collect()
+    }
+}
+
+impl Default for DeploymentNameTemplate {
+    fn default() -> Self {
+        Self::parse(DEFAULT_DEPLOYMENT_NAME_TEMPLATE).expect("The default template is valid")
+    }
+}
+
+impl TryFrom<String> for DeploymentNameTemplate {
+    type Error = eyre::Error;
+
+    fn try_from(template: String) -> Result<Self, Self::Error> {
+        Self::parse(&template)
+    }
+}
+
+impl From<DeploymentNameTemplate> for String {
+    fn from(template: DeploymentNameTemplate) -> Self {
+        template.template
+    }
+}
+
+/// How far a repository got on its way to Central
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PublishStatus {
+    /// The bundle was uploaded, and Central has taken over
+    Uploaded,
+    /// The bundle was built, but the upload was skipped
+    DryRun,
+    /// The bundle passed validation at Central, and the deployment was dropped
+    Validated,
+}
+
+/// Central found problems with a deployment
+#[derive(Debug)]
+pub struct ValidationFailed {
+    pub deployment_id: String,
+    pub errors: Vec<String>,
+}
+
+impl Display for ValidationFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Deployment {} failed validation: {}",
+            self.deployment_id,
+            self.errors.join("; ")
+        )
+    }
+}
+
+impl std::error::Error for ValidationFailed {}
+
+/// A repository is already being published, by an earlier request to close it
+#[derive(Debug)]
+pub struct PublishInProgress {
+    pub repository_id: String,
+}
+
+impl Display for PublishInProgress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Repository {} is already being published",
+            self.repository_id
+        )
+    }
+}
+
+impl std::error::Error for PublishInProgress {}
+
+/// The repositories being published, so that each is only sent to Central once at a time
+#[derive(Debug, Default)]
+pub struct PublishesInFlight {
+    repository_keys: Mutex<HashSet<RepositoryKey>>,
+}
+
+impl PublishesInFlight {
+    /// Mark the repository as being published until the returned guard is dropped
+    pub fn start(
+        self: &Arc<Self>,
+        repository_key: &RepositoryKey,
+    ) -> Result<PublishInFlight, PublishInProgress> {
+        let mut repository_keys = self
+            .repository_keys
+            .lock()
+            .expect("publishes in flight lock poisoned");
+        if !repository_keys.insert(repository_key.clone()) {
+            return Err(PublishInProgress {
+                repository_id: repository_key.get_repository_id(),
+            });
+        }
+
+        Ok(PublishInFlight {
+            publishes_in_flight: Arc::clone(self),
+            repository_key: repository_key.clone(),
+        })
+    }
+}
+
+/// A publish in progress, which is forgotten once it completes or fails
+#[derive(Debug)]
+pub struct PublishInFlight {
+    publishes_in_flight: Arc<PublishesInFlight>,
+    repository_key: RepositoryKey,
+}
+
+impl Drop for PublishInFlight {
+    fn drop(&mut self) {
+        self.publishes_in_flight
+            .repository_keys
+            .lock()
+            .expect("publishes in flight lock poisoned")
+            .remove(&self.repository_key);
+    }
+}
+
+/// Central did not accept the upload of a repository in time
+#[derive(Debug)]
+pub struct PublishTimedOut {
+    pub repository_id: String,
+    pub timeout: Duration,
+}
+
+impl Display for PublishTimedOut {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Upload of repository {} to Central did not complete within {} seconds, check Central for the deployment before dropping the repository",
+            self.repository_id,
+            self.timeout.as_secs_f64()
+        )
+    }
+}
+
+impl std::error::Error for PublishTimedOut {}
+
+/// The bundle built for a repository is larger than allowed to be sent to Central
+#[derive(Debug)]
+pub struct BundleTooLarge {
+    pub repository_id: String,
+    pub size: u64,
+    pub max_size: u64,
+}
+
+impl Display for BundleTooLarge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Bundle for repository {} is {} bytes, larger than the {} bytes allowed",
+            self.repository_id, self.size, self.max_size
+        )
+    }
+}
+
+impl std::error::Error for BundleTooLarge {}
+
+/// The result of publishing a repository
+#[derive(Debug, Clone, PartialEq)]
+pub struct PublishOutcome {
+    /// The deployment id assigned by Central, if the bundle was uploaded
+    pub deployment_id: Option<String>,
+    pub status: PublishStatus,
+    /// The lowercase hex SHA-256 of the bundle
+    pub bundle_sha256: String,
+    pub file_count: usize,
+    /// The files in the bundle, with their uncompressed sizes
+    pub bundle_entries: Vec<ZipEntry>,
+}
+
+#[instrument(
+    skip(portal_api_client, repository, credentials),
+    fields(repository_id = %repository_key.get_repository_id(), deployment_id)
+)]
+pub async fn publish(
     portal_api_client: &PortalApiClient,
-    repository: &R,
+    repository: &dyn Repository,
     credentials: &Credentials,
     repository_key: &RepositoryKey,
     publishing_type: PublishingType,
-) -> eyre::Result<()> {
-    let zip_data = repository.finish(&repository_key).await?;
-    let zip_data = zip_data.as_buffer()?;
+    options: &PublishOptions,
+) -> eyre::Result<PublishOutcome> {
+    // rejected before touching the repository, which the publish in progress is still using
+    let _publish_in_flight = options
+        .publishes_in_flight
+        .as_ref()
+        .map(|publishes_in_flight| publishes_in_flight.start(repository_key))
+        .transpose()?;
 
-    portal_api_client
-        .upload_from_memory(
-            &credentials,
-            &format!(
-                "{} (via OSSRH API Proxy)",
-                repository_key.get_repository_id()
-            ),
+    // a repository that could not be closed is left as it was, so there is nothing to roll back
+    let zip_file = match repository.finish(repository_key).await {
+        Ok(zip_file) => zip_file,
+        Err(e) => {
+            if e.downcast_ref::<RepositoryNotOpen>().is_none() {
+                record_publish_errors(repository, repository_key, &e).await;
+            }
+            return Err(e);
+        }
+    };
+
+    let publish_result: eyre::Result<PublishOutcome> = async {
+        let entries = zip_file.entries().to_vec();
+        let zip_data = zip_file.as_buffer()?;
+        let bundle_sha256 = ChecksumAlgorithm::Sha256.digest(&zip_data);
+
+        let bundle_size = zip_data.len() as u64;
+        tracing::info!(
+            "Built a {bundle_size} byte bundle with {} file(s)",
+            entries.len()
+        );
+        if let Some(max_size) = options.max_bundle_size {
+            if bundle_size > max_size {
+                return Err(BundleTooLarge {
+                    repository_id: repository_key.get_repository_id(),
+                    size: bundle_size,
+                    max_size,
+                }
+                .into());
+            }
+        }
+
+        if options.dry_run {
+            dry_run(repository_key, &entries, zip_data, options).await?;
+            return Ok(PublishOutcome {
+                deployment_id: None,
+                status: PublishStatus::DryRun,
+                bundle_sha256,
+                file_count: entries.len(),
+                bundle_entries: entries,
+            });
+        }
+
+        if let Some(upload_rate_limiter) = &options.upload_rate_limiter {
+            upload_rate_limiter
+                .acquire(&bundle_namespace(repository_key, &entries))
+                .await;
+        }
+
+        if options.validate_only {
+            let deployment_id = validate(
+                portal_api_client,
+                credentials,
+                repository_key,
+                zip_data,
+                options,
+            )
+            .await?;
+            Span::current().record("deployment_id", &deployment_id);
+            return Ok(PublishOutcome {
+                deployment_id: Some(deployment_id),
+                status: PublishStatus::Validated,
+                bundle_sha256,
+                file_count: entries.len(),
+                bundle_entries: entries,
+            });
+        }
+
+        let archive_data = options
+            .bundle_archive_dir
+            .as_ref()
+            .map(|_| zip_data.clone());
+        let deployment_name = options.deployment_name(repository_key);
+        if let Some(pending_uploads) = &options.pending_uploads {
+            if let Err(e) = pending_uploads
+                .record(repository_key, &deployment_name, publishing_type, &zip_data)
+                .await
+            {
+                tracing::warn!("Failed to record the pending upload: {e:#}");
+            }
+        }
+
+        let upload = portal_api_client.upload_from_memory(
+            credentials,
+            &deployment_name,
             publishing_type,
             zip_data,
+        );
+        let upload_result = tokio::time::timeout(options.publish_timeout, upload).await;
+
+        // a failed upload is rolled back below, so it is only left pending by a crash
+        if let Some(pending_uploads) = &options.pending_uploads {
+            if let Err(e) = pending_uploads.remove(repository_key).await {
+                tracing::warn!("Failed to remove the pending upload: {e:#}");
+            }
+        }
+
+        let deployment_id = upload_result.map_err(|_| PublishTimedOut {
+            repository_id: repository_key.get_repository_id(),
+            timeout: options.publish_timeout,
+        })??;
+        Span::current().record("deployment_id", &deployment_id);
+
+        // allows the status of the repository to follow the deployment at Central
+        if let Err(e) = repository
+            .record_deployment_id(repository_key, &deployment_id)
+            .await
+        {
+            tracing::error!("Failed to record the deployment id {deployment_id}: {e}");
+        }
+
+        if let (Some(archive_dir), Some(archive_data)) = (&options.bundle_archive_dir, archive_data)
+        {
+            archive_bundle(archive_dir, repository_key, &deployment_id, archive_data).await;
+        }
+
+        Ok(PublishOutcome {
+            deployment_id: Some(deployment_id),
+            status: PublishStatus::Uploaded,
+            bundle_sha256,
+            file_count: entries.len(),
+            bundle_entries: entries,
+        })
+    }
+    .await;
+
+    if let Err(e) = &publish_result {
+        record_publish_errors(repository, repository_key, e).await;
+
+        if e.downcast_ref::<PublishTimedOut>().is_some() {
+            // Central may have received the bundle anyway, and publishing it again could duplicate it
+            if let Err(mark_error) = repository.mark_unknown(repository_key).await {
+                tracing::error!("Failed to mark the repository as unknown: {mark_error}");
+            }
+        } else if let Err(reopen_error) = repository.reopen(repository_key).await {
+            // the files are still there, so let the client fix the repository and close it again
+            tracing::error!("Failed to reopen the repository: {reopen_error}");
+        }
+    } else if let Err(e) = repository.remove_files(repository_key).await {
+        tracing::error!("Failed to remove the files of the published repository: {e}");
+    }
+
+    publish_result
+}
+
+/// Keep the reason for a failed publish around, so that it can be surfaced when the client polls
+async fn record_publish_errors(
+    repository: &dyn Repository,
+    repository_key: &RepositoryKey,
+    error: &eyre::Error,
+) {
+    let errors = match error.downcast_ref::<ValidationFailed>() {
+        Some(validation_failed) => validation_failed.errors.clone(),
+        None => vec![format!("{error:#}")],
+    };
+    if let Err(record_error) = repository.record_errors(repository_key, errors).await {
+        tracing::error!("Failed to record the publish errors: {record_error}");
+    }
+}
+
+/// The namespace that the upload of a bundle is rate limited under
+///
+/// That is the groupId prefix shared by the POMs in the bundle, falling back to the repository's
+/// profile for a bundle without any.
+fn bundle_namespace(repository_key: &RepositoryKey, entries: &[ZipEntry]) -> String {
+    // a POM is under the directories of its groupId, then its artifactId and version
+    let mut group_ids = entries
+        .iter()
+        .filter(|entry| entry.path.ends_with(".pom"))
+        .map(|entry| entry.path.split('/').collect::<Vec<_>>())
+        .filter(|directories| directories.len() > 3)
+        .map(|mut directories| {
+            directories.truncate(directories.len() - 3);
+            directories
+        });
+
+    let Some(mut namespace) = group_ids.next() else {
+        return repository_key.get_profile_id();
+    };
+    for group_id in group_ids {
+        let shared = namespace
+            .iter()
+            .zip(&group_id)
+            .take_while(|(a, b)| a == b)
+            .count();
+        namespace.truncate(shared);
+    }
+
+    if namespace.is_empty() {
+        repository_key.get_profile_id()
+    } else {
+        namespace.join(".")
+    }
+}
+
+/// Upload the bundle of a repository whose publish was interrupted, returning the deployment id
+///
+/// The pending upload is forgotten once it completes, or if the repository has moved on since.
+#[instrument(
+    skip(
+        portal_api_client,
+        repository,
+        pending_uploads,
+        pending_upload,
+        credentials
+    ),
+    fields(repository_id = pending_upload.repository_id, deployment_id)
+)]
+pub async fn retry_pending_upload(
+    portal_api_client: &PortalApiClient,
+    repository: &dyn Repository,
+    pending_uploads: &PendingUploads,
+    pending_upload: &PendingUpload,
+    credentials: &Credentials,
+) -> eyre::Result<String> {
+    let repository_key = pending_upload.repository_key()?;
+
+    let repository_state = repository.get_state(&repository_key).await?;
+    if !matches!(repository_state, RepositoryState::Closed) {
+        pending_uploads.remove(&repository_key).await?;
+        eyre::bail!(
+            "Repository {} is {repository_state}, so its upload is no longer pending",
+            pending_upload.repository_id
+        );
+    }
+
+    let deployment_id = portal_api_client
+        .upload_from_file(
+            credentials,
+            &pending_upload.deployment_name,
+            pending_upload.publishing_type,
+            &pending_uploads.bundle_path(&repository_key),
         )
         .await?;
+    Span::current().record("deployment_id", &deployment_id);
+
+    if let Err(e) = repository
+        .record_deployment_id(&repository_key, &deployment_id)
+        .await
+    {
+        tracing::error!("Failed to record the deployment id {deployment_id}: {e}");
+    }
+    if let Err(e) = repository.remove_files(&repository_key).await {
+        tracing::error!("Failed to remove the files of the published repository: {e}");
+    }
+    pending_uploads.remove(&repository_key).await?;
+
+    Ok(deployment_id)
+}
+
+/// Check on the Central deployment of a published repository
+///
+/// Problems found by Central are recorded as the errors of the repository, so that they only need
+/// to be retrieved once. Returns `None` if the repository has not been uploaded, or its deployment
+/// has already settled, as a settled deployment is not checked again.
+#[instrument(
+    skip(portal_api_client, repository, credentials),
+    fields(repository_id = %repository_key.get_repository_id(), deployment_id)
+)]
+pub async fn refresh_deployment_state(
+    portal_api_client: &PortalApiClient,
+    repository: &dyn Repository,
+    credentials: &Credentials,
+    repository_key: &RepositoryKey,
+) -> eyre::Result<Option<DeploymentState>> {
+    let Some(deployment_id) = repository.get_deployment_id(repository_key).await? else {
+        return Ok(None);
+    };
+    Span::current().record("deployment_id", &deployment_id);
+    if repository.is_deployment_settled(repository_key).await? {
+        return Ok(None);
+    }
+
+    let deployment_status = portal_api_client
+        .deployment_status(credentials, &deployment_id)
+        .await?;
+
+    if deployment_status.deployment_state == DeploymentState::Failed {
+        let mut errors = deployment_status.error_messages();
+        if errors.is_empty() {
+            errors.push(format!("Deployment {deployment_id} failed at Central"));
+        }
+        repository.record_errors(repository_key, errors).await?;
+    }
+    if deployment_status.deployment_state.is_settled() {
+        repository.record_deployment_settled(repository_key).await?;
+    }
+
+    Ok(Some(deployment_status.deployment_state))
+}
+
+/// Upload the bundle for Central to validate, then drop the deployment so it is never published
+///
+/// Returns the id of the dropped deployment, or [ValidationFailed] with Central's report.
+async fn validate(
+    portal_api_client: &PortalApiClient,
+    credentials: &Credentials,
+    repository_key: &RepositoryKey,
+    zip_data: Vec<u8>,
+    options: &PublishOptions,
+) -> eyre::Result<String> {
+    let deployment_id = portal_api_client
+        .upload_from_memory(
+            credentials,
+            &options.deployment_name(repository_key),
+            PublishingType::UserManaged,
+            zip_data,
+        )
+        .await?;
+
+    let deployment_status = portal_api_client
+        .wait_for_validation(
+            credentials,
+            &deployment_id,
+            options.validation_poll_interval,
+            options.validation_timeout,
+        )
+        .await;
+
+    if let Err(e) = portal_api_client
+        .drop_deployment(credentials, &deployment_id)
+        .await
+    {
+        tracing::warn!("Failed to drop the validated deployment {deployment_id}: {e}");
+    }
+
+    let deployment_status = deployment_status?;
+    if deployment_status.deployment_state == DeploymentState::Failed {
+        return Err(ValidationFailed {
+            deployment_id,
+            errors: deployment_status.error_messages(),
+        }
+        .into());
+    }
+
+    tracing::info!("Deployment {deployment_id} passed validation");
+    Ok(deployment_id)
+}
+
+/// Keep a copy of an uploaded bundle, named after the repository and its deployment at Central
+///
+/// The bundle is already with Central, so failing to archive it only warrants a warning.
+async fn archive_bundle(
+    archive_dir: &Path,
+    repository_key: &RepositoryKey,
+    deployment_id: &str,
+    zip_data: Vec<u8>,
+) {
+    let bundle_path = archive_dir.join(format!(
+        "{}-{deployment_id}.zip",
+        repository_key.get_repository_id()
+    ));
+    match tokio::fs::write(&bundle_path, zip_data).await {
+        Ok(()) => tracing::info!("Archived the bundle: {bundle_path:?}"),
+        Err(e) => tracing::warn!("Failed to archive the bundle to {bundle_path:?}: {e}"),
+    }
+}
+
+/// Report on the bundle that would have been uploaded
+async fn dry_run(
+    repository_key: &RepositoryKey,
+    entries: &[ZipEntry],
+    zip_data: Vec<u8>,
+    options: &PublishOptions,
+) -> eyre::Result<()> {
+    tracing::info!(
+        "Dry run, skipping upload of {} byte bundle with {} file(s)",
+        zip_data.len(),
+        entries.len()
+    );
+    for entry in entries {
+        tracing::info!("Bundle entry: {} ({} bytes)", entry.path, entry.size);
+    }
+
+    if let Some(dry_run_dir) = &options.dry_run_dir {
+        let bundle_path = dry_run_dir.join(format!("{}.zip", repository_key.get_repository_id()));
+        tokio::fs::write(&bundle_path, zip_data)
+            .await
+            .wrap_err_with(|| format!("Failed to write the dry run bundle: {bundle_path:?}"))?;
+        tracing::info!("Wrote the dry run bundle: {bundle_path:?}");
+    }
+
     Ok(())
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::collections::HashMap;
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::sync::Mutex;
+
+    use bytes::Bytes;
+    use repository::local_repository::LocalRepository;
+    use repository::traits::RepositoryState;
+    use temp_dir::TempDir;
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::Subscriber;
+    use tracing_subscriber::layer::{Context, Layer};
+    use tracing_subscriber::prelude::*;
+    use tracing_subscriber::registry::LookupSpan;
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::*;
+
+    /// The only file in a repository from [stage_jar]
+    pub(crate) const STAGED_JAR_PATH: &str = "com/example/example/0.1.0/example-0.1.0.jar";
+
+    /// Start a repository for the test user and upload a JAR to it, as a client would
+    pub(crate) async fn stage_jar(repository: &dyn Repository) -> eyre::Result<RepositoryKey> {
+        let repository_key = repository
+            .start("test_user", &IpAddr::V4(Ipv4Addr::LOCALHOST), "comexample")
+            .await?;
+        repository
+            .add_file(
+                &repository_key,
+                Path::new(STAGED_JAR_PATH),
+                Box::pin(futures::stream::once(async {
+                    Ok(Bytes::from("test_file_content"))
+                })),
+            )
+            .await?;
+
+        Ok(repository_key)
+    }
+
+    /// A new local repository with a repository from [stage_jar] in it
+    pub(crate) async fn staged_repository() -> eyre::Result<(LocalRepository, RepositoryKey)> {
+        let local_repository = LocalRepository::new()?;
+        let repository_key = stage_jar(&local_repository).await?;
+
+        Ok((local_repository, repository_key))
+    }
+
+    #[test]
+    fn render_deployment_name() -> eyre::Result<()> {
+        let repository_key = RepositoryKey::new(
+            "test_user",
+            &IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+            Some("comexample".to_string()),
+            3,
+        );
+
+        assert_eq!(
+            DeploymentNameTemplate::default().render(&repository_key),
+            "comexample-3"
+        );
+        assert_eq!(
+            DeploymentNameTemplate::parse("{namespace} by {user} ({repository_id})")?
+                .render(&repository_key),
+            "comexample by test_user (comexample-3)"
+        );
+
+        // a value that looks like a placeholder is not substituted in turn
+        let repository_key = RepositoryKey::new(
+            "{namespace}",
+            &IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+            Some("comexample".to_string()),
+            3,
+        );
+        assert_eq!(
+            DeploymentNameTemplate::parse("{user}: {namespace}")?.render(&repository_key),
+            "{namespace}: comexample"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn truncate_long_deployment_names() -> eyre::Result<()> {
+        let repository_key = RepositoryKey::new(
+            "test_user",
+            &IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+            Some("comexample".to_string()),
+            3,
+        );
+        let options = PublishOptions {
+            deployment_name_template: DeploymentNameTemplate::parse(
+                "{namespace} published by {user}",
+            )?,
+            max_deployment_name_length: 32,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            options.deployment_name(&repository_key),
+            "comexample (via OSSRH API Proxy)"
+        );
+        assert_eq!(
+            PublishOptions {
+                append_proxy_suffix: false,
+                ..options
+            }
+            .deployment_name(&repository_key),
+            "comexample published by test_use"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn keep_valid_deployment_names() -> eyre::Result<()> {
+        let repository_key = RepositoryKey::new(
+            "test_user",
+            &IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+            Some("comexample".to_string()),
+            3,
+        );
+
+        assert_eq!(
+            PublishOptions::default().deployment_name(&repository_key),
+            "comexample-3 (via OSSRH API Proxy)"
+        );
+        assert_eq!(
+            PublishOptions {
+                deployment_name_template: DeploymentNameTemplate::parse(
+                    "{namespace}\n{repository_id}\t"
+                )?,
+                append_proxy_suffix: false,
+                ..Default::default()
+            }
+            .deployment_name(&repository_key),
+            "comexamplecomexample-3"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn toggle_proxy_suffix() -> eyre::Result<()> {
+        for (append_proxy_suffix, deployment_name) in [
+            (true, "comexample-0 (via OSSRH API Proxy)"),
+            (false, "comexample-0"),
+        ] {
+            let mock_server = MockServer::start().await;
+            Mock::given(method("POST"))
+                .and(path("/api/v1/publisher/upload"))
+                .and(query_param("name", deployment_name))
+                .respond_with(ResponseTemplate::new(201).set_body_string("test_deployment_id"))
+                .expect(1)
+                .mount(&mock_server)
+                .await;
+
+            let (local_repository, repository_key) = staged_repository().await?;
+
+            publish(
+                &PortalApiClient::client(&mock_server.uri())?,
+                &local_repository,
+                &Credentials::new("test_username".to_string(), "test_password".to_string()),
+                &repository_key,
+                PublishingType::Automatic,
+                &PublishOptions {
+                    append_proxy_suffix,
+                    ..Default::default()
+                },
+            )
+            .await?;
+
+            mock_server.verify().await;
+        }
+
+        Ok(())
+    }
+
+    /// Collects the fields recorded on the spans of one name
+    #[derive(Clone, Default)]
+    pub(crate) struct SpanFields {
+        pub(crate) span_name: &'static str,
+        pub(crate) fields: Arc<Mutex<HashMap<String, String>>>,
+    }
+
+    impl Visit for SpanFields {
+        fn record_str(&mut self, field: &Field, value: &str) {
+            let mut fields = self.fields.lock().unwrap();
+            fields.insert(field.name().to_string(), value.to_string());
+        }
+
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            let mut fields = self.fields.lock().unwrap();
+            fields.insert(field.name().to_string(), format!("{value:?}"));
+        }
+    }
+
+    impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for SpanFields {
+        fn on_new_span(&self, attributes: &Attributes<'_>, _id: &Id, _ctx: Context<'_, S>) {
+            if attributes.metadata().name() == self.span_name {
+                attributes.record(&mut self.clone());
+            }
+        }
+
+        fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+            if ctx
+                .metadata(id)
+                .is_some_and(|metadata| metadata.name() == self.span_name)
+            {
+                values.record(&mut self.clone());
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn trace_repository_and_deployment_ids() -> eyre::Result<()> {
+        let span_fields = SpanFields {
+            span_name: "publish",
+            ..Default::default()
+        };
+        let _guard = tracing_subscriber::registry()
+            .with(span_fields.clone())
+            .set_default();
+
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/api/v1/publisher/upload"))
+            .respond_with(ResponseTemplate::new(201).set_body_string("test_deployment_id"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let (local_repository, repository_key) = staged_repository().await?;
+
+        publish(
+            &PortalApiClient::client(&mock_server.uri())?,
+            &local_repository,
+            &Credentials::new("test_username".to_string(), "test_password".to_string()),
+            &repository_key,
+            PublishingType::Automatic,
+            &PublishOptions::default(),
+        )
+        .await?;
+
+        let fields = span_fields.fields.lock().unwrap();
+        assert_eq!(fields["repository_id"], "comexample-0");
+        assert_eq!(fields["deployment_id"], "test_deployment_id");
+
+        Ok(())
+    }
+
+    #[test]
+    fn reject_unknown_placeholders() {
+        for template in ["{repository}", "{repository_id", "{user} {version}"] {
+            assert!(
+                DeploymentNameTemplate::parse(template).is_err(),
+                "Accepted {template:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn rate_limit_under_bundle_group_prefix() {
+        let ip_addr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
+        let no_profile_key = RepositoryKey::new("test_user", &ip_addr, None, 0);
+        let entries = |paths: &[&str]| {
+            paths
+                .iter()
+                .map(|path| ZipEntry {
+                    path: path.to_string(),
+                    size: 0,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            bundle_namespace(
+                &no_profile_key,
+                &entries(&[
+                    "com/example/example/0.1.0/example-0.1.0.jar",
+                    "com/example/example/0.1.0/example-0.1.0.pom",
+                ])
+            ),
+            "com.example"
+        );
+        assert_eq!(
+            bundle_namespace(
+                &no_profile_key,
+                &entries(&[
+                    "com/example/core/core/0.1.0/core-0.1.0.pom",
+                    "com/example/cli/cli/0.1.0/cli-0.1.0.pom",
+                ])
+            ),
+            "com.example"
+        );
+
+        // without a POM, the profile is the closest thing to a namespace
+        let profile_key =
+            RepositoryKey::new("test_user", &ip_addr, Some("comexample".to_string()), 0);
+        assert_eq!(
+            bundle_namespace(
+                &profile_key,
+                &entries(&["com/example/example/0.1.0/example-0.1.0.jar"])
+            ),
+            "comexample"
+        );
+    }
+
+    #[tokio::test]
+    async fn failed_publish_records_errors() -> eyre::Result<()> {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/api/v1/publisher/upload"))
+            .respond_with(
+                ResponseTemplate::new(400).set_body_string(r#"{"error": "missing_signature"}"#),
+            )
+            .mount(&mock_server)
+            .await;
+
+        let portal_api_client = PortalApiClient::client(&mock_server.uri())?;
+        let (local_repository, repository_key) = staged_repository().await?;
+
+        let error = publish(
+            &portal_api_client,
+            &local_repository,
+            &Credentials::new("test_username".to_string(), "test_password".to_string()),
+            &repository_key,
+            PublishingType::Automatic,
+            &PublishOptions::default(),
+        )
+        .await
+        .expect_err("Succeeded, incorrectly");
+        assert!(error.to_string().contains("Upload request failed"));
+
+        let errors = local_repository.get_errors(&repository_key).await?;
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("missing_signature"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn failed_publish_can_be_finished_again() -> eyre::Result<()> {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/api/v1/publisher/upload"))
+            .respond_with(ResponseTemplate::new(500))
+            .mount(&mock_server)
+            .await;
+
+        let portal_api_client = PortalApiClient::client(&mock_server.uri())?;
+        let (local_repository, repository_key) = staged_repository().await?;
+
+        let error = publish(
+            &portal_api_client,
+            &local_repository,
+            &Credentials::new("test_username".to_string(), "test_password".to_string()),
+            &repository_key,
+            PublishingType::Automatic,
+            &PublishOptions::default(),
+        )
+        .await
+        .expect_err("Succeeded, incorrectly");
+        assert!(error.to_string().contains("Upload request failed"));
+
+        assert!(matches!(
+            local_repository.get_state(&repository_key).await?,
+            RepositoryState::Open
+        ));
+        let zip_file = local_repository.finish(&repository_key).await?;
+        let entry_paths = zip_file
+            .entries()
+            .iter()
+            .map(|entry| entry.path.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(entry_paths, vec![STAGED_JAR_PATH]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn oversized_bundle_rejected_before_upload() -> eyre::Result<()> {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/api/v1/publisher/upload"))
+            .respond_with(ResponseTemplate::new(201).set_body_string("deployment-1"))
+            .expect(0)
+            .mount(&mock_server)
+            .await;
+
+        let portal_api_client = PortalApiClient::client(&mock_server.uri())?;
+        let (local_repository, repository_key) = staged_repository().await?;
+
+        let error = publish(
+            &portal_api_client,
+            &local_repository,
+            &Credentials::new("test_username".to_string(), "test_password".to_string()),
+            &repository_key,
+            PublishingType::Automatic,
+            &PublishOptions {
+                max_bundle_size: Some(16),
+                ..Default::default()
+            },
+        )
+        .await
+        .expect_err("Succeeded, incorrectly");
+        let bundle_too_large = error
+            .downcast_ref::<BundleTooLarge>()
+            .expect("Failed for another reason");
+        assert_eq!(bundle_too_large.max_size, 16);
+        assert!(bundle_too_large.size > 16);
+
+        assert!(matches!(
+            local_repository.get_state(&repository_key).await?,
+            RepositoryState::Open
+        ));
+        assert!(mock_server
+            .received_requests()
+            .await
+            .unwrap_or_default()
+            .is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn concurrent_publishes_upload_once() -> eyre::Result<()> {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/api/v1/publisher/upload"))
+            .respond_with(
+                ResponseTemplate::new(201)
+                    .set_body_string("test_deployment_id")
+                    .set_delay(Duration::from_millis(100)),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let portal_api_client = PortalApiClient::client(&mock_server.uri())?;
+        let (local_repository, repository_key) = staged_repository().await?;
+
+        let credentials =
+            Credentials::new("test_username".to_string(), "test_password".to_string());
+        let publishes_in_flight = Arc::new(PublishesInFlight::default());
+        let options = PublishOptions {
+            publishes_in_flight: Some(publishes_in_flight.clone()),
+            ..Default::default()
+        };
+        let close = || {
+            publish(
+                &portal_api_client,
+                &local_repository,
+                &credentials,
+                &repository_key,
+                PublishingType::Automatic,
+                &options,
+            )
+        };
+
+        let (first, second) = tokio::join!(close(), close());
+
+        assert_eq!(first?.deployment_id.as_deref(), Some("test_deployment_id"));
+        let error = second.expect_err("Published twice, incorrectly");
+        assert!(error.downcast_ref::<PublishInProgress>().is_some());
+        assert!(publishes_in_flight
+            .repository_keys
+            .lock()
+            .unwrap()
+            .is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn republish_leaves_published_repository() -> eyre::Result<()> {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/api/v1/publisher/upload"))
+            .respond_with(ResponseTemplate::new(201).set_body_string("test_deployment_id"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let portal_api_client = PortalApiClient::client(&mock_server.uri())?;
+        let (local_repository, repository_key) = staged_repository().await?;
+
+        let credentials =
+            Credentials::new("test_username".to_string(), "test_password".to_string());
+        let options = PublishOptions::default();
+        let close = || {
+            publish(
+                &portal_api_client,
+                &local_repository,
+                &credentials,
+                &repository_key,
+                PublishingType::Automatic,
+                &options,
+            )
+        };
+        close().await?;
+
+        let error = close().await.expect_err("Published twice, incorrectly");
+        assert!(error.downcast_ref::<RepositoryNotOpen>().is_some());
+        assert!(matches!(
+            local_repository.get_state(&repository_key).await?,
+            RepositoryState::Closed
+        ));
+        assert!(local_repository
+            .get_errors(&repository_key)
+            .await?
+            .is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn timed_out_publish_leaves_repository_unknown() -> eyre::Result<()> {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/api/v1/publisher/upload"))
+            .respond_with(
+                ResponseTemplate::new(201)
+                    .set_body_string("test_deployment_id")
+                    .set_delay(Duration::from_secs(5)),
+            )
+            .mount(&mock_server)
+            .await;
+
+        let portal_api_client = PortalApiClient::client(&mock_server.uri())?;
+        let (local_repository, repository_key) = staged_repository().await?;
+
+        let error = publish(
+            &portal_api_client,
+            &local_repository,
+            &Credentials::new("test_username".to_string(), "test_password".to_string()),
+            &repository_key,
+            PublishingType::Automatic,
+            &PublishOptions {
+                publish_timeout: Duration::from_millis(100),
+                ..Default::default()
+            },
+        )
+        .await
+        .expect_err("Succeeded, incorrectly");
+        assert!(error.downcast_ref::<PublishTimedOut>().is_some());
+
+        assert!(matches!(
+            local_repository.get_state(&repository_key).await?,
+            RepositoryState::Unknown
+        ));
+        let errors = local_repository.get_errors(&repository_key).await?;
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("did not complete within 0.1 seconds"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn successful_publish_outcome() -> eyre::Result<()> {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/api/v1/publisher/upload"))
+            .respond_with(ResponseTemplate::new(201).set_body_string("test_deployment_id"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let portal_api_client = PortalApiClient::client(&mock_server.uri())?;
+        let (local_repository, repository_key) = staged_repository().await?;
+        local_repository
+            .add_file(
+                &repository_key,
+                Path::new("com/example/example/0.1.0/example-0.1.0.pom"),
+                Box::pin(futures::stream::once(async {
+                    Ok(Bytes::from("test_file_content"))
+                })),
+            )
+            .await?;
+
+        let outcome = publish(
+            &portal_api_client,
+            &local_repository,
+            &Credentials::new("test_username".to_string(), "test_password".to_string()),
+            &repository_key,
+            PublishingType::Automatic,
+            &PublishOptions::default(),
+        )
+        .await?;
+
+        assert_eq!(outcome.deployment_id.as_deref(), Some("test_deployment_id"));
+        assert_eq!(outcome.status, PublishStatus::Uploaded);
+        assert_eq!(outcome.bundle_sha256.len(), 64);
+        assert_eq!(outcome.file_count, 2);
+        assert_eq!(
+            local_repository.get_deployment_id(&repository_key).await?,
+            Some("test_deployment_id".to_string())
+        );
+        // the files are no longer needed once Central has the bundle
+        assert_eq!(
+            local_repository
+                .get_file(&repository_key, Path::new(STAGED_JAR_PATH))
+                .await?,
+            None
+        );
+
+        mock_server.verify().await;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn archive_uploaded_bundle() -> eyre::Result<()> {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/api/v1/publisher/upload"))
+            .respond_with(ResponseTemplate::new(201).set_body_string("test_deployment_id"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let portal_api_client = PortalApiClient::client(&mock_server.uri())?;
+        let archive_dir = TempDir::new()?;
+        let (local_repository, repository_key) = staged_repository().await?;
+
+        let outcome = publish(
+            &portal_api_client,
+            &local_repository,
+            &Credentials::new("test_username".to_string(), "test_password".to_string()),
+            &repository_key,
+            PublishingType::Automatic,
+            &PublishOptions {
+                bundle_archive_dir: Some(archive_dir.path().to_path_buf()),
+                ..Default::default()
+            },
+        )
+        .await?;
+
+        let bundle = tokio::fs::read(
+            archive_dir
+                .path()
+                .join("comexample-0-test_deployment_id.zip"),
+        )
+        .await?;
+        assert_eq!(
+            outcome.bundle_sha256,
+            ChecksumAlgorithm::Sha256.digest(&bundle)
+        );
+
+        let requests = mock_server.received_requests().await.unwrap_or_default();
+        assert_eq!(requests.len(), 1);
+        assert!(requests[0]
+            .body
+            .windows(bundle.len())
+            .any(|window| window == bundle.as_slice()));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn archive_failure_does_not_fail_publish() -> eyre::Result<()> {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/api/v1/publisher/upload"))
+            .respond_with(ResponseTemplate::new(201).set_body_string("test_deployment_id"))
+            .mount(&mock_server)
+            .await;
+
+        let portal_api_client = PortalApiClient::client(&mock_server.uri())?;
+        let archive_dir = TempDir::new()?;
+        let (local_repository, repository_key) = staged_repository().await?;
+
+        let outcome = publish(
+            &portal_api_client,
+            &local_repository,
+            &Credentials::new("test_username".to_string(), "test_password".to_string()),
+            &repository_key,
+            PublishingType::Automatic,
+            &PublishOptions {
+                bundle_archive_dir: Some(archive_dir.path().join("missing")),
+                ..Default::default()
+            },
+        )
+        .await?;
+
+        assert_eq!(outcome.status, PublishStatus::Uploaded);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn validate_only_records_report() -> eyre::Result<()> {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/api/v1/publisher/upload"))
+            .and(query_param("publishingType", "USER_MANAGED"))
+            .respond_with(ResponseTemplate::new(201).set_body_string("test_deployment_id"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/api/v1/publisher/status"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                r#"{
+                    "deploymentId": "test_deployment_id",
+                    "deploymentName": "test_deployment",
+                    "deploymentState": "FAILED",
+                    "errors": {
+                        "pkg:maven/com.example/example@0.1.0?type=jar": [
+                            "Missing signature for file: example-0.1.0.jar",
+                            "Missing sha1 checksum for file: example-0.1.0.jar"
+                        ]
+                    }
+                }"#,
+            ))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("DELETE"))
+            .and(path("/api/v1/publisher/deployment/test_deployment_id"))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let portal_api_client = PortalApiClient::client(&mock_server.uri())?;
+        let (local_repository, repository_key) = staged_repository().await?;
+
+        let error = publish(
+            &portal_api_client,
+            &local_repository,
+            &Credentials::new("test_username".to_string(), "test_password".to_string()),
+            &repository_key,
+            PublishingType::Automatic,
+            &PublishOptions {
+                validate_only: true,
+                ..Default::default()
+            },
+        )
+        .await
+        .expect_err("Passed validation, incorrectly");
+        assert!(error.downcast_ref::<ValidationFailed>().is_some());
+
+        let errors = local_repository.get_errors(&repository_key).await?;
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].ends_with("Missing signature for file: example-0.1.0.jar"));
+
+        mock_server.verify().await;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn refresh_failed_deployment() -> eyre::Result<()> {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/api/v1/publisher/status"))
+            .and(query_param("id", "test_deployment_id"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                r#"{
+                    "deploymentId": "test_deployment_id",
+                    "deploymentName": "test_deployment",
+                    "deploymentState": "FAILED",
+                    "errors": {
+                        "pkg:maven/com.example/example@0.1.0?type=pom": [
+                            "Project name is missing"
+                        ]
+                    }
+                }"#,
+            ))
+            .mount(&mock_server)
+            .await;
+
+        let portal_api_client = PortalApiClient::client(&mock_server.uri())?;
+        let local_repository = LocalRepository::new()?;
+        let credentials =
+            Credentials::new("test_username".to_string(), "test_password".to_string());
+
+        let repository_key = local_repository
+            .start(
+                "test_user",
+                &IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+                "test_profile",
+            )
+            .await?;
+
+        // nothing to check before the repository is uploaded
+        let deployment_state = refresh_deployment_state(
+            &portal_api_client,
+            &local_repository,
+            &credentials,
+            &repository_key,
+        )
+        .await?;
+        assert_eq!(deployment_state, None);
+
+        local_repository
+            .record_deployment_id(&repository_key, "test_deployment_id")
+            .await?;
+
+        let deployment_state = refresh_deployment_state(
+            &portal_api_client,
+            &local_repository,
+            &credentials,
+            &repository_key,
+        )
+        .await?;
+        assert_eq!(deployment_state, Some(DeploymentState::Failed));
+        assert_eq!(
+            local_repository.get_errors(&repository_key).await?,
+            vec!["pkg:maven/com.example/example@0.1.0?type=pom: Project name is missing"]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn settled_deployments_are_checked_once() -> eyre::Result<()> {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/api/v1/publisher/status"))
+            .and(query_param("id", "test_deployment_id"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                r#"{
+                    "deploymentId": "test_deployment_id",
+                    "deploymentName": "test_deployment",
+                    "deploymentState": "PUBLISHED"
+                }"#,
+            ))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let portal_api_client = PortalApiClient::client(&mock_server.uri())?;
+        let local_repository = LocalRepository::new()?;
+        let credentials =
+            Credentials::new("test_username".to_string(), "test_password".to_string());
+
+        let repository_key = local_repository
+            .start(
+                "test_user",
+                &IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+                "test_profile",
+            )
+            .await?;
+        local_repository
+            .record_deployment_id(&repository_key, "test_deployment_id")
+            .await?;
+
+        for expected_state in [Some(DeploymentState::Published), None] {
+            let deployment_state = refresh_deployment_state(
+                &portal_api_client,
+                &local_repository,
+                &credentials,
+                &repository_key,
+            )
+            .await?;
+            assert_eq!(deployment_state, expected_state);
+        }
+
+        mock_server.verify().await;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn dry_run_skips_upload() -> eyre::Result<()> {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/api/v1/publisher/upload"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("test_deployment_id"))
+            .expect(0)
+            .mount(&mock_server)
+            .await;
+
+        let portal_api_client = PortalApiClient::client(&mock_server.uri())?;
+        let dry_run_dir = TempDir::new()?;
+        let (local_repository, repository_key) = staged_repository().await?;
+
+        let outcome = publish(
+            &portal_api_client,
+            &local_repository,
+            &Credentials::new("test_username".to_string(), "test_password".to_string()),
+            &repository_key,
+            PublishingType::Automatic,
+            &PublishOptions {
+                dry_run: true,
+                dry_run_dir: Some(dry_run_dir.path().to_path_buf()),
+                ..Default::default()
+            },
+        )
+        .await?;
+
+        let bundle = tokio::fs::read(dry_run_dir.path().join("comexample-0.zip")).await?;
+        assert!(!bundle.is_empty());
+
+        assert_eq!(outcome.status, PublishStatus::DryRun);
+        assert_eq!(outcome.deployment_id, None);
+        assert_eq!(
+            outcome.bundle_sha256,
+            ChecksumAlgorithm::Sha256.digest(&bundle)
+        );
+        assert_eq!(outcome.file_count, 1);
+
+        mock_server.verify().await;
+
+        Ok(())
+    }
+}
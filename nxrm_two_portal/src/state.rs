@@ -1,27 +1,75 @@
 use std::sync::Arc;
 
+use axum::extract::FromRef;
 use portal_api::PortalApiClient;
 use repository::traits::Repository;
+use tokio::sync::Semaphore;
 
-pub struct AppState<R: Repository> {
-    pub repository: Arc<R>,
+use crate::config::AppConfig;
+use crate::extract::{BodyLogLimit, TrustForwardedHeaders};
+use crate::limit::UploadRateLimiter;
+use crate::pending::PendingUploads;
+use crate::publish::{PublishOptions, PublishesInFlight};
+
+#[derive(Clone)]
+pub struct AppState {
+    pub repository: Arc<dyn Repository>,
     pub portal_api_client: Arc<PortalApiClient>,
+    pub app_config: Arc<AppConfig>,
+    /// Shared by the uploads in progress, to bound how many run at once
+    pub upload_permits: Arc<Semaphore>,
+    /// Shared by the publishes to Central, to keep them under the configured rate
+    pub upload_rate_limiter: Option<Arc<UploadRateLimiter>>,
+    /// Where uploads to Central are kept until they complete, if anywhere
+    pub pending_uploads: Option<Arc<PendingUploads>>,
+    /// Shared by the publishes to Central, to publish each repository once at a time
+    pub publishes_in_flight: Arc<PublishesInFlight>,
 }
 
-impl<R: Repository> AppState<R> {
-    pub fn new(repository: R, portal_api_client: PortalApiClient) -> Self {
+impl AppState {
+    pub fn new(
+        repository: impl Repository + 'static,
+        portal_api_client: PortalApiClient,
+        app_config: AppConfig,
+    ) -> Self {
+        let upload_permits = Arc::new(Semaphore::new(app_config.max_concurrent_uploads));
+        let upload_rate_limiter = app_config
+            .uploads_per_minute
+            .map(|uploads_per_minute| Arc::new(UploadRateLimiter::new(uploads_per_minute)));
+        let pending_uploads = app_config
+            .pending_upload_dir
+            .as_ref()
+            .map(|pending_upload_dir| Arc::new(PendingUploads::new(pending_upload_dir)));
         Self {
             repository: Arc::new(repository),
             portal_api_client: Arc::new(portal_api_client),
+            app_config: Arc::new(app_config),
+            upload_permits,
+            upload_rate_limiter,
+            pending_uploads,
+            publishes_in_flight: Arc::default(),
         }
     }
-}
 
-impl<R: Repository> Clone for AppState<R> {
-    fn clone(&self) -> Self {
-        Self {
-            repository: self.repository.clone(),
-            portal_api_client: self.portal_api_client.clone(),
+    /// The configured publish options, sharing the rate limit and pending uploads with other publishes
+    pub fn publish_options(&self) -> PublishOptions {
+        PublishOptions {
+            upload_rate_limiter: self.upload_rate_limiter.clone(),
+            pending_uploads: self.pending_uploads.clone(),
+            publishes_in_flight: Some(self.publishes_in_flight.clone()),
+            ..self.app_config.publish_options()
         }
     }
 }
+
+impl FromRef<AppState> for BodyLogLimit {
+    fn from_ref(app_state: &AppState) -> Self {
+        BodyLogLimit(app_state.app_config.debug_body_log_limit_bytes)
+    }
+}
+
+impl FromRef<AppState> for TrustForwardedHeaders {
+    fn from_ref(app_state: &AppState) -> Self {
+        TrustForwardedHeaders(app_state.app_config.trust_forwarded_headers)
+    }
+}
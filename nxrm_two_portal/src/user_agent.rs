@@ -0,0 +1,129 @@
+use axum::{
+    extract::{Request, State},
+    http::{header::USER_AGENT, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use tracing::{instrument, Span};
+
+use crate::state::AppState;
+
+/// Record the user agent of each request, rejecting those that are not allowed with `403 Forbidden`
+///
+/// Traced to discover which Maven and Gradle plugins, and which of their versions, use the proxy.
+#[instrument(skip(app_state, req, next), fields(user_agent))]
+pub async fn check_user_agent(
+    State(app_state): State<AppState>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let user_agent = req
+        .headers()
+        .get(USER_AGENT)
+        .and_then(|user_agent| user_agent.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+    Span::current().record("user_agent", &user_agent);
+
+    if !app_state.app_config.user_agent_allowed(&user_agent) {
+        tracing::warn!("Rejecting the user agent {user_agent:?}");
+        return (StatusCode::FORBIDDEN, "User agent is not allowed").into_response();
+    }
+
+    next.run(req).await
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, middleware, routing::get, Router};
+    use portal_api::PortalApiClient;
+    use repository::local_repository::LocalRepository;
+    use tower::ServiceExt;
+    use tracing_subscriber::prelude::*;
+
+    use super::*;
+    use crate::config::AppConfig;
+    use crate::publish::tests::SpanFields;
+
+    fn app(overrides: &[(&str, &str)]) -> eyre::Result<Router> {
+        let app_state = AppState::new(
+            LocalRepository::new()?,
+            PortalApiClient::client("http://localhost")?,
+            AppConfig::with_overrides(overrides)?,
+        );
+
+        Ok(Router::new()
+            .route("/", get(|| async { "example" }))
+            .layer(middleware::from_fn_with_state(
+                app_state.clone(),
+                check_user_agent,
+            ))
+            .with_state(app_state))
+    }
+
+    async fn request(app: Router, user_agent: &str) -> eyre::Result<StatusCode> {
+        let response = app
+            .oneshot(
+                Request::get("/")
+                    .header(USER_AGENT, user_agent)
+                    .body(Body::empty())?,
+            )
+            .await?;
+
+        Ok(response.status())
+    }
+
+    #[tokio::test]
+    async fn trace_user_agent() -> eyre::Result<()> {
+        let span_fields = SpanFields {
+            span_name: "check_user_agent",
+            ..Default::default()
+        };
+        let _guard = tracing_subscriber::registry()
+            .with(span_fields.clone())
+            .set_default();
+
+        let status = request(app(&[])?, "Apache-Maven/3.9.6 (Java 17; Linux)").await?;
+
+        assert_eq!(status, StatusCode::OK);
+        let fields = span_fields.fields.lock().unwrap();
+        assert_eq!(
+            fields.get("user_agent").map(String::as_str),
+            Some("Apache-Maven/3.9.6 (Java 17; Linux)")
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reject_blocked_user_agent() -> eyre::Result<()> {
+        let app = app(&[("blocked_user_agents", "curl")])?;
+
+        assert_eq!(
+            request(app.clone(), "curl/8.5.0").await?,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            request(app, "Gradle/8.5 (Linux;6.1;amd64)").await?,
+            StatusCode::OK
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reject_user_agent_outside_allowlist() -> eyre::Result<()> {
+        let app = app(&[("allowed_user_agents", "Apache-Maven")])?;
+
+        assert_eq!(
+            request(app.clone(), "Gradle/8.5 (Linux;6.1;amd64)").await?,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            request(app, "Apache-Maven/3.9.6 (Java 17; Linux)").await?,
+            StatusCode::OK
+        );
+
+        Ok(())
+    }
+}
@@ -1,5 +1,6 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use std::path::PathBuf;
+use std::time::Duration;
 
 use portal_api::{
     api_types::PublishingType::Automatic, Credentials, PortalApiClient, CENTRAL_HOST,
@@ -8,22 +9,56 @@ use tracing_subscriber::{fmt, prelude::*, EnvFilter};
 
 /// An example CLI to upload bundles to Central
 #[derive(Parser, Debug)]
-#[command(version, about)]
+#[command(
+    version,
+    about,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
 struct Cli {
     /// Central URL
-    #[arg(short, long)]
+    #[arg(short, long, global = true)]
     central_host: Option<String>,
 
     /// The username of the credentials (if not provided, it will be prompted)
-    #[arg(short, long)]
+    #[arg(short, long, global = true)]
     username: Option<String>,
 
+    /// The credentials as `username:password`, instead of prompting for them
+    #[arg(long, global = true, conflicts_with = "username")]
+    credentials: Option<String>,
+
     /// The deployment name (if not provided, defaults to "Upload")
     #[arg(short, long)]
     deployment_name: Option<String>,
 
     /// The path to a .zip/.tgz/etc. to upload
-    upload_bundle: PathBuf,
+    #[arg(required = true)]
+    upload_bundle: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Check the status of a deployment
+    Status {
+        /// The deployment ID printed by an upload
+        deployment_id: String,
+
+        /// Wait for Central to finish validating the deployment
+        #[arg(short, long)]
+        wait: bool,
+
+        /// Seconds between checks while waiting
+        #[arg(long, default_value_t = 5)]
+        poll_interval: u64,
+
+        /// Seconds to wait before giving up
+        #[arg(long, default_value_t = 600)]
+        timeout: u64,
+    },
 }
 
 #[tokio::main]
@@ -37,29 +72,64 @@ pub async fn main() -> eyre::Result<()> {
 
     let host = cli.central_host.unwrap_or(CENTRAL_HOST.to_string());
 
-    let username = match cli.username {
-        Some(username) => username.to_string(),
-        None => promptly::prompt("Publisher token username")?,
-    };
+    let credentials = match cli.credentials {
+        Some(credentials) => Credentials::from_colon_string(&credentials)?,
+        None => {
+            let username = match cli.username {
+                Some(username) => username.to_string(),
+                None => promptly::prompt("Publisher token username")?,
+            };
 
-    let password = rpassword::prompt_password("Publisher token password: ")?;
+            let password = rpassword::prompt_password("Publisher token password: ")?;
 
-    let credentials = Credentials::new(username, password);
+            Credentials::new(username, password)
+        }
+    };
 
     let api_client = PortalApiClient::client(&host)?;
 
-    let deployment_name = cli.deployment_name.unwrap_or("Upload".to_string());
-
-    let deployment_id = api_client
-        .upload_from_file(
-            &credentials,
-            &deployment_name,
-            Automatic,
-            &cli.upload_bundle,
-        )
-        .await?;
-
-    println!("Deployment ID: {deployment_id}");
+    match cli.command {
+        Some(Command::Status {
+            deployment_id,
+            wait,
+            poll_interval,
+            timeout,
+        }) => {
+            let mut deployment_status = api_client
+                .deployment_status(&credentials, &deployment_id)
+                .await?;
+            println!("Deployment state: {:?}", deployment_status.deployment_state);
+
+            if wait && !deployment_status.deployment_state.is_validation_complete() {
+                println!("Waiting for Central to validate the deployment...");
+                deployment_status = api_client
+                    .wait_for_validation(
+                        &credentials,
+                        &deployment_id,
+                        Duration::from_secs(poll_interval),
+                        Duration::from_secs(timeout),
+                    )
+                    .await?;
+                println!("Deployment state: {:?}", deployment_status.deployment_state);
+            }
+
+            for error_message in deployment_status.error_messages() {
+                println!("Error: {error_message}");
+            }
+        }
+        None => {
+            let upload_bundle = cli
+                .upload_bundle
+                .ok_or_else(|| eyre::eyre!("A bundle to upload is required"))?;
+            let deployment_name = cli.deployment_name.unwrap_or("Upload".to_string());
+
+            let deployment_id = api_client
+                .upload_from_file(&credentials, &deployment_name, Automatic, &upload_bundle)
+                .await?;
+
+            println!("Deployment ID: {deployment_id}");
+        }
+    }
 
     Ok(())
 }
@@ -1,6 +1,8 @@
-use serde::Serialize;
+use std::collections::BTreeMap;
 
-#[derive(Debug, Serialize)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum PublishingType {
     /// A successful upload results in a validated bundle, which must be manually published
@@ -10,6 +12,60 @@ pub enum PublishingType {
     Automatic,
 }
 
+/// The progress of a deployment through Central
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum DeploymentState {
+    /// Uploaded and waiting to be processed
+    Pending,
+    Validating,
+    /// Passed validation, and waiting to be published by the user
+    Validated,
+    Publishing,
+    Published,
+    /// Failed validation or publishing, see [DeploymentStatus::errors]
+    Failed,
+}
+
+impl DeploymentState {
+    /// Whether Central has finished validating the deployment
+    pub fn is_validation_complete(&self) -> bool {
+        !matches!(self, DeploymentState::Pending | DeploymentState::Validating)
+    }
+
+    /// Whether Central is done with the deployment, so that its state no longer changes
+    pub fn is_settled(&self) -> bool {
+        matches!(self, DeploymentState::Published | DeploymentState::Failed)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeploymentStatus {
+    pub deployment_id: String,
+    pub deployment_name: String,
+    pub deployment_state: DeploymentState,
+    #[serde(default)]
+    pub purls: Vec<String>,
+    /// The problems found with the deployment, keyed by the component they were found in
+    #[serde(default)]
+    pub errors: BTreeMap<String, Vec<String>>,
+}
+
+impl DeploymentStatus {
+    /// Every problem found with the deployment, prefixed by its component
+    pub fn error_messages(&self) -> Vec<String> {
+        self.errors
+            .iter()
+            .flat_map(|(component, messages)| {
+                messages
+                    .iter()
+                    .map(move |message| format!("{component}: {message}"))
+            })
+            .collect()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -27,4 +83,30 @@ mod tests {
                 .expect("Failed to convert to a string")
         );
     }
+
+    #[test]
+    fn test_deployment_status() -> Result<(), serde_json::Error> {
+        let deployment_status: DeploymentStatus = serde_json::from_str(
+            r#"{
+                "deploymentId": "28570f16-da32-4c14-bd2e-c1acc0782365",
+                "deploymentName": "central-bundle.zip",
+                "deploymentState": "FAILED",
+                "purls": [],
+                "errors": {
+                    "pkg:maven/com.example/example@0.1.0?type=jar": [
+                        "Missing signature for file: example-0.1.0.jar"
+                    ]
+                }
+            }"#,
+        )?;
+
+        assert_eq!(deployment_status.deployment_state, DeploymentState::Failed);
+        assert!(deployment_status.deployment_state.is_validation_complete());
+        assert_eq!(
+            deployment_status.error_messages(),
+            vec!["pkg:maven/com.example/example@0.1.0?type=jar: Missing signature for file: example-0.1.0.jar"]
+        );
+
+        Ok(())
+    }
 }
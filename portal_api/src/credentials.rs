@@ -1,10 +1,17 @@
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+
 use base64::engine::general_purpose::STANDARD;
 use base64::engine::Engine;
 use reqwest::{
     header::{HeaderValue, AUTHORIZATION},
     RequestBuilder,
 };
+use serde::{Deserialize, Serialize};
+use xml::reader::{EventReader, XmlEvent};
 
+/// A Central user token
+#[derive(Clone)]
 pub struct Credentials {
     username: String,
     password: String,
@@ -15,18 +22,338 @@ impl Credentials {
         Self { username, password }
     }
 
+    /// Split a `username:password` string, as CLIs and CI systems often pass credentials
+    ///
+    /// Only the first `:` separates them, so the password may contain more.
+    pub fn from_colon_string(credentials: &str) -> eyre::Result<Self> {
+        let (username, password) = credentials
+            .split_once(':')
+            .ok_or_else(|| eyre::eyre!("Credentials must be given as username:password"))?;
+        Ok(Self::new(username.to_string(), password.to_string()))
+    }
+
     pub fn add_credentials_to_request(
         &self,
         request: RequestBuilder,
+        auth_scheme: AuthScheme,
     ) -> eyre::Result<RequestBuilder> {
-        let token_header = HeaderValue::from_str(&self.as_bearer_token())?;
+        let token_header = HeaderValue::from_str(&self.authorization(auth_scheme))?;
         let request = request.header(AUTHORIZATION, token_header);
         tracing::trace!("Added {AUTHORIZATION} header");
         Ok(request)
     }
 
-    fn as_bearer_token(&self) -> String {
+    /// The value of the `Authorization` header that presents these credentials
+    fn authorization(&self, auth_scheme: AuthScheme) -> String {
         let token = STANDARD.encode(format!("{}:{}", self.username, self.password));
-        format!("UserToken {token}")
+        match auth_scheme {
+            AuthScheme::UserToken => format!("UserToken {token}"),
+            AuthScheme::Basic => format!("Basic {token}"),
+        }
+    }
+}
+
+/// How [Credentials] are presented to the server
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthScheme {
+    /// `Authorization: UserToken <base64(username:password)>`, as Central expects
+    #[default]
+    UserToken,
+    /// Standard HTTP Basic authentication, for compatible servers that expect it
+    Basic,
+}
+
+/// The problems that can prevent credentials being read from a Maven `settings.xml`
+#[derive(Debug)]
+pub enum SettingsError {
+    /// The settings file could not be read
+    Read(PathBuf, std::io::Error),
+    /// The settings file is not well-formed XML
+    Malformed(PathBuf, String),
+    /// No `<server>` has the requested `<id>`
+    ServerNotFound(String),
+    /// The server is missing its `<username>` or `<password>`
+    MissingField(String, &'static str),
+    /// A `${...}` placeholder that is not an `env.` property, or refers to an unset variable
+    UnresolvedPlaceholder(String, String),
+}
+
+impl Display for SettingsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SettingsError::Read(path, e) => write!(f, "Failed to read {path:?}: {e}"),
+            SettingsError::Malformed(path, e) => write!(f, "Malformed settings in {path:?}: {e}"),
+            SettingsError::ServerNotFound(server_id) => {
+                write!(f, "No server with the id {server_id:?} in the settings")
+            }
+            SettingsError::MissingField(server_id, field) => {
+                write!(f, "Server {server_id:?} has no <{field}>")
+            }
+            SettingsError::UnresolvedPlaceholder(server_id, placeholder) => write!(
+                f,
+                "Server {server_id:?} uses the placeholder {placeholder:?}, which could not be resolved"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SettingsError {}
+
+impl Credentials {
+    /// Read the `<username>` and `<password>` of a `<server>` in a Maven `settings.xml`
+    ///
+    /// `${env.NAME}` placeholders are replaced with the value of the environment variable.
+    pub fn from_settings_xml(path: &Path, server_id: &str) -> Result<Self, SettingsError> {
+        let contents =
+            std::fs::read(path).map_err(|e| SettingsError::Read(path.to_path_buf(), e))?;
+        let server = find_server(&contents, server_id)
+            .map_err(|e| SettingsError::Malformed(path.to_path_buf(), e.to_string()))?
+            .ok_or_else(|| SettingsError::ServerNotFound(server_id.to_string()))?;
+
+        let username = server
+            .username
+            .ok_or_else(|| SettingsError::MissingField(server_id.to_string(), "username"))?;
+        let password = server
+            .password
+            .ok_or_else(|| SettingsError::MissingField(server_id.to_string(), "password"))?;
+
+        let env_var = |variable: &str| std::env::var(variable).ok();
+        Ok(Self::new(
+            resolve_placeholders(server_id, &username, env_var)?,
+            resolve_placeholders(server_id, &password, env_var)?,
+        ))
+    }
+}
+
+/// The credentials of a `<server>`, as written in the settings
+#[derive(Default)]
+struct ServerEntry {
+    username: Option<String>,
+    password: Option<String>,
+}
+
+/// The entry of the server with the id, if there is one
+fn find_server(
+    contents: &[u8],
+    server_id: &str,
+) -> Result<Option<ServerEntry>, xml::reader::Error> {
+    let mut element_path: Vec<String> = Vec::new();
+    let mut text = String::new();
+    let mut id = None;
+    let mut server = ServerEntry::default();
+
+    for event in EventReader::new(contents) {
+        match event? {
+            XmlEvent::StartElement { name, .. } => {
+                element_path.push(name.local_name);
+                text.clear();
+            }
+            XmlEvent::Characters(characters) | XmlEvent::CData(characters) => {
+                text.push_str(&characters);
+            }
+            XmlEvent::EndElement { .. } => {
+                let path = element_path.iter().map(String::as_str).collect::<Vec<_>>();
+                let value = Some(text.trim().to_string());
+                match path.as_slice() {
+                    ["settings", "servers", "server", "id"] => id = value,
+                    ["settings", "servers", "server", "username"] => server.username = value,
+                    ["settings", "servers", "server", "password"] => server.password = value,
+                    ["settings", "servers", "server"] => {
+                        let server = std::mem::take(&mut server);
+                        if id.take().as_deref() == Some(server_id) {
+                            return Ok(Some(server));
+                        }
+                    }
+                    _ => {}
+                }
+                element_path.pop();
+                text.clear();
+            }
+            _ => {}
+        }
+    }
+
+    Ok(None)
+}
+
+/// Substitute the `${env.NAME}` placeholders in a value, looking each variable up with `env_var`
+fn resolve_placeholders(
+    server_id: &str,
+    value: &str,
+    env_var: impl Fn(&str) -> Option<String>,
+) -> Result<String, SettingsError> {
+    let unresolved = |placeholder: &str| {
+        SettingsError::UnresolvedPlaceholder(server_id.to_string(), placeholder.to_string())
+    };
+
+    let mut resolved = String::new();
+    let mut remaining = value;
+    while let Some(start) = remaining.find("${") {
+        resolved.push_str(&remaining[..start]);
+        let Some(length) = remaining[start..].find('}') else {
+            return Err(unresolved(&remaining[start..]));
+        };
+        let placeholder = &remaining[start..start + length + 1];
+        let variable = placeholder[2..placeholder.len() - 1]
+            .strip_prefix("env.")
+            .ok_or_else(|| unresolved(placeholder))?;
+        resolved.push_str(&env_var(variable).ok_or_else(|| unresolved(placeholder))?);
+        remaining = &remaining[start + length + 1..];
+    }
+    resolved.push_str(remaining);
+
+    Ok(resolved)
+}
+
+#[cfg(test)]
+mod tests {
+    use temp_dir::TempDir;
+
+    use super::*;
+
+    const SETTINGS_XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<settings xmlns="http://maven.apache.org/SETTINGS/1.0.0">
+  <servers>
+    <server>
+      <id>other</id>
+      <username>other_username</username>
+      <password>other_password</password>
+    </server>
+    <server>
+      <id>central</id>
+      <username>central_username</username>
+      <password>central_password</password>
+    </server>
+    <server>
+      <id>no-password</id>
+      <username>central_username</username>
+    </server>
+    <server>
+      <id>placeholders</id>
+      <username>placeholders_username</username>
+      <password>${central.password}</password>
+    </server>
+  </servers>
+</settings>"#;
+
+    fn settings_file(temp_dir: &TempDir) -> eyre::Result<PathBuf> {
+        let settings_path = temp_dir.child("settings.xml");
+        std::fs::write(&settings_path, SETTINGS_XML)?;
+        Ok(settings_path)
+    }
+
+    #[test]
+    fn server_credentials() -> eyre::Result<()> {
+        let temp_dir = TempDir::new()?;
+
+        let credentials = Credentials::from_settings_xml(&settings_file(&temp_dir)?, "central")?;
+
+        assert_eq!(credentials.username, "central_username");
+        assert_eq!(credentials.password, "central_password");
+
+        Ok(())
+    }
+
+    #[test]
+    fn colon_separated_credentials() -> eyre::Result<()> {
+        let credentials = Credentials::from_colon_string("test_user:test_password")?;
+        assert_eq!(credentials.username, "test_user");
+        assert_eq!(credentials.password, "test_password");
+
+        let credentials = Credentials::from_colon_string("test_user:pass:word:")?;
+        assert_eq!(credentials.username, "test_user");
+        assert_eq!(credentials.password, "pass:word:");
+
+        let credentials = Credentials::from_colon_string("test_user:")?;
+        assert_eq!(credentials.password, "");
+
+        assert!(Credentials::from_colon_string("test_user").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn authorization_header() {
+        let credentials = Credentials::new("test_user".to_string(), "test_password".to_string());
+
+        assert_eq!(
+            credentials.authorization(AuthScheme::UserToken),
+            "UserToken dGVzdF91c2VyOnRlc3RfcGFzc3dvcmQ="
+        );
+        assert_eq!(
+            credentials.authorization(AuthScheme::Basic),
+            "Basic dGVzdF91c2VyOnRlc3RfcGFzc3dvcmQ="
+        );
+    }
+
+    #[test]
+    fn missing_settings_file() -> eyre::Result<()> {
+        let temp_dir = TempDir::new()?;
+
+        let error = Credentials::from_settings_xml(&temp_dir.child("settings.xml"), "central")
+            .err()
+            .ok_or_else(|| eyre::eyre!("Read credentials from a missing file"))?;
+
+        assert!(matches!(error, SettingsError::Read(_, _)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn missing_server() -> eyre::Result<()> {
+        let temp_dir = TempDir::new()?;
+        let settings_path = settings_file(&temp_dir)?;
+
+        let error = Credentials::from_settings_xml(&settings_path, "missing")
+            .err()
+            .ok_or_else(|| eyre::eyre!("Read credentials of a missing server"))?;
+        assert!(
+            matches!(error, SettingsError::ServerNotFound(server_id) if server_id == "missing")
+        );
+
+        let error = Credentials::from_settings_xml(&settings_path, "no-password")
+            .err()
+            .ok_or_else(|| eyre::eyre!("Read credentials without a password"))?;
+        assert!(matches!(error, SettingsError::MissingField(_, "password")));
+
+        Ok(())
+    }
+
+    #[test]
+    fn placeholders() -> eyre::Result<()> {
+        // looked up without touching the environment of the process, which other tests share
+        let env_var = |variable: &str| {
+            (variable == "PORTAL_API_TEST_USERNAME").then(|| "env_username".to_string())
+        };
+
+        assert_eq!(
+            resolve_placeholders("central", "${env.PORTAL_API_TEST_USERNAME}", env_var)?,
+            "env_username"
+        );
+        assert_eq!(
+            resolve_placeholders(
+                "central",
+                "prefix-${env.PORTAL_API_TEST_USERNAME}-suffix",
+                env_var
+            )?,
+            "prefix-env_username-suffix"
+        );
+        assert!(matches!(
+            resolve_placeholders("central", "${env.PORTAL_API_TEST_MISSING}", env_var),
+            Err(SettingsError::UnresolvedPlaceholder(_, placeholder))
+                if placeholder == "${env.PORTAL_API_TEST_MISSING}"
+        ));
+
+        let temp_dir = TempDir::new()?;
+        let error = Credentials::from_settings_xml(&settings_file(&temp_dir)?, "placeholders")
+            .err()
+            .ok_or_else(|| eyre::eyre!("Resolved a non-environment placeholder"))?;
+        assert!(matches!(
+            error,
+            SettingsError::UnresolvedPlaceholder(_, placeholder) if placeholder == "${central.password}"
+        ));
+
+        Ok(())
     }
 }
@@ -1,59 +1,163 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
-use api_types::PublishingType;
-use eyre::ContextCompat;
+use api_types::{DeploymentStatus, PublishingType};
+use eyre::{ContextCompat, WrapErr};
+use repository::traits::ZipFile;
 use reqwest::{
-    header::{HeaderMap, HeaderValue, USER_AGENT},
+    header::{HeaderMap, HeaderName, HeaderValue, USER_AGENT},
     multipart::{Form, Part},
     Body, Client, ClientBuilder,
 };
 use tokio::fs::File;
+use tokio::io::AsyncRead;
 use tokio_util::codec::{BytesCodec, FramedRead};
 use url::Url;
 
 pub mod api_types;
 pub mod credentials;
 
-pub use credentials::Credentials;
+pub use credentials::{AuthScheme, Credentials, SettingsError};
 
 pub const CENTRAL_HOST: &str = "https://central.sonatype.com";
 
 const API_ENDPOINT: &str = "/api/v1/publisher/";
 const UPLOAD_ENDPOINT: &str = "upload"; // relative to API_ENDPOINT
+const STATUS_ENDPOINT: &str = "status"; // relative to API_ENDPOINT
+const DEPLOYMENT_ENDPOINT: &str = "deployment/"; // relative to API_ENDPOINT
 
 const UPLOAD_MIME_STR: &str = "application/octet-stream";
+/// The name given to bundles uploaded from memory, unless the caller chooses one
+const DEFAULT_BUNDLE_FILE_NAME: &str = "bundle.zip";
 
 /// The client for publishing via the Central Publisher Portal
 pub struct PortalApiClient {
     client: Client,
     host: Url,
+    auth_scheme: AuthScheme,
 }
 
-impl PortalApiClient {
-    /// Publish to Maven Central
+/// Builds a [PortalApiClient], publishing to Maven Central unless another host is set
+pub struct PortalApiClientBuilder {
+    host: String,
+    headers: Vec<(String, String)>,
+    allowed_hosts: Option<Vec<String>>,
+    auth_scheme: AuthScheme,
+}
+
+impl PortalApiClientBuilder {
+    /// Publish to a compatible server instead of Maven Central
+    pub fn host(mut self, host: &str) -> Self {
+        self.host = host.to_string();
+        self
+    }
+
+    /// Send the header with every request, replacing any default of the same name
     ///
-    /// Provide [Credentials] to publish via a generated token.
-    pub fn central_client() -> eyre::Result<Self> {
-        Self::client(CENTRAL_HOST)
+    /// Invalid names and values are reported by [PortalApiClientBuilder::build].
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
     }
 
-    /// Publish to a compatible server
+    /// Present credentials with another scheme than Central's `UserToken`
+    pub fn auth_scheme(mut self, auth_scheme: AuthScheme) -> Self {
+        self.auth_scheme = auth_scheme;
+        self
+    }
+
+    /// Only build a client for an `https` URL on Maven Central or one of these hosts
     ///
-    /// Publish to an arbitrary server that implements the same API as Maven Central.
-    pub fn client(host: &str) -> eyre::Result<Self> {
+    /// For when the host is not fully trusted, such as when it comes from configuration.
+    pub fn allowed_hosts<I, S>(mut self, allowed_hosts: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.allowed_hosts = Some(allowed_hosts.into_iter().map(Into::into).collect());
+        self
+    }
+
+    pub fn build(self) -> eyre::Result<PortalApiClient> {
+        if let Some(allowed_hosts) = &self.allowed_hosts {
+            check_allowed_host(&self.host, allowed_hosts)?;
+        }
+
         let mut default_headers = HeaderMap::new();
 
         let user_agent_header =
             HeaderValue::from_str(&format!("portal_api client ({})", env!("CARGO_PKG_NAME")))?;
         default_headers.insert(USER_AGENT, user_agent_header);
 
+        for (name, value) in self.headers {
+            let header_name = HeaderName::from_bytes(name.as_bytes())
+                .wrap_err_with(|| format!("Invalid header name: {name:?}"))?;
+            let header_value = HeaderValue::from_str(&value)
+                .wrap_err_with(|| format!("Invalid value for header {name}"))?;
+            default_headers.insert(header_name, header_value);
+        }
+
         let client = ClientBuilder::default()
             .default_headers(default_headers)
             .build()?;
 
-        let host = Url::parse(host)?;
+        let host = Url::parse(&self.host)?;
+
+        Ok(PortalApiClient {
+            client,
+            host,
+            auth_scheme: self.auth_scheme,
+        })
+    }
+}
+
+/// Check that `host` is an `https` URL on Maven Central or one of the `allowed_hosts`
+pub fn check_allowed_host(host: &str, allowed_hosts: &[String]) -> eyre::Result<()> {
+    let url = Url::parse(host).wrap_err_with(|| format!("Invalid host URL: {host:?}"))?;
+    if url.scheme() != "https" {
+        eyre::bail!("Only https hosts are allowed, not {:?}", url.scheme());
+    }
+
+    let host_name = url
+        .host_str()
+        .wrap_err_with(|| format!("No host name in {host:?}"))?;
+    let central_host_name = Url::parse(CENTRAL_HOST)?
+        .host_str()
+        .map(str::to_string)
+        .unwrap_or_default();
+    let is_allowed = std::iter::once(&central_host_name)
+        .chain(allowed_hosts)
+        .any(|allowed_host| allowed_host.eq_ignore_ascii_case(host_name));
+    if !is_allowed {
+        eyre::bail!("Host {host_name:?} is not in the allowed hosts");
+    }
+
+    Ok(())
+}
 
-        Ok(Self { client, host })
+impl PortalApiClient {
+    /// Publish to Maven Central
+    ///
+    /// Provide [Credentials] to publish via a generated token.
+    pub fn central_client() -> eyre::Result<Self> {
+        Self::client(CENTRAL_HOST)
+    }
+
+    /// Publish to a compatible server
+    ///
+    /// Publish to an arbitrary server that implements the same API as Maven Central.
+    pub fn client(host: &str) -> eyre::Result<Self> {
+        Self::builder().host(host).build()
+    }
+
+    /// Configure a client beyond the host, such as with headers required by a gateway in front of Central
+    pub fn builder() -> PortalApiClientBuilder {
+        PortalApiClientBuilder {
+            host: CENTRAL_HOST.to_string(),
+            headers: Vec::new(),
+            allowed_hosts: None,
+            auth_scheme: AuthScheme::default(),
+        }
     }
 
     #[tracing::instrument(skip(self, credentials, upload_bundle_contents))]
@@ -63,9 +167,29 @@ impl PortalApiClient {
         deployment_name: &str,
         publishing_type: PublishingType,
         upload_bundle_contents: Vec<u8>,
+    ) -> eyre::Result<String> {
+        self.upload_from_memory_with_file_name(
+            credentials,
+            deployment_name,
+            publishing_type,
+            upload_bundle_contents,
+            DEFAULT_BUNDLE_FILE_NAME,
+        )
+        .await
+    }
+
+    /// Upload a bundle held in memory, giving it `file_name` rather than `bundle.zip` in the upload
+    #[tracing::instrument(skip(self, credentials, upload_bundle_contents))]
+    pub async fn upload_from_memory_with_file_name(
+        &self,
+        credentials: &Credentials,
+        deployment_name: &str,
+        publishing_type: PublishingType,
+        upload_bundle_contents: Vec<u8>,
+        file_name: &str,
     ) -> eyre::Result<String> {
         let part = Part::bytes(upload_bundle_contents)
-            .file_name("bundle.zip")
+            .file_name(file_name.to_string())
             .mime_str(UPLOAD_MIME_STR)?;
 
         let deployment_id = self
@@ -84,15 +208,68 @@ impl PortalApiClient {
         upload_bundle_path: &PathBuf,
     ) -> eyre::Result<String> {
         let file = File::open(upload_bundle_path).await?;
-        let stream = FramedRead::new(file, BytesCodec::new());
-        let body = Body::wrap_stream(stream);
         let file_name = upload_bundle_path
             .file_name()
             .wrap_err("Expected a valid filename")?
             .to_string_lossy()
             .to_string();
+
+        self.upload_from_reader(
+            credentials,
+            deployment_name,
+            publishing_type,
+            file,
+            &file_name,
+        )
+        .await
+    }
+
+    /// Zip a directory laid out like a Maven repository, such as a `target/` staging directory, and upload it
+    ///
+    /// Entries keep their paths relative to `directory`. Hidden files and directories are skipped.
+    #[tracing::instrument(skip(self, credentials))]
+    pub async fn upload_from_directory(
+        &self,
+        credentials: &Credentials,
+        deployment_name: &str,
+        publishing_type: PublishingType,
+        directory: &Path,
+    ) -> eyre::Result<String> {
+        let mut zip_file = ZipFile::in_memory();
+        for relative_path in directory_files(directory).await? {
+            let file = File::open(directory.join(&relative_path)).await?;
+            zip_file.add_file(relative_path, file).await?;
+        }
+        tracing::debug!("Zipped {} file(s)", zip_file.entries().len());
+
+        self.upload_from_memory(
+            credentials,
+            deployment_name,
+            publishing_type,
+            zip_file.as_buffer()?,
+        )
+        .await
+    }
+
+    /// Upload a bundle streamed from any source, such as a pipe or generated content
+    ///
+    /// `file_name` is the name given to the bundle in the upload.
+    #[tracing::instrument(skip(self, credentials, reader))]
+    pub async fn upload_from_reader<R>(
+        &self,
+        credentials: &Credentials,
+        deployment_name: &str,
+        publishing_type: PublishingType,
+        reader: R,
+        file_name: &str,
+    ) -> eyre::Result<String>
+    where
+        R: AsyncRead + Send + Sync + 'static,
+    {
+        let stream = FramedRead::new(reader, BytesCodec::new());
+        let body = Body::wrap_stream(stream);
         let part = Part::stream(body)
-            .file_name(file_name)
+            .file_name(file_name.to_string())
             .mime_str(UPLOAD_MIME_STR)?;
 
         let deployment_id = self
@@ -102,7 +279,89 @@ impl PortalApiClient {
         Ok(deployment_id)
     }
 
-    #[tracing::instrument(skip(self, credentials, part))]
+    /// Retrieve the current state of a deployment
+    #[tracing::instrument(skip(self, credentials))]
+    pub async fn deployment_status(
+        &self,
+        credentials: &Credentials,
+        deployment_id: &str,
+    ) -> eyre::Result<DeploymentStatus> {
+        let url = self.host.join(API_ENDPOINT)?.join(STATUS_ENDPOINT)?;
+
+        let request = self.client.post(url).query(&[("id", deployment_id)]);
+        let request = credentials.add_credentials_to_request(request, self.auth_scheme)?;
+
+        let response = request.send().await?;
+
+        tracing::trace!("Got response: {:?}", response);
+        if !response.status().is_success() {
+            let status = response.status();
+            let response_body = response.text().await?;
+            eyre::bail!("Status request failed ({status}): {response_body}");
+        }
+
+        Ok(response.json().await?)
+    }
+
+    /// Poll the status of a deployment until Central has finished validating it
+    #[tracing::instrument(skip(self, credentials))]
+    pub async fn wait_for_validation(
+        &self,
+        credentials: &Credentials,
+        deployment_id: &str,
+        poll_interval: Duration,
+        timeout: Duration,
+    ) -> eyre::Result<DeploymentStatus> {
+        let started = Instant::now();
+        loop {
+            let deployment_status = self.deployment_status(credentials, deployment_id).await?;
+            if deployment_status.deployment_state.is_validation_complete() {
+                return Ok(deployment_status);
+            }
+
+            if started.elapsed() >= timeout {
+                eyre::bail!(
+                    "Timed out waiting for deployment {deployment_id} to be validated, last state: {:?}",
+                    deployment_status.deployment_state
+                );
+            }
+            tracing::debug!(
+                "Deployment is {:?}, checking again in {poll_interval:?}",
+                deployment_status.deployment_state
+            );
+            tokio::time::sleep(poll_interval).await;
+        }
+    }
+
+    /// Drop a deployment that has not been published
+    #[tracing::instrument(skip(self, credentials))]
+    pub async fn drop_deployment(
+        &self,
+        credentials: &Credentials,
+        deployment_id: &str,
+    ) -> eyre::Result<()> {
+        let url = self
+            .host
+            .join(API_ENDPOINT)?
+            .join(DEPLOYMENT_ENDPOINT)?
+            .join(deployment_id)?;
+
+        let request = self.client.delete(url);
+        let request = credentials.add_credentials_to_request(request, self.auth_scheme)?;
+
+        let response = request.send().await?;
+
+        tracing::trace!("Got response: {:?}", response);
+        if !response.status().is_success() {
+            let status = response.status();
+            let response_body = response.text().await?;
+            eyre::bail!("Drop request failed ({status}): {response_body}");
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self, credentials, part), fields(deployment_id))]
     async fn upload_part(
         &self,
         credentials: &Credentials,
@@ -122,17 +381,21 @@ impl PortalApiClient {
             .query(&[("name", deployment_name)])
             .query(&[("publishingType", publishing_type)])
             .multipart(bundle);
-        let request = credentials.add_credentials_to_request(request)?;
+        let request = credentials.add_credentials_to_request(request, self.auth_scheme)?;
 
         let response = request.send().await?;
 
         tracing::trace!("Got response: {:?}", response);
         let deployment_id = if response.status().is_success() {
+            let deployment_id = response.text().await?;
+            tracing::Span::current().record("deployment_id", &deployment_id);
             tracing::info!("Upload request succeeded");
-            response.text().await?
+            deployment_id
         } else {
-            tracing::debug!("Response body: {:?}", response.text().await?);
-            eyre::bail!("Upload request failed");
+            let status = response.status();
+            let response_body = response.text().await?;
+            tracing::debug!("Response body: {response_body:?}");
+            eyre::bail!("Upload request failed ({status}): {response_body}");
         };
         tracing::trace!("Upload request to {url_display} - Complete");
 
@@ -140,6 +403,30 @@ impl PortalApiClient {
     }
 }
 
+/// The files under the directory that are not hidden, relative to it and sorted
+async fn directory_files(directory: &Path) -> eyre::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut directories = vec![PathBuf::new()];
+    while let Some(relative_directory) = directories.pop() {
+        let mut entries = tokio::fs::read_dir(directory.join(&relative_directory)).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+
+            let relative_path = relative_directory.join(entry.file_name());
+            if entry.file_type().await?.is_dir() {
+                directories.push(relative_path);
+            } else {
+                files.push(relative_path);
+            }
+        }
+    }
+    files.sort();
+
+    Ok(files)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -171,6 +458,250 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn upload_from_reader() -> eyre::Result<()> {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/api/v1/publisher/upload"))
+            .and(query_param("name", "test_deployment"))
+            .and(body_string_contains("generated bundle contents"))
+            .and(body_string_contains(
+                r#"form-data; name="bundle"; filename="bundle.zip""#,
+            ))
+            .respond_with(ResponseTemplate::new(201).set_body_string("test_deployment_id"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let client = PortalApiClient::client(&mock_server.uri())?;
+
+        let deployment_id = client
+            .upload_from_reader(
+                &Credentials::new("test_username".to_string(), "test_password".to_string()),
+                "test_deployment",
+                PublishingType::Automatic,
+                std::io::Cursor::new(b"generated bundle contents".to_vec()),
+                "bundle.zip",
+            )
+            .await?;
+
+        assert_eq!(deployment_id, "test_deployment_id");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn upload_from_directory() -> eyre::Result<()> {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/api/v1/publisher/upload"))
+            .and(query_param("name", "test_deployment"))
+            .respond_with(ResponseTemplate::new(201).set_body_string("test_deployment_id"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let temp_dir = temp_dir::TempDir::new()?;
+        let version_dir = temp_dir.path().join("com/example/example/0.1.0");
+        tokio::fs::create_dir_all(&version_dir).await?;
+        tokio::fs::create_dir_all(temp_dir.path().join(".git")).await?;
+        for file_path in [
+            version_dir.join("example-0.1.0.jar"),
+            version_dir.join("example-0.1.0.pom"),
+            version_dir.join(".DS_Store"),
+            temp_dir.path().join(".git/config"),
+        ] {
+            tokio::fs::write(file_path, "test_file_content").await?;
+        }
+
+        let client = PortalApiClient::client(&mock_server.uri())?;
+
+        let deployment_id = client
+            .upload_from_directory(
+                &Credentials::new("test_username".to_string(), "test_password".to_string()),
+                "test_deployment",
+                PublishingType::Automatic,
+                temp_dir.path(),
+            )
+            .await?;
+
+        assert_eq!(deployment_id, "test_deployment_id");
+
+        let requests = mock_server.received_requests().await.unwrap_or_default();
+        let body = &requests[0].body;
+        let contains = |needle: &[u8]| body.windows(needle.len()).any(|window| window == needle);
+        assert!(contains(b"com/example/example/0.1.0/example-0.1.0.jar"));
+        assert!(contains(b"com/example/example/0.1.0/example-0.1.0.pom"));
+        assert!(!contains(b".DS_Store"));
+        assert!(!contains(b".git"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn upload_with_file_name() -> eyre::Result<()> {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/api/v1/publisher/upload"))
+            .respond_with(ResponseTemplate::new(201).set_body_string("test_deployment_id"))
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+
+        let client = PortalApiClient::client(&mock_server.uri())?;
+        let credentials =
+            Credentials::new("test_username".to_string(), "test_password".to_string());
+
+        client
+            .upload_from_memory(
+                &credentials,
+                "test_deployment",
+                PublishingType::Automatic,
+                b"test_bundle".to_vec(),
+            )
+            .await?;
+        client
+            .upload_from_memory_with_file_name(
+                &credentials,
+                "test_deployment",
+                PublishingType::Automatic,
+                b"test_bundle".to_vec(),
+                "comexample-1.zip",
+            )
+            .await?;
+
+        let requests = mock_server.received_requests().await.unwrap_or_default();
+        let contains =
+            |body: &[u8], needle: &[u8]| body.windows(needle.len()).any(|window| window == needle);
+        assert!(contains(&requests[0].body, br#"filename="bundle.zip""#));
+        assert!(contains(
+            &requests[1].body,
+            br#"filename="comexample-1.zip""#
+        ));
+        assert!(!contains(&requests[1].body, b"bundle.zip"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn upload_with_custom_headers() -> eyre::Result<()> {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/api/v1/publisher/upload"))
+            .and(header("x-api-key", "test_api_key"))
+            .and(header("x-trace-id", "test_trace_id"))
+            .respond_with(ResponseTemplate::new(201).set_body_string("test_deployment_id"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let client = PortalApiClient::builder()
+            .host(&mock_server.uri())
+            .header("X-Api-Key", "test_api_key")
+            .header("x-trace-id", "test_trace_id")
+            .build()?;
+
+        let deployment_id = client
+            .upload_from_memory(
+                &Credentials::new("test_username".to_string(), "test_password".to_string()),
+                "test_deployment",
+                PublishingType::Automatic,
+                b"test_bundle".to_vec(),
+            )
+            .await?;
+        assert_eq!(deployment_id, "test_deployment_id");
+
+        mock_server.verify().await;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn upload_with_basic_auth() -> eyre::Result<()> {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/api/v1/publisher/upload"))
+            .and(header(
+                "Authorization",
+                "Basic dGVzdF91c2VybmFtZTp0ZXN0X3Bhc3N3b3Jk",
+            ))
+            .respond_with(ResponseTemplate::new(201).set_body_string("test_deployment_id"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let client = PortalApiClient::builder()
+            .host(&mock_server.uri())
+            .auth_scheme(AuthScheme::Basic)
+            .build()?;
+
+        let deployment_id = client
+            .upload_from_memory(
+                &Credentials::new("test_username".to_string(), "test_password".to_string()),
+                "test_deployment",
+                PublishingType::Automatic,
+                b"test_bundle".to_vec(),
+            )
+            .await?;
+        assert_eq!(deployment_id, "test_deployment_id");
+
+        mock_server.verify().await;
+
+        Ok(())
+    }
+
+    #[test]
+    fn reject_invalid_headers() {
+        assert!(PortalApiClient::builder()
+            .header("x api key", "test_api_key")
+            .build()
+            .is_err());
+        assert!(PortalApiClient::builder()
+            .header("x-api-key", "test\napi_key")
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn restrict_to_allowed_hosts() -> eyre::Result<()> {
+        let allowed_hosts = ["central.example".to_string()];
+
+        check_allowed_host(CENTRAL_HOST, &[])?;
+        check_allowed_host("https://central.example/api", &allowed_hosts)?;
+        check_allowed_host("https://CENTRAL.example:8443", &allowed_hosts)?;
+
+        for host in [
+            "http://central.sonatype.com",
+            "http://central.example",
+            "https://central.example.attacker.example",
+            "https://169.254.169.254/latest/meta-data",
+            "file:///etc/passwd",
+            "central.example",
+        ] {
+            assert!(
+                check_allowed_host(host, &allowed_hosts).is_err(),
+                "Allowed {host:?}"
+            );
+        }
+
+        PortalApiClient::builder()
+            .host("https://central.example")
+            .allowed_hosts(allowed_hosts.clone())
+            .build()?;
+        assert!(PortalApiClient::builder()
+            .host("https://internal.example")
+            .allowed_hosts(allowed_hosts)
+            .build()
+            .is_err());
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn failed_upload() -> eyre::Result<()> {
         let mock_server = MockServer::start().await;
@@ -195,6 +726,66 @@ mod tests {
             .expect_err("Succeeded, incorrectly");
 
         assert!(error.to_string().contains("Upload request failed"));
+        assert!(error.to_string().contains("example_error"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn wait_for_validation() -> eyre::Result<()> {
+        let mock_server = MockServer::start().await;
+
+        for (deployment_state, times) in [("VALIDATING", 2), ("VALIDATED", 1)] {
+            Mock::given(method("POST"))
+                .and(path("/api/v1/publisher/status"))
+                .and(query_param("id", "test_deployment_id"))
+                .respond_with(ResponseTemplate::new(200).set_body_string(format!(
+                    r#"{{"deploymentId": "test_deployment_id", "deploymentName": "test_deployment", "deploymentState": "{deployment_state}"}}"#
+                )))
+                .up_to_n_times(times)
+                .expect(times)
+                .mount(&mock_server)
+                .await;
+        }
+
+        let client = PortalApiClient::client(&mock_server.uri())?;
+
+        let deployment_status = client
+            .wait_for_validation(
+                &Credentials::new("test_username".to_string(), "test_password".to_string()),
+                "test_deployment_id",
+                Duration::from_millis(1),
+                Duration::from_secs(10),
+            )
+            .await?;
+
+        assert_eq!(
+            deployment_status.deployment_state,
+            api_types::DeploymentState::Validated
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn drop_deployment() -> eyre::Result<()> {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("DELETE"))
+            .and(path("/api/v1/publisher/deployment/test_deployment_id"))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let client = PortalApiClient::client(&mock_server.uri())?;
+
+        client
+            .drop_deployment(
+                &Credentials::new("test_username".to_string(), "test_password".to_string()),
+                "test_deployment_id",
+            )
+            .await?;
 
         Ok(())
     }
@@ -0,0 +1,180 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+use md5::Md5;
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha512};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// The checksum sidecar files that are accepted alongside artifacts
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChecksumAlgorithm {
+    Md5,
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+impl ChecksumAlgorithm {
+    pub const ALL: [ChecksumAlgorithm; 4] = [
+        ChecksumAlgorithm::Md5,
+        ChecksumAlgorithm::Sha1,
+        ChecksumAlgorithm::Sha256,
+        ChecksumAlgorithm::Sha512,
+    ];
+
+    /// The extension of the sidecar file, without the leading `.`
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ChecksumAlgorithm::Md5 => "md5",
+            ChecksumAlgorithm::Sha1 => "sha1",
+            ChecksumAlgorithm::Sha256 => "sha256",
+            ChecksumAlgorithm::Sha512 => "sha512",
+        }
+    }
+
+    /// Compute the lowercase hex digest of the contents
+    pub fn digest(&self, contents: &[u8]) -> String {
+        match self {
+            ChecksumAlgorithm::Md5 => to_hex(&Md5::digest(contents)),
+            ChecksumAlgorithm::Sha1 => to_hex(&Sha1::digest(contents)),
+            ChecksumAlgorithm::Sha256 => to_hex(&Sha256::digest(contents)),
+            ChecksumAlgorithm::Sha512 => to_hex(&Sha512::digest(contents)),
+        }
+    }
+
+    /// Compute the lowercase hex digest of everything read, without holding it all in memory
+    pub async fn digest_reader<R: AsyncRead + Unpin>(&self, reader: R) -> io::Result<String> {
+        match self {
+            ChecksumAlgorithm::Md5 => digest_reader_with::<Md5, _>(reader).await,
+            ChecksumAlgorithm::Sha1 => digest_reader_with::<Sha1, _>(reader).await,
+            ChecksumAlgorithm::Sha256 => digest_reader_with::<Sha256, _>(reader).await,
+            ChecksumAlgorithm::Sha512 => digest_reader_with::<Sha512, _>(reader).await,
+        }
+    }
+
+    /// The path of this algorithm's sidecar for the file
+    pub fn sidecar_path(&self, file_path: &Path) -> PathBuf {
+        let mut sidecar_path = file_path.as_os_str().to_owned();
+        sidecar_path.push(".");
+        sidecar_path.push(self.extension());
+        PathBuf::from(sidecar_path)
+    }
+}
+
+/// If the path is a checksum sidecar, the file it describes and the algorithm used
+pub fn checksum_target(sidecar_path: &Path) -> Option<(PathBuf, ChecksumAlgorithm)> {
+    let extension = sidecar_path.extension()?.to_str()?;
+    let algorithm = ChecksumAlgorithm::ALL
+        .into_iter()
+        .find(|algorithm| algorithm.extension() == extension)?;
+
+    Some((sidecar_path.with_extension(""), algorithm))
+}
+
+/// Whether a sidecar's contents record the expected digest
+///
+/// Sidecars may be just the digest or the `sha1sum` style `<digest>  <file name>`.
+pub fn sidecar_matches(sidecar_contents: &str, expected_digest: &str) -> bool {
+    sidecar_contents
+        .split_whitespace()
+        .next()
+        .is_some_and(|digest| digest.eq_ignore_ascii_case(expected_digest))
+}
+
+/// How to handle a checksum sidecar that does not match the file it describes
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum ChecksumMismatchPolicy {
+    /// Bundle the sidecar as uploaded
+    #[default]
+    Ignore,
+    /// Replace the sidecar with the checksum of the uploaded file
+    Regenerate,
+    /// Refuse to bundle the repository
+    Reject,
+}
+
+impl TryFrom<&str> for ChecksumMismatchPolicy {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value.to_lowercase().as_str() {
+            "ignore" => Ok(ChecksumMismatchPolicy::Ignore),
+            "regenerate" => Ok(ChecksumMismatchPolicy::Regenerate),
+            "reject" => Ok(ChecksumMismatchPolicy::Reject),
+            other => Err(format!(
+                "Could not convert {other} into a ChecksumMismatchPolicy"
+            )),
+        }
+    }
+}
+
+async fn digest_reader_with<D: Digest, R: AsyncRead + Unpin>(mut reader: R) -> io::Result<String> {
+    let mut hasher = D::new();
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let read = reader.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+
+    Ok(to_hex(&hasher.finalize()))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn digest_readers() -> std::io::Result<()> {
+        for algorithm in ChecksumAlgorithm::ALL {
+            assert_eq!(
+                algorithm.digest_reader(&b"test_file_content"[..]).await?,
+                algorithm.digest(b"test_file_content")
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn digests() {
+        assert_eq!(
+            ChecksumAlgorithm::Md5.digest(b"test_file_content"),
+            "a9fb1b2ff9bbbabce16f8ac966bf6499"
+        );
+        assert_eq!(
+            ChecksumAlgorithm::Sha1.digest(b"test_file_content"),
+            "67b983e13c778959f437eb8ddc785b82aed2ce30"
+        );
+    }
+
+    #[test]
+    fn sidecar_targets() {
+        assert_eq!(
+            checksum_target(Path::new("com/example/example-0.1.0.jar.sha1")),
+            Some((
+                PathBuf::from("com/example/example-0.1.0.jar"),
+                ChecksumAlgorithm::Sha1
+            ))
+        );
+        assert_eq!(
+            checksum_target(Path::new("com/example/example-0.1.0.jar")),
+            None
+        );
+    }
+
+    #[test]
+    fn sidecar_formats() {
+        assert!(sidecar_matches("ABCDEF\n", "abcdef"));
+        assert!(sidecar_matches("abcdef  example-0.1.0.jar\n", "abcdef"));
+        assert!(!sidecar_matches("", "abcdef"));
+        assert!(!sidecar_matches("abcde0", "abcdef"));
+    }
+}
@@ -1,3 +1,9 @@
+pub mod checksums;
+pub mod metadata;
+pub mod namespaces;
+pub mod options;
+pub mod poms;
+pub mod signatures;
 pub mod traits;
 
 #[cfg(feature = "local")]
@@ -2,45 +2,184 @@ use async_trait::async_trait;
 use async_walkdir::{Filtering, WalkDir};
 use bytes::Bytes;
 use eyre::WrapErr;
-use futures::{Stream, TryStreamExt};
+use flate2::{write::GzEncoder, Compression};
+use futures::stream::BoxStream;
+use futures::TryStreamExt;
 use path_absolutize::Absolutize;
-use std::collections::HashMap;
-use std::fmt::Debug;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap};
 use std::io;
 use std::net::IpAddr;
 use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 use temp_dir::TempDir;
+use time::OffsetDateTime;
 use tokio::fs::OpenOptions;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::sync::RwLock;
+use tokio::sync::{Mutex, RwLock};
 use tokio::{fs::File, io::BufWriter};
-use tokio_util::io::StreamReader;
 use tracing::instrument;
 
-use crate::traits::{Repository, RepositoryKey, RepositoryState, ZipFile, NO_PROFILE};
+use crate::checksums::{
+    checksum_target, sidecar_matches, ChecksumAlgorithm, ChecksumMismatchPolicy,
+};
+use crate::metadata::generate_maven_metadata;
+use crate::options::RepositoryOptions;
+use crate::poms::{check_poms, is_pom};
+use crate::signatures::{requires_signature, signature_path, SIGNATURE_EXTENSION};
+use crate::traits::{
+    FileLimitExceeded, FileMetadata, QuotaExceeded, Repository, RepositoryKey, RepositoryNotFound,
+    RepositoryNotOpen, RepositoryState, StoredFile, ZipFile, NO_PROFILE,
+};
 
 const REPOSITORY_FOLDER: &str = "repository_contents";
 const REPOSITORY_STATE_FILE: &str = "repository_state";
+const REPOSITORY_ERRORS_FILE: &str = "repository_errors";
+const REPOSITORY_DEPLOYMENT_FILE: &str = "repository_deployment";
+const REPOSITORY_DEPLOYMENT_SETTLED_FILE: &str = "repository_deployment_settled";
+const REPOSITORY_ACTIVITY_FILE: &str = "repository_activity";
+const REPOSITORY_LAST_ACTIVITY_FILE: &str = "repository_last_activity";
+const REPOSITORY_INDEXES_FILE: &str = "repository_indexes";
+const PROFILE_IDS_FILE: &str = "profile_ids";
+const DEFAULT_TEMP_DIR_PREFIX: &str = "local-repository";
+/// Sessions kept before the least recently used is forgotten, so that a client inventing session ids cannot grow them without end
+const MAX_NO_PROFILE_SESSIONS: usize = 10_000;
 
 pub struct LocalRepository {
-    root: TempDir,
+    root: PathBuf,
+    /// Removed when the repository is dropped, if the root is temporary
+    _temp_dir: Option<TempDir>,
     repository_indexes: RwLock<HashMap<String, u32>>,
+    /// The open repository of each upload session, forgotten once it is closed or dropped
+    no_profile_sessions: RwLock<HashMap<String, NoProfileSession>>,
+    /// The namespaces that have been given numeric profile IDs, the first having ID 1
+    profile_namespaces: RwLock<Vec<String>>,
+    /// The number of files in each repository, counted from disk when first needed
+    file_counts: RwLock<HashMap<String, usize>>,
+    /// The bytes written or being written to each repository, measured from disk when first needed
+    repository_sizes: RwLock<HashMap<String, u64>>,
+    /// Held while a file in the root is replaced, so that the latest contents are written last
+    root_files: Mutex<()>,
+    options: RepositoryOptions,
 }
 
 impl LocalRepository {
     pub fn new() -> eyre::Result<Self> {
-        let root = TempDir::with_prefix("local-repository")?;
+        Self::with_options(RepositoryOptions::default())
+    }
+
+    pub fn with_options(options: RepositoryOptions) -> eyre::Result<Self> {
+        let temp_dir_parent = match &options.temp_dir_parent {
+            Some(temp_dir_parent) => temp_dir_parent.absolutize()?.into_owned(),
+            None => std::env::temp_dir(),
+        };
+        let temp_dir_prefix = options
+            .temp_dir_prefix
+            .as_deref()
+            .unwrap_or(DEFAULT_TEMP_DIR_PREFIX);
+        // an absolute prefix replaces the system temporary directory that it is joined to
+        let temp_dir =
+            TempDir::with_prefix(temp_dir_parent.join(temp_dir_prefix).to_string_lossy())?;
+        if options.private_root {
+            restrict_to_owner(temp_dir.path())?;
+        }
 
         let repository_indexes = RwLock::new(HashMap::new());
+        let no_profile_sessions = RwLock::new(HashMap::new());
+
+        tracing::debug!("Created new local repository: {:?}", temp_dir.path());
+
+        Ok(Self {
+            root: temp_dir.path().to_path_buf(),
+            _temp_dir: Some(temp_dir),
+            repository_indexes,
+            no_profile_sessions,
+            profile_namespaces: RwLock::new(Vec::new()),
+            file_counts: RwLock::new(HashMap::new()),
+            repository_sizes: RwLock::new(HashMap::new()),
+            root_files: Mutex::new(()),
+            options,
+        })
+    }
+
+    /// Keep the repositories in a directory that outlives the process
+    ///
+    /// The repository indexes allocated by an earlier process are reloaded, so that new repositories do not reuse them.
+    pub fn with_root(root: impl AsRef<Path>, options: RepositoryOptions) -> eyre::Result<Self> {
+        std::fs::create_dir_all(&root)?;
+        let root = root
+            .as_ref()
+            .absolutize()
+            .wrap_err_with(|| format!("Failed to canonicalize {:?}", root.as_ref()))?
+            .into_owned();
+        if options.private_root {
+            restrict_to_owner(&root)?;
+        }
 
-        tracing::debug!("Created new local repository: {:?}", root.path());
+        let repository_indexes = RwLock::new(read_repository_indexes(&root)?);
+        let profile_namespaces = RwLock::new(read_profile_namespaces(&root)?);
+        let no_profile_sessions = RwLock::new(HashMap::new());
+
+        tracing::debug!("Opened local repository: {root:?}");
 
         Ok(Self {
             root,
+            _temp_dir: None,
             repository_indexes,
+            no_profile_sessions,
+            profile_namespaces,
+            file_counts: RwLock::new(HashMap::new()),
+            repository_sizes: RwLock::new(HashMap::new()),
+            root_files: Mutex::new(()),
+            options,
         })
     }
 
+    /// Archive every repository with its files and state, along with the indexes, as a `.tar.gz`
+    ///
+    /// For backing up the staging state. Unlike [Repository::finish], nothing is closed or removed.
+    pub async fn export_archive(&self) -> eyre::Result<Vec<u8>> {
+        // held so that no repository is started part way through
+        let _repository_indexes = self.repository_indexes.read().await;
+
+        let mut files = self.repository_files(&self.root).await?;
+        files.sort();
+
+        let mut archive = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+        for relative_path in files {
+            // left behind by an interrupted write of a root file
+            if relative_path
+                .extension()
+                .is_some_and(|extension| extension == "tmp")
+            {
+                continue;
+            }
+
+            let entry_path = self.root.join(&relative_path);
+            let contents = match tokio::fs::read(&entry_path).await {
+                Ok(contents) => contents,
+                // removed since the files were listed, such as by a dropped repository
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            };
+            let modified = tokio::fs::metadata(&entry_path)
+                .await?
+                .modified()?
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|modified| modified.as_secs())
+                .unwrap_or_default();
+
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            header.set_mtime(modified);
+            header.set_cksum();
+            archive.append_data(&mut header, &relative_path, contents.as_slice())?;
+        }
+
+        Ok(archive.into_inner()?.finish()?)
+    }
+
     async fn retrieve_new_index(
         &self,
         user_id: &str,
@@ -48,27 +187,133 @@ impl LocalRepository {
         profile_id: &str,
     ) -> eyre::Result<u32> {
         let repository_index_key = create_repository_index_key(user_id, ip_addr, profile_id);
-        let mut repository_indexes = self.repository_indexes.write().await;
-        let repository_index = repository_indexes
-            .entry(repository_index_key.to_string())
+        let repository_index = *self
+            .repository_indexes
+            .write()
+            .await
+            .entry(repository_index_key)
             .and_modify(|i| *i += 1)
             .or_insert(0);
 
-        Ok(repository_index.to_owned())
+        // persisted before the index is used, so that a crash cannot lead to it being allocated again
+        self.write_repository_indexes().await?;
+
+        Ok(repository_index)
+    }
+
+    /// Replace the persisted indexes, as a JSON object of the largest index for each key
+    ///
+    /// The keys hold the user and profile IDs that clients provide, so they are escaped as JSON.
+    async fn write_repository_indexes(&self) -> eyre::Result<()> {
+        let _root_files = self.root_files.lock().await;
+        // copied once the write is queued, so that an older copy never replaces a newer one
+        let repository_indexes = self
+            .repository_indexes
+            .read()
+            .await
+            .iter()
+            .map(|(repository_index_key, repository_index)| {
+                (repository_index_key.clone(), *repository_index)
+            })
+            .collect::<BTreeMap<_, _>>();
+
+        self.replace_root_file(
+            REPOSITORY_INDEXES_FILE,
+            &serde_json::to_string_pretty(&repository_indexes)?,
+        )
+        .await
+    }
+
+    /// Replace a file in the root, writing to a temporary file first so that it is never partially written
+    ///
+    /// Callers hold `root_files`, so that the temporary files are not written concurrently.
+    async fn replace_root_file(&self, file_name: &str, contents: &str) -> eyre::Result<()> {
+        let file_path = self.root.join(file_name);
+        let temporary_file_path = self.root.join(format!("{file_name}.tmp"));
+
+        let mut temporary_file = File::create(&temporary_file_path).await?;
+        temporary_file.write_all(contents.as_bytes()).await?;
+        temporary_file.sync_all().await?;
+        tokio::fs::rename(&temporary_file_path, &file_path).await?;
+
+        Ok(())
+    }
+
+    /// Replace the persisted profile namespaces, as a JSON array in the order their IDs were allocated
+    async fn write_profile_namespaces(&self) -> eyre::Result<()> {
+        let _root_files = self.root_files.lock().await;
+        // copied once the write is queued, so that an older copy never replaces a newer one
+        let profile_namespaces = self.profile_namespaces.read().await.clone();
+
+        self.replace_root_file(
+            PROFILE_IDS_FILE,
+            &serde_json::to_string_pretty(&profile_namespaces)?,
+        )
+        .await
     }
 
-    async fn retrieve_current_no_profile_index(
+    /// The index of the session's repository, allocating a new one the first time a session is seen
+    ///
+    /// Uploads without a session share a single default repository.
+    async fn retrieve_no_profile_index(
         &self,
         user_id: &str,
         ip_addr: &IpAddr,
+        session_id: Option<&str>,
     ) -> eyre::Result<u32> {
         let repository_index_key = create_repository_index_key(user_id, ip_addr, NO_PROFILE);
-        let mut repository_indexes = self.repository_indexes.write().await;
-        let repository_index = repository_indexes
-            .entry(repository_index_key.to_string())
-            .or_insert(0);
+        let session_key = format!("{repository_index_key}/{}", session_id.unwrap_or_default());
+        let mut no_profile_sessions = self.no_profile_sessions.write().await;
+        if let Some(session) = no_profile_sessions.get_mut(&session_key) {
+            session.last_used = Instant::now();
+            return Ok(session.repository_index);
+        }
+
+        if no_profile_sessions.len() >= MAX_NO_PROFILE_SESSIONS {
+            let least_recently_used = no_profile_sessions
+                .iter()
+                .min_by_key(|(_, session)| session.last_used)
+                .map(|(session_key, _)| session_key.clone());
+            if let Some(session_key) = least_recently_used {
+                tracing::warn!("Forgetting the least recently used upload session: {session_key}");
+                no_profile_sessions.remove(&session_key);
+            }
+        }
+
+        let repository_index = self
+            .retrieve_new_index(user_id, ip_addr, NO_PROFILE)
+            .await?;
+        no_profile_sessions.insert(
+            session_key,
+            NoProfileSession {
+                repository_index,
+                last_used: Instant::now(),
+            },
+        );
 
-        Ok(repository_index.to_owned())
+        Ok(repository_index)
+    }
+
+    /// Forget the sessions uploading to the repository, so that their next upload starts a new one
+    async fn forget_no_profile_sessions(&self, repository_key: &RepositoryKey) {
+        let profile_id = repository_key.get_profile_id();
+        if profile_id != NO_PROFILE {
+            return;
+        }
+
+        let repository_index_key = create_repository_index_key(
+            &repository_key.user_id,
+            &repository_key.ip_addr,
+            &profile_id,
+        );
+        let session_prefix = format!("{repository_index_key}/");
+        self.no_profile_sessions
+            .write()
+            .await
+            .retain(|session_key, session| {
+                !(session_key.starts_with(&session_prefix)
+                    && session.repository_index == repository_key.repository_index)
+            });
     }
 
     async fn validate_repository(&self, repository_key: &RepositoryKey) -> eyre::Result<()> {
@@ -94,38 +339,22 @@ impl LocalRepository {
         &self,
         repository_key: &RepositoryKey,
     ) -> eyre::Result<PathBuf> {
-        let repository_file_path = repository_key_to_file_path(repository_key);
-        let absolute_path = self
-            .root
-            .path()
-            .join(repository_file_path)
-            .join(REPOSITORY_FOLDER);
-        let absolute_path = absolute_path
-            .absolutize()
-            .wrap_err_with(|| format!("Failed to canonicalize {absolute_path:?}"))?;
-
-        if absolute_path.starts_with(self.root.path()) {
-            Ok(absolute_path.into_owned())
-        } else {
-            Err(eyre::eyre!("Invalid repository: {repository_key}"))
-        }
+        self.absolute_path_for_repository_file(repository_key, REPOSITORY_FOLDER)
     }
 
-    fn absolute_path_for_repository_state(
+    /// The path of one of the files kept next to the contents of the repository, such as [REPOSITORY_STATE_FILE]
+    fn absolute_path_for_repository_file(
         &self,
         repository_key: &RepositoryKey,
+        file_name: &str,
     ) -> eyre::Result<PathBuf> {
         let repository_file_path = repository_key_to_file_path(repository_key);
-        let absolute_path = self
-            .root
-            .path()
-            .join(repository_file_path)
-            .join(REPOSITORY_STATE_FILE);
+        let absolute_path = self.root.join(repository_file_path).join(file_name);
         let absolute_path = absolute_path
             .absolutize()
             .wrap_err_with(|| format!("Failed to canonicalize {absolute_path:?}"))?;
 
-        if absolute_path.starts_with(self.root.path()) {
+        if absolute_path.starts_with(&self.root) {
             Ok(absolute_path.into_owned())
         } else {
             Err(eyre::eyre!("Invalid repository: {repository_key}"))
@@ -153,12 +382,55 @@ impl LocalRepository {
         }
     }
 
+    /// Reject files that may not be added to a repository under the configured options
+    fn check_allowed_file(&self, file_path: &Path) -> eyre::Result<()> {
+        if !self.options.allows_file(file_path) {
+            eyre::bail!("File {file_path:?} does not have an allowed extension");
+        }
+        if let Some(namespaces) = &self.options.namespaces {
+            if !namespaces.authorizes_path(file_path) {
+                eyre::bail!("File {file_path:?} is not in an authorized namespace");
+            }
+        }
+        if !self.options.has_min_path_depth(file_path) {
+            tracing::warn!("Rejected an upload without Maven coordinates: {file_path:?}");
+            eyre::bail!(
+                "File {file_path:?} is not in a groupId/artifactId/version directory (expected at least {} path segments)",
+                self.options.min_path_depth
+            );
+        }
+
+        Ok(())
+    }
+
+    /// The absolute path of a file in the repository, if both exist
+    async fn existing_file_path(
+        &self,
+        repository_key: &RepositoryKey,
+        file_path: impl AsRef<Path>,
+    ) -> eyre::Result<Option<PathBuf>> {
+        if self.validate_repository(repository_key).await.is_err() {
+            return Ok(None);
+        }
+        let file_path = self.validated_path_in_repository(repository_key, file_path)?;
+
+        if tokio::fs::metadata(&file_path)
+            .await
+            .is_ok_and(|metadata| metadata.is_file())
+        {
+            Ok(Some(file_path))
+        } else {
+            Ok(None)
+        }
+    }
+
     async fn write_repository_state(
         &self,
         repository_key: &RepositoryKey,
         repository_state: RepositoryState,
     ) -> eyre::Result<()> {
-        let state_file_path = self.absolute_path_for_repository_state(repository_key)?;
+        let state_file_path =
+            self.absolute_path_for_repository_file(repository_key, REPOSITORY_STATE_FILE)?;
         let mut state_file = OpenOptions::new()
             .create(true)
             .write(true)
@@ -170,14 +442,93 @@ impl LocalRepository {
             .write_all(repository_state.to_string().as_bytes())
             .await?;
 
+        self.append_repository_activity(repository_key, &repository_state)
+            .await?;
+        self.write_last_activity(repository_key).await?;
+
+        Ok(())
+    }
+
+    /// Record now as the last activity, as a unix timestamp in nanoseconds
+    ///
+    /// Kept apart from the state, which does not change while files are uploaded.
+    async fn write_last_activity(&self, repository_key: &RepositoryKey) -> eyre::Result<()> {
+        let last_activity_path =
+            self.absolute_path_for_repository_file(repository_key, REPOSITORY_LAST_ACTIVITY_FILE)?;
+        let timestamp = OffsetDateTime::now_utc().unix_timestamp_nanos();
+        tokio::fs::write(last_activity_path, timestamp.to_string()).await?;
+
+        Ok(())
+    }
+
+    async fn read_last_activity(
+        &self,
+        repository_key: &RepositoryKey,
+    ) -> eyre::Result<Option<OffsetDateTime>> {
+        let last_activity_path =
+            self.absolute_path_for_repository_file(repository_key, REPOSITORY_LAST_ACTIVITY_FILE)?;
+        match tokio::fs::read_to_string(last_activity_path).await {
+            Ok(timestamp) => Ok(Some(OffsetDateTime::from_unix_timestamp_nanos(
+                timestamp.trim().parse()?,
+            )?)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Record the state being entered, one `<state> <unix timestamp in nanoseconds>` per line
+    async fn append_repository_activity(
+        &self,
+        repository_key: &RepositoryKey,
+        repository_state: &RepositoryState,
+    ) -> eyre::Result<()> {
+        let activity_file_path =
+            self.absolute_path_for_repository_file(repository_key, REPOSITORY_ACTIVITY_FILE)?;
+        let mut activity_file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(activity_file_path)
+            .await?;
+
+        let timestamp = OffsetDateTime::now_utc().unix_timestamp_nanos();
+        activity_file
+            .write_all(format!("{repository_state} {timestamp}\n").as_bytes())
+            .await?;
+
         Ok(())
     }
 
+    async fn read_repository_activity(
+        &self,
+        repository_key: &RepositoryKey,
+    ) -> eyre::Result<Vec<(RepositoryState, OffsetDateTime)>> {
+        let activity_file_path =
+            self.absolute_path_for_repository_file(repository_key, REPOSITORY_ACTIVITY_FILE)?;
+        let activity_string = match tokio::fs::read_to_string(activity_file_path).await {
+            Ok(activity_string) => activity_string,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut activity = Vec::new();
+        for line in activity_string.lines().filter(|line| !line.is_empty()) {
+            let (state, timestamp) = line
+                .split_once(' ')
+                .ok_or_else(|| eyre::eyre!("Malformed repository activity: {line:?}"))?;
+            let state: RepositoryState = state.try_into().map_err(|e: String| eyre::eyre!(e))?;
+            let timestamp = OffsetDateTime::from_unix_timestamp_nanos(timestamp.parse()?)?;
+            activity.push((state, timestamp));
+        }
+
+        Ok(activity)
+    }
+
     async fn read_repository_state(
         &self,
         repository_key: &RepositoryKey,
     ) -> eyre::Result<RepositoryState> {
-        let state_file_path = self.absolute_path_for_repository_state(repository_key)?;
+        let state_file_path =
+            self.absolute_path_for_repository_file(repository_key, REPOSITORY_STATE_FILE)?;
         let mut state_file = File::open(state_file_path).await?;
 
         let mut state_string = String::new();
@@ -189,6 +540,360 @@ impl LocalRepository {
             .map_err(|e: String| eyre::eyre!(e))?;
         Ok(state)
     }
+
+    /// List the files in the repository, relative to its root
+    async fn repository_files(&self, repository_root: &Path) -> eyre::Result<Vec<PathBuf>> {
+        let mut entries = WalkDir::new(repository_root).filter(|entry| async move {
+            if let Ok(file_type) = entry.file_type().await {
+                if !file_type.is_dir() {
+                    return Filtering::Continue;
+                }
+            } else {
+                tracing::error!("Encountered error reading file entry: {:?}", entry.path());
+            }
+            Filtering::Ignore
+        });
+
+        let mut files = Vec::new();
+        while let Some(entry) = entries.try_next().await? {
+            let entry_path = entry.path();
+            let relative_path = entry_path.strip_prefix(repository_root)?;
+            files.push(relative_path.to_path_buf());
+        }
+
+        Ok(files)
+    }
+
+    /// Check that the POMs are well-formed and declare their coordinates
+    async fn check_poms(&self, repository_root: &Path, files: &[PathBuf]) -> eyre::Result<()> {
+        if !self.options.validate_poms {
+            return Ok(());
+        }
+
+        let mut poms = Vec::new();
+        for pom_path in files.iter().filter(|file| is_pom(file)) {
+            let contents = tokio::fs::read(repository_root.join(pom_path)).await?;
+            poms.push((pom_path.to_path_buf(), contents));
+        }
+
+        let problems = check_poms(&poms);
+        if !problems.is_empty() {
+            eyre::bail!("POM check failed: {}", problems.join(", "));
+        }
+
+        tracing::debug!("POM check passed");
+        Ok(())
+    }
+
+    /// Count a new file towards the limit of the repository, failing if it is already full
+    async fn reserve_file(
+        &self,
+        repository_key: &RepositoryKey,
+        max_files: usize,
+    ) -> eyre::Result<()> {
+        let mut file_counts = self.file_counts.write().await;
+        let file_count = match file_counts.entry(repository_key.to_string()) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let repository_root = self.absolute_path_for_repository(repository_key)?;
+                entry.insert(self.repository_files(&repository_root).await?.len())
+            }
+        };
+
+        if *file_count >= max_files {
+            return Err(FileLimitExceeded {
+                repository_id: repository_key.get_repository_id(),
+                max_files,
+            }
+            .into());
+        }
+        *file_count += 1;
+
+        Ok(())
+    }
+
+    /// Give back the place of a new file that failed to be written
+    async fn release_file(&self, repository_key: &RepositoryKey) {
+        if let Some(file_count) = self
+            .file_counts
+            .write()
+            .await
+            .get_mut(&repository_key.to_string())
+        {
+            *file_count = file_count.saturating_sub(1);
+        }
+    }
+
+    /// Count bytes about to be written towards the quota of the repository, failing if they do not fit
+    async fn reserve_bytes(
+        &self,
+        repository_key: &RepositoryKey,
+        bytes: u64,
+        max_size: u64,
+    ) -> eyre::Result<()> {
+        let mut repository_sizes = self.repository_sizes.write().await;
+        let repository_size = match repository_sizes.entry(repository_key.to_string()) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let repository_root = self.absolute_path_for_repository(repository_key)?;
+                entry.insert(self.files_size(&repository_root).await?)
+            }
+        };
+
+        if repository_size.saturating_add(bytes) > max_size {
+            return Err(QuotaExceeded {
+                repository_id: repository_key.get_repository_id(),
+                max_size,
+            }
+            .into());
+        }
+        *repository_size += bytes;
+
+        Ok(())
+    }
+
+    /// Give back bytes that are no longer in the repository, as a file failed to be written or was replaced
+    async fn release_bytes(&self, repository_key: &RepositoryKey, bytes: u64) {
+        if let Some(repository_size) = self
+            .repository_sizes
+            .write()
+            .await
+            .get_mut(&repository_key.to_string())
+        {
+            *repository_size = repository_size.saturating_sub(bytes);
+        }
+    }
+
+    /// Have the files of the repository counted and measured again, after they have changed other than by
+    /// [Repository::add_file]
+    async fn forget_file_totals(&self, repository_key: &RepositoryKey) {
+        let repository_key = repository_key.to_string();
+        self.file_counts.write().await.remove(&repository_key);
+        self.repository_sizes.write().await.remove(&repository_key);
+    }
+
+    /// The total size of the files under the repository root
+    async fn files_size(&self, repository_root: &Path) -> eyre::Result<u64> {
+        let mut size = 0;
+        for relative_path in self.repository_files(repository_root).await? {
+            size += tokio::fs::metadata(repository_root.join(relative_path))
+                .await?
+                .len();
+        }
+
+        Ok(size)
+    }
+
+    /// Check the signatures of the files, as configured, before they are bundled
+    ///
+    /// All of the problems are collected so that they can be fixed with a single re-upload.
+    async fn check_signatures(
+        &self,
+        repository_root: &Path,
+        files: &[PathBuf],
+    ) -> eyre::Result<()> {
+        let verifier = self.options.signature_verifier.as_ref();
+        if !self.options.require_signatures && verifier.is_none() {
+            return Ok(());
+        }
+
+        let mut problems = Vec::new();
+        for file in files.iter().filter(|file| requires_signature(file)) {
+            let signature_file = signature_path(file);
+            if !files.contains(&signature_file) {
+                if self.options.require_signatures {
+                    problems.push(format!("Missing signature: {}", signature_file.display()));
+                }
+                continue;
+            }
+
+            if let Some(verifier) = verifier {
+                let contents = tokio::fs::read(repository_root.join(file)).await?;
+                let signature = tokio::fs::read(repository_root.join(&signature_file)).await?;
+                if let Err(e) = verifier.verify(&contents, &signature) {
+                    problems.push(format!(
+                        "Invalid signature: {} ({e})",
+                        signature_file.display()
+                    ));
+                }
+            }
+        }
+
+        if !problems.is_empty() {
+            eyre::bail!("Signature check failed: {}", problems.join(", "));
+        }
+
+        tracing::debug!("Signature check passed");
+        Ok(())
+    }
+
+    /// Check that the checksum sidecars describe the files that were uploaded
+    ///
+    /// A client that re-uploads an artifact without its sidecars would otherwise have a stale
+    /// checksum bundled, which Central rejects.
+    async fn check_checksums(&self, repository_root: &Path, files: &[PathBuf]) -> eyre::Result<()> {
+        let policy = self.options.checksum_mismatch;
+        if policy == ChecksumMismatchPolicy::Ignore {
+            return Ok(());
+        }
+
+        let mut problems = Vec::new();
+        for sidecar in files {
+            let Some((target, algorithm)) = checksum_target(sidecar) else {
+                continue;
+            };
+            if !files.contains(&target) {
+                continue;
+            }
+
+            let contents = tokio::fs::read(repository_root.join(&target)).await?;
+            let expected_digest = algorithm.digest(&contents);
+            let sidecar_path = repository_root.join(sidecar);
+            let sidecar_contents = tokio::fs::read_to_string(&sidecar_path).await?;
+            if sidecar_matches(&sidecar_contents, &expected_digest) {
+                continue;
+            }
+
+            match policy {
+                ChecksumMismatchPolicy::Regenerate => {
+                    tracing::debug!("Regenerating stale checksum: {sidecar:?}");
+                    tokio::fs::write(&sidecar_path, expected_digest).await?;
+                }
+                _ => problems.push(format!("Checksum mismatch: {}", sidecar.display())),
+            }
+        }
+
+        if !problems.is_empty() {
+            eyre::bail!("Checksum check failed: {}", problems.join(", "));
+        }
+
+        tracing::debug!("Checksum check passed");
+        Ok(())
+    }
+
+    /// Write the `.md5` and `.sha1` sidecars that are missing for files other than checksums and signatures
+    ///
+    /// Returns the files in the repository, including those that were written.
+    async fn write_missing_checksums(
+        &self,
+        repository_root: &Path,
+        mut files: Vec<PathBuf>,
+    ) -> eyre::Result<Vec<PathBuf>> {
+        let targets = files
+            .iter()
+            .filter(|file| {
+                checksum_target(file).is_none()
+                    && file
+                        .extension()
+                        .is_none_or(|extension| extension != SIGNATURE_EXTENSION)
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+
+        for target in targets {
+            for algorithm in [ChecksumAlgorithm::Md5, ChecksumAlgorithm::Sha1] {
+                let sidecar = algorithm.sidecar_path(&target);
+                if files.contains(&sidecar) {
+                    continue;
+                }
+
+                let file = File::open(repository_root.join(&target)).await?;
+                let digest = algorithm.digest_reader(file).await?;
+                tracing::trace!("Writing missing checksum: {sidecar:?}");
+                tokio::fs::write(repository_root.join(&sidecar), digest).await?;
+                files.push(sidecar);
+            }
+        }
+
+        Ok(files)
+    }
+
+    /// Write a `maven-metadata.xml` and its checksums into each artifact directory
+    ///
+    /// Returns the files in the repository, including those that were written.
+    async fn write_maven_metadata(
+        &self,
+        repository_root: &Path,
+        mut files: Vec<PathBuf>,
+    ) -> eyre::Result<Vec<PathBuf>> {
+        let now = time::OffsetDateTime::now_utc();
+        let last_updated = format!(
+            "{:04}{:02}{:02}{:02}{:02}{:02}",
+            now.year(),
+            u8::from(now.month()),
+            now.day(),
+            now.hour(),
+            now.minute(),
+            now.second()
+        );
+
+        for (metadata_path, metadata) in generate_maven_metadata(&files, &last_updated) {
+            let mut written = vec![(metadata_path.clone(), metadata.clone())];
+            for algorithm in [ChecksumAlgorithm::Md5, ChecksumAlgorithm::Sha1] {
+                written.push((
+                    algorithm.sidecar_path(&metadata_path),
+                    algorithm.digest(metadata.as_bytes()),
+                ));
+            }
+
+            for (relative_path, contents) in written {
+                tracing::trace!("Writing generated metadata: {relative_path:?}");
+                tokio::fs::write(repository_root.join(&relative_path), contents).await?;
+                if !files.contains(&relative_path) {
+                    files.push(relative_path);
+                }
+            }
+        }
+
+        Ok(files)
+    }
+
+    async fn write_repository_errors(
+        &self,
+        repository_key: &RepositoryKey,
+        errors: &[String],
+    ) -> eyre::Result<()> {
+        let errors_file_path =
+            self.absolute_path_for_repository_file(repository_key, REPOSITORY_ERRORS_FILE)?;
+        let mut errors_file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(errors_file_path)
+            .await?;
+
+        // one error per line, so multi-line messages are flattened
+        for error in errors {
+            let error = error.lines().map(str::trim).collect::<Vec<_>>().join(" ");
+            errors_file.write_all(error.as_bytes()).await?;
+            errors_file.write_all(b"\n").await?;
+        }
+
+        Ok(())
+    }
+
+    async fn read_repository_errors(
+        &self,
+        repository_key: &RepositoryKey,
+    ) -> eyre::Result<Vec<String>> {
+        let errors_file_path =
+            self.absolute_path_for_repository_file(repository_key, REPOSITORY_ERRORS_FILE)?;
+        let mut errors_file = match File::open(errors_file_path).await {
+            Ok(errors_file) => errors_file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut errors_string = String::new();
+        errors_file.read_to_string(&mut errors_string).await?;
+
+        let errors = errors_string
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(String::from)
+            .collect();
+        Ok(errors)
+    }
 }
 
 #[async_trait]
@@ -200,21 +905,27 @@ impl Repository for LocalRepository {
         ip_addr: &IpAddr,
         profile_id: &str,
     ) -> eyre::Result<RepositoryKey> {
-        let repository_index = self
-            .retrieve_new_index(user_id, ip_addr, profile_id)
-            .await?;
-        let repository_key = RepositoryKey::new(
-            user_id,
-            ip_addr,
-            Some(profile_id.to_string()),
-            repository_index,
-        );
+        let repository_key = loop {
+            let repository_index = self
+                .retrieve_new_index(user_id, ip_addr, profile_id)
+                .await?;
+            let repository_key = RepositoryKey::new(
+                user_id,
+                ip_addr,
+                Some(profile_id.to_string()),
+                repository_index,
+            );
+
+            let path = self.absolute_path_for_repository(&repository_key)?;
+            if claim_directory(&path).await? {
+                tracing::trace!("Created repository folders: {path:?}");
+                break repository_key;
+            }
+            // left behind by a repository whose index was lost, so it is never shared
+            tracing::warn!("Skipping the existing repository folder: {path:?}");
+        };
         tracing::debug!("Starting repository: {}", repository_key);
 
-        let path = self.absolute_path_for_repository(&repository_key)?;
-        tokio::fs::create_dir_all(&path).await?;
-        tracing::trace!("Created repository folders: {path:?}");
-
         self.write_repository_state(&repository_key, RepositoryState::Open)
             .await?;
         tracing::debug!("Opened the repository");
@@ -227,9 +938,10 @@ impl Repository for LocalRepository {
         &self,
         user_id: &str,
         ip_addr: &IpAddr,
+        session_id: Option<&str>,
     ) -> eyre::Result<RepositoryKey> {
         let repository_index = self
-            .retrieve_current_no_profile_index(user_id, ip_addr)
+            .retrieve_no_profile_index(user_id, ip_addr, session_id)
             .await?;
         let repository_key = RepositoryKey::new(user_id, ip_addr, None, repository_index);
         tracing::debug!("Opening repository: {}", repository_key);
@@ -245,82 +957,285 @@ impl Repository for LocalRepository {
         Ok(repository_key)
     }
 
-    /// Correctness: Assumes a well-behaved plugin that is not attempting to upload the same file concurrently
-    #[instrument(skip(file_contents))]
-    async fn add_file<P, S>(
+    #[instrument]
+    async fn find_no_profile_repository(
         &self,
-        repository_key: &RepositoryKey,
-        file_path: P,
-        file_contents: S,
-    ) -> eyre::Result<()>
-    where
-        P: AsRef<Path> + Debug + Send,
-        S: Stream<Item = eyre::Result<Bytes>> + Send,
-    {
-        tracing::debug!("Adding file to repository: {repository_key}");
-        self.validate_repository(repository_key).await?;
-        let file_path = self.validated_path_in_repository(repository_key, file_path)?;
-        let parent = file_path
-            .parent()
+        user_id: &str,
+        ip_addr: &IpAddr,
+        session_id: Option<&str>,
+    ) -> Option<RepositoryKey> {
+        let repository_index_key = create_repository_index_key(user_id, ip_addr, NO_PROFILE);
+        let session_key = format!("{repository_index_key}/{}", session_id.unwrap_or_default());
+        let repository_index = self
+            .no_profile_sessions
+            .read()
+            .await
+            .get(&session_key)?
+            .repository_index;
+
+        let repository_key = RepositoryKey::new(user_id, ip_addr, None, repository_index);
+        self.exists(&repository_key).await.then_some(repository_key)
+    }
+
+    /// Correctness: Assumes a well-behaved plugin that is not attempting to upload the same file concurrently
+    #[instrument(skip(file_contents))]
+    async fn add_file(
+        &self,
+        repository_key: &RepositoryKey,
+        file_path: &Path,
+        file_contents: BoxStream<'_, eyre::Result<Bytes>>,
+    ) -> eyre::Result<()> {
+        tracing::debug!("Adding file to repository: {repository_key}");
+        self.validate_repository(repository_key).await?;
+        if !self.exists(repository_key).await {
+            return Err(RepositoryNotFound {
+                repository_id: repository_key.get_repository_id(),
+            }
+            .into());
+        }
+        self.check_allowed_file(file_path)?;
+        let file_path = self.validated_path_in_repository(repository_key, file_path)?;
+        // a long upload is activity from its start, not only once it completes
+        self.write_last_activity(repository_key).await?;
+        let parent = file_path
+            .parent()
             .ok_or_else(|| eyre::eyre!("No parent folder found for {file_path:?}"))?;
 
         tokio::fs::create_dir_all(parent).await?;
         tracing::trace!("Created repository folders: {file_path:?}");
 
-        // Adapted from the Tokio examples
-        async {
-            let body_with_io_error =
-                file_contents.map_err(|err| io::Error::new(io::ErrorKind::Other, err));
-            let body_reader = StreamReader::new(body_with_io_error);
-            futures::pin_mut!(body_reader);
+        // a file being replaced no longer counts towards the quota once it is truncated
+        if self.options.max_repository_size.is_some() {
+            if let Ok(metadata) = tokio::fs::metadata(&file_path).await {
+                self.release_bytes(repository_key, metadata.len()).await;
+            }
+        }
+
+        let reserved_file = match self.options.max_files_per_repository {
+            Some(max_files) if !tokio::fs::try_exists(&file_path).await? => {
+                self.reserve_file(repository_key, max_files).await?;
+                true
+            }
+            _ => false,
+        };
 
+        // each chunk is reserved before it is written, so that concurrent uploads cannot overrun the quota together
+        let mut reserved_bytes = 0;
+        let written = async {
+            let mut file_contents = file_contents;
             let mut file = BufWriter::new(File::create(&file_path).await?);
+            while let Some(chunk) = file_contents.try_next().await? {
+                if let Some(max_size) = self.options.max_repository_size {
+                    let chunk_size = chunk.len() as u64;
+                    self.reserve_bytes(repository_key, chunk_size, max_size)
+                        .await?;
+                    reserved_bytes += chunk_size;
+                }
+                file.write_all(&chunk).await?;
+            }
+            file.flush().await?;
 
-            tokio::io::copy(&mut body_reader, &mut file).await?;
+            Ok::<_, eyre::Error>(())
+        }
+        .await;
 
-            Ok::<_, io::Error>(())
+        if let Err(e) = written {
+            let _ = tokio::fs::remove_file(&file_path).await;
+            if reserved_file {
+                self.release_file(repository_key).await;
+            }
+            self.release_bytes(repository_key, reserved_bytes).await;
+            return Err(e);
         }
-        .await?;
 
         tracing::trace!("File written to: {file_path:?}");
+        self.write_last_activity(repository_key).await?;
+        Ok(())
+    }
+
+    #[instrument]
+    async fn move_file(
+        &self,
+        repository_key: &RepositoryKey,
+        from: &Path,
+        to: &Path,
+    ) -> eyre::Result<()> {
+        tracing::debug!("Moving file in repository: {repository_key}");
+        self.check_allowed_file(to)?;
+        let Some(from_path) = self.existing_file_path(repository_key, from).await? else {
+            eyre::bail!("File {from:?} is not in repository {repository_key}");
+        };
+        let to_path = self.validated_path_in_repository(repository_key, to)?;
+        let parent = to_path
+            .parent()
+            .ok_or_else(|| eyre::eyre!("No parent folder found for {to_path:?}"))?;
+
+        tokio::fs::create_dir_all(parent).await?;
+        let replaces_file = tokio::fs::try_exists(&to_path).await?;
+        tokio::fs::rename(&from_path, &to_path).await?;
+        if replaces_file {
+            self.forget_file_totals(repository_key).await;
+        }
+
+        tracing::trace!("File moved to: {to_path:?}");
+        self.write_last_activity(repository_key).await?;
         Ok(())
     }
 
+    #[instrument]
+    async fn get_file(
+        &self,
+        repository_key: &RepositoryKey,
+        file_path: &Path,
+    ) -> eyre::Result<Option<Vec<u8>>> {
+        tracing::debug!("Getting file from repository: {repository_key}");
+        let Some(file_path) = self.existing_file_path(repository_key, file_path).await? else {
+            return Ok(None);
+        };
+
+        match tokio::fs::read(&file_path).await {
+            Ok(contents) => Ok(Some(contents)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    #[instrument]
+    async fn open_file(
+        &self,
+        repository_key: &RepositoryKey,
+        file_path: &Path,
+    ) -> eyre::Result<Option<StoredFile>> {
+        tracing::debug!("Opening file from repository: {repository_key}");
+        let Some(file_path) = self.existing_file_path(repository_key, file_path).await? else {
+            return Ok(None);
+        };
+
+        let file = match File::open(&file_path).await {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        // taken from the opened file, so that it matches what is read even if the file is replaced
+        let size = file.metadata().await?.len();
+
+        Ok(Some(StoredFile {
+            reader: Box::pin(file),
+            size,
+        }))
+    }
+
+    #[instrument]
+    async fn file_metadata(
+        &self,
+        repository_key: &RepositoryKey,
+        file_path: &Path,
+    ) -> eyre::Result<Option<FileMetadata>> {
+        tracing::debug!("Getting file metadata from repository: {repository_key}");
+        let Some(file_path) = self.existing_file_path(repository_key, file_path).await? else {
+            return Ok(None);
+        };
+
+        let file = match File::open(&file_path).await {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let metadata = file.metadata().await?;
+        let sha1 = ChecksumAlgorithm::Sha1.digest_reader(file).await?;
+
+        Ok(Some(FileMetadata {
+            size: metadata.len(),
+            modified: metadata.modified()?.into(),
+            sha1,
+        }))
+    }
+
+    #[instrument]
+    async fn get_file_size(
+        &self,
+        repository_key: &RepositoryKey,
+        file_path: &Path,
+    ) -> eyre::Result<Option<u64>> {
+        tracing::debug!("Getting file size from repository: {repository_key}");
+        let Some(file_path) = self.existing_file_path(repository_key, file_path).await? else {
+            return Ok(None);
+        };
+
+        match tokio::fs::metadata(&file_path).await {
+            Ok(metadata) => Ok(Some(metadata.len())),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    #[instrument]
+    async fn repository_size(&self, repository_key: &RepositoryKey) -> eyre::Result<u64> {
+        tracing::debug!("Getting the size of repository");
+        self.validate_repository(repository_key).await?;
+        let path = self.absolute_path_for_repository(repository_key)?;
+
+        self.files_size(&path).await
+    }
+
     #[instrument]
     async fn finish(&self, repository_key: &RepositoryKey) -> eyre::Result<ZipFile> {
         tracing::debug!("Finishing repository");
         self.validate_repository(repository_key).await?;
-        let path = self.absolute_path_for_repository(&repository_key)?;
-        // create the zip file from all of the existing files
-        let mut zip_file = ZipFile::in_memory();
 
-        let mut entries = WalkDir::new(&path).filter(|entry| async move {
-            if let Ok(file_type) = entry.file_type().await {
-                if !file_type.is_dir() {
-                    return Filtering::Continue;
-                }
-            } else {
-                tracing::error!("Encountered error reading file entry: {:?}", entry.path());
+        let state = self.read_repository_state(repository_key).await?;
+        if !matches!(state, RepositoryState::Open) {
+            return Err(RepositoryNotOpen {
+                repository_id: repository_key.get_repository_id(),
+                state: state.to_string(),
             }
-            Filtering::Ignore
-        });
+            .into());
+        }
+        let path = self.absolute_path_for_repository(repository_key)?;
+        let files = self.repository_files(&path).await?;
+
+        if files.is_empty() && !self.options.allow_empty_bundles {
+            eyre::bail!(
+                "Repository {} has no files to publish, upload some before closing it",
+                repository_key.get_repository_id()
+            );
+        }
 
-        while let Some(entry) = entries.try_next().await? {
-            let entry_path = entry.path();
+        self.check_signatures(&path, &files).await?;
+        self.check_checksums(&path, &files).await?;
+        self.check_poms(&path, &files).await?;
+
+        let files = if self.options.generate_checksums {
+            self.write_missing_checksums(&path, files).await?
+        } else {
+            files
+        };
+        let mut files = if self.options.generate_maven_metadata {
+            self.write_maven_metadata(&path, files).await?
+        } else {
+            files
+        };
+
+        // create the zip file from all of the existing files
+        let mut zip_file = if self.options.deterministic_bundles {
+            files.sort();
+            ZipFile::deterministic()
+        } else {
+            ZipFile::in_memory()
+        };
+
+        for relative_path in files {
+            let entry_path = path.join(&relative_path);
             tracing::trace!("Adding file to .zip: {entry_path:?}");
-            let relative_path = entry_path.strip_prefix(&path)?;
             let file_to_add = File::open(&entry_path).await?;
             zip_file.add_file(relative_path, file_to_add).await?;
         }
 
         tracing::debug!("Created .zip file for repository");
 
-        // delete the repository folder
-        tokio::fs::remove_dir_all(&path).await?;
-        tracing::debug!("Cleaned up the repository: {path:?}");
-
         self.write_repository_state(repository_key, RepositoryState::Closed)
             .await?;
+        self.forget_no_profile_sessions(repository_key).await;
         tracing::debug!("Closed the repository");
 
         Ok(zip_file)
@@ -338,6 +1253,96 @@ impl Repository for LocalRepository {
         Ok(())
     }
 
+    #[instrument]
+    async fn drop_repository(&self, repository_key: &RepositoryKey) -> eyre::Result<()> {
+        tracing::debug!("Dropping repository");
+        self.validate_repository(repository_key).await?;
+
+        let path = self.root.join(repository_key_to_file_path(repository_key));
+        match tokio::fs::remove_dir_all(&path).await {
+            Ok(()) => tracing::debug!("Dropped the repository: {path:?}"),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+        self.forget_file_totals(repository_key).await;
+        self.forget_no_profile_sessions(repository_key).await;
+
+        Ok(())
+    }
+
+    #[instrument]
+    async fn remove_files(&self, repository_key: &RepositoryKey) -> eyre::Result<()> {
+        tracing::debug!("Removing the files of repository");
+        self.validate_repository(repository_key).await?;
+
+        let path = self.absolute_path_for_repository(repository_key)?;
+        match tokio::fs::remove_dir_all(&path).await {
+            Ok(()) => tracing::debug!("Cleaned up the repository: {path:?}"),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+        self.forget_file_totals(repository_key).await;
+
+        Ok(())
+    }
+
+    #[instrument]
+    async fn abort(&self, repository_key: &RepositoryKey) -> eyre::Result<()> {
+        tracing::debug!("Aborting repository");
+        self.validate_repository(repository_key).await?;
+
+        let state = self.read_repository_state(repository_key).await?;
+        if !matches!(state, RepositoryState::Open | RepositoryState::Unknown) {
+            eyre::bail!(
+                "Repository {repository_key} is {state}, only open or unknown repositories can be dropped"
+            );
+        }
+
+        let path = self.absolute_path_for_repository(repository_key)?;
+        match tokio::fs::remove_dir_all(&path).await {
+            Ok(()) => tracing::debug!("Removed the files of the aborted repository: {path:?}"),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+        self.forget_file_totals(repository_key).await;
+
+        self.write_repository_state(repository_key, RepositoryState::Dropped)
+            .await?;
+        self.forget_no_profile_sessions(repository_key).await;
+        tracing::debug!("Dropped the repository");
+
+        Ok(())
+    }
+
+    #[instrument]
+    async fn reopen(&self, repository_key: &RepositoryKey) -> eyre::Result<()> {
+        tracing::debug!("Reopening repository");
+        self.validate_repository(repository_key).await?;
+
+        // the files are gone if they were removed after an earlier publish
+        let path = self.absolute_path_for_repository(repository_key)?;
+        tokio::fs::create_dir_all(&path).await?;
+        self.forget_file_totals(repository_key).await;
+
+        self.write_repository_state(repository_key, RepositoryState::Open)
+            .await?;
+        tracing::debug!("Reopened the repository");
+
+        Ok(())
+    }
+
+    #[instrument]
+    async fn mark_unknown(&self, repository_key: &RepositoryKey) -> eyre::Result<()> {
+        tracing::debug!("Marking the state of repository as unknown");
+        self.validate_repository(repository_key).await?;
+
+        self.write_repository_state(repository_key, RepositoryState::Unknown)
+            .await?;
+        tracing::debug!("Marked the repository as unknown");
+
+        Ok(())
+    }
+
     #[instrument]
     async fn get_state(&self, repository_key: &RepositoryKey) -> eyre::Result<RepositoryState> {
         tracing::debug!("Getting the state of repository");
@@ -350,104 +1355,585 @@ impl Repository for LocalRepository {
 
         Ok(state)
     }
-}
 
-impl std::fmt::Debug for LocalRepository {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("LocalRepository")
-            .field("root", &self.root.path())
-            .field("repository_versions", &"opaque")
-            .finish()
+    #[instrument]
+    async fn get_activity(
+        &self,
+        repository_key: &RepositoryKey,
+    ) -> eyre::Result<Vec<(RepositoryState, OffsetDateTime)>> {
+        tracing::debug!("Getting the activity of repository");
+        self.validate_repository(repository_key).await?;
+
+        let activity = self.read_repository_activity(repository_key).await?;
+
+        Ok(activity)
     }
-}
 
-/// Convenience function to ensure consistent construction of file paths
-fn repository_key_to_file_path(repository_key: &RepositoryKey) -> PathBuf {
-    PathBuf::from(format!(
-        "{}/{}/{}-{}/",
-        repository_key.user_id,
-        repository_key.ip_addr,
-        repository_key.get_profile_id(),
-        repository_key.repository_index
-    ))
-}
+    #[instrument]
+    async fn touch(&self, repository_key: &RepositoryKey) -> eyre::Result<()> {
+        tracing::trace!("Touching repository");
+        self.validate_repository(repository_key).await?;
 
-/// Convenience function to ensure consistent construction of repository index keys
-fn create_repository_index_key(user_id: &str, ip_addr: &IpAddr, profile_id: &str) -> String {
-    format!("{user_id}/{ip_addr}/{profile_id}")
-}
+        self.write_last_activity(repository_key).await
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::{
-        io::{Cursor, Read},
-        net::Ipv4Addr,
-    };
-    use zip::read::ZipArchive;
+    #[instrument]
+    async fn last_activity(
+        &self,
+        repository_key: &RepositoryKey,
+    ) -> eyre::Result<Option<OffsetDateTime>> {
+        self.validate_repository(repository_key).await?;
 
-    #[tokio::test]
-    async fn simple_end_to_end_test() -> eyre::Result<()> {
-        let test_file_path = "com/example/file.txt";
-        let test_file_contents = "test_file_content";
-        let local_repository = LocalRepository::new()?;
+        // repositories from before the last activity was kept only have their state changes
+        match self.read_last_activity(repository_key).await? {
+            Some(last_activity) => Ok(Some(last_activity)),
+            None => Ok(self
+                .read_repository_activity(repository_key)
+                .await?
+                .last()
+                .map(|(_, timestamp)| *timestamp)),
+        }
+    }
 
-        // start the repository
-        let repository_key = local_repository
-            .start(
-                "test_user",
-                &IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
-                "test_profile",
+    #[instrument]
+    async fn remove_idle_repositories(
+        &self,
+        max_idle: Duration,
+    ) -> eyre::Result<Vec<RepositoryKey>> {
+        let idle_since = OffsetDateTime::now_utc() - max_idle;
+        let repository_indexes = self.repository_indexes.read().await.clone();
+
+        let mut removed = Vec::new();
+        for (repository_index_key, max_index) in repository_indexes {
+            let Some((user_id, ip_addr, profile_id)) =
+                parse_repository_index_key(&repository_index_key)
+            else {
+                tracing::warn!("Skipping malformed repository index key: {repository_index_key}");
+                continue;
+            };
+
+            for repository_index in 0..=max_index {
+                let repository_key =
+                    RepositoryKey::new(user_id, &ip_addr, profile_id.clone(), repository_index);
+                if !self.exists(&repository_key).await {
+                    continue;
+                }
+                // a closed repository may still be publishing, and its files are removed once it has
+                let state = self.read_repository_state(&repository_key).await?;
+                if !matches!(state, RepositoryState::Open) {
+                    continue;
+                }
+                let is_idle = self
+                    .last_activity(&repository_key)
+                    .await?
+                    .is_some_and(|last_activity| last_activity < idle_since);
+                if !is_idle {
+                    continue;
+                }
+
+                self.drop_repository(&repository_key).await?;
+                tracing::info!("Removed idle repository {repository_key}");
+                removed.push(repository_key);
+            }
+        }
+
+        Ok(removed)
+    }
+
+    #[instrument]
+    async fn exists(&self, repository_key: &RepositoryKey) -> bool {
+        if self.validate_repository(repository_key).await.is_err() {
+            return false;
+        }
+
+        // the index of a repository removed while idle remains allocated
+        let has_state =
+            match self.absolute_path_for_repository_file(repository_key, REPOSITORY_STATE_FILE) {
+                Ok(state_file_path) => tokio::fs::try_exists(state_file_path)
+                    .await
+                    .unwrap_or(false),
+                Err(_) => false,
+            };
+
+        has_state
+            && !matches!(
+                self.read_repository_state(repository_key).await,
+                Ok(RepositoryState::Dropped)
             )
-            .await?;
+    }
 
-        let file_contents = futures::stream::once(async { Ok(Bytes::from(test_file_contents)) });
+    #[instrument]
+    async fn list_repositories(
+        &self,
+        user_id: &str,
+        ip_addr: &IpAddr,
+    ) -> eyre::Result<Vec<(RepositoryKey, RepositoryState)>> {
+        tracing::debug!("Listing repositories");
+        let user_prefix = create_repository_index_key(user_id, ip_addr, "");
+        let mut profiles = {
+            let repository_indexes = self.repository_indexes.read().await;
+            repository_indexes
+                .iter()
+                .filter_map(|(repository_index_key, max_index)| {
+                    let profile_id = repository_index_key.strip_prefix(&user_prefix)?;
+                    Some((profile_id.to_string(), *max_index))
+                })
+                .collect::<Vec<_>>()
+        };
+        profiles.sort();
+
+        let mut repositories = Vec::new();
+        for (profile_id, max_index) in profiles {
+            let profile_id = Some(profile_id).filter(|profile_id| profile_id != NO_PROFILE);
+            for repository_index in 0..=max_index {
+                let repository_key =
+                    RepositoryKey::new(user_id, ip_addr, profile_id.clone(), repository_index);
+
+                // an index is allocated before the repository's state is first written
+                let state_file_path =
+                    self.absolute_path_for_repository_file(&repository_key, REPOSITORY_STATE_FILE)?;
+                if !tokio::fs::try_exists(state_file_path).await? {
+                    continue;
+                }
 
-        // add a file
-        local_repository
-            .add_file(&repository_key, test_file_path, file_contents)
-            .await?;
+                let state = self.read_repository_state(&repository_key).await?;
+                repositories.push((repository_key, state));
+            }
+        }
 
-        // finish the repository
-        let zip_file = local_repository.finish(&repository_key).await?;
-        let zip_contents = zip_file.as_buffer()?;
+        Ok(repositories)
+    }
 
-        // verifiy the zip
-        let mut zip_reader = ZipArchive::new(Cursor::new(zip_contents))?;
-        assert_eq!(
-            zip_reader.file_names().collect::<Vec<&str>>(),
-            vec![test_file_path]
-        );
-        let mut actual_file = zip_reader.by_name(test_file_path)?;
-        let mut actual_content = String::new();
-        actual_file.read_to_string(&mut actual_content)?;
+    #[instrument]
+    async fn numeric_profile_id(&self, namespace: &str) -> eyre::Result<u64> {
+        let profile_id = {
+            let mut profile_namespaces = self.profile_namespaces.write().await;
+            if let Some(position) = profile_namespaces
+                .iter()
+                .position(|known| known == namespace)
+            {
+                return Ok(position as u64 + 1);
+            }
 
-        assert_eq!(&actual_content, test_file_contents);
+            tracing::debug!("Allocating a numeric profile ID");
+            profile_namespaces.push(namespace.to_string());
+            profile_namespaces.len() as u64
+        };
+        self.write_profile_namespaces().await?;
 
-        Ok(())
+        Ok(profile_id)
     }
 
-    #[tokio::test]
-    async fn reject_directory_traversal() -> eyre::Result<()> {
-        let local_repository = LocalRepository::new()?;
+    #[instrument]
+    async fn namespace_for_profile_id(&self, profile_id: u64) -> eyre::Result<Option<String>> {
+        let profile_namespaces = self.profile_namespaces.read().await;
+        let namespace = usize::try_from(profile_id)
+            .ok()
+            .and_then(|profile_id| profile_id.checked_sub(1))
+            .and_then(|position| profile_namespaces.get(position));
+
+        Ok(namespace.cloned())
+    }
 
-        // start the repository
-        let repository_key = local_repository
-            .start(
-                "test_user",
-                &IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
-                "test_profile",
-            )
+    #[instrument(skip(errors))]
+    async fn record_errors(
+        &self,
+        repository_key: &RepositoryKey,
+        errors: Vec<String>,
+    ) -> eyre::Result<()> {
+        tracing::debug!("Recording {} error(s) for repository", errors.len());
+        self.validate_repository(repository_key).await?;
+
+        self.write_repository_errors(repository_key, &errors)
             .await?;
 
-        let file_contents = futures::stream::once(async { Ok(Bytes::from("test_file_content")) });
+        Ok(())
+    }
+
+    #[instrument]
+    async fn get_errors(&self, repository_key: &RepositoryKey) -> eyre::Result<Vec<String>> {
+        tracing::debug!("Getting the errors of repository");
+        self.validate_repository(repository_key).await?;
+
+        let errors = self.read_repository_errors(repository_key).await?;
+
+        Ok(errors)
+    }
+
+    #[instrument]
+    async fn record_deployment_id(
+        &self,
+        repository_key: &RepositoryKey,
+        deployment_id: &str,
+    ) -> eyre::Result<()> {
+        tracing::debug!("Recording the deployment of repository");
+        self.validate_repository(repository_key).await?;
+
+        let deployment_file_path =
+            self.absolute_path_for_repository_file(repository_key, REPOSITORY_DEPLOYMENT_FILE)?;
+        tokio::fs::write(deployment_file_path, deployment_id.trim()).await?;
+
+        let settled_file_path = self.absolute_path_for_repository_file(
+            repository_key,
+            REPOSITORY_DEPLOYMENT_SETTLED_FILE,
+        )?;
+        match tokio::fs::remove_file(settled_file_path).await {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        Ok(())
+    }
+
+    #[instrument]
+    async fn get_deployment_id(
+        &self,
+        repository_key: &RepositoryKey,
+    ) -> eyre::Result<Option<String>> {
+        tracing::debug!("Getting the deployment of repository");
+        self.validate_repository(repository_key).await?;
+
+        let deployment_file_path =
+            self.absolute_path_for_repository_file(repository_key, REPOSITORY_DEPLOYMENT_FILE)?;
+        match tokio::fs::read_to_string(deployment_file_path).await {
+            Ok(deployment_id) => Ok(Some(deployment_id)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    #[instrument]
+    async fn record_deployment_settled(&self, repository_key: &RepositoryKey) -> eyre::Result<()> {
+        tracing::debug!("Recording the deployment of repository as settled");
+        self.validate_repository(repository_key).await?;
+
+        let settled_file_path = self.absolute_path_for_repository_file(
+            repository_key,
+            REPOSITORY_DEPLOYMENT_SETTLED_FILE,
+        )?;
+        tokio::fs::write(settled_file_path, "").await?;
+
+        Ok(())
+    }
+
+    #[instrument]
+    async fn is_deployment_settled(&self, repository_key: &RepositoryKey) -> eyre::Result<bool> {
+        tracing::debug!("Checking whether the deployment of repository has settled");
+        self.validate_repository(repository_key).await?;
+
+        let settled_file_path = self.absolute_path_for_repository_file(
+            repository_key,
+            REPOSITORY_DEPLOYMENT_SETTLED_FILE,
+        )?;
+        Ok(tokio::fs::try_exists(settled_file_path).await?)
+    }
+}
+
+/// The repository that an upload session without a profile uploads to
+struct NoProfileSession {
+    repository_index: u32,
+    last_used: Instant,
+}
+
+impl std::fmt::Debug for LocalRepository {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LocalRepository")
+            .field("root", &self.root)
+            .field("repository_versions", &"opaque")
+            .field("options", &self.options)
+            .finish()
+    }
+}
+
+/// The largest index allocated for each repository index key, as persisted in the root
+fn read_repository_indexes(root: &Path) -> eyre::Result<HashMap<String, u32>> {
+    let indexes_string = match std::fs::read_to_string(root.join(REPOSITORY_INDEXES_FILE)) {
+        Ok(indexes_string) => indexes_string,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(HashMap::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    serde_json::from_str(&indexes_string).wrap_err("Malformed repository indexes")
+}
+
+/// The namespaces that have been given numeric profile IDs, as persisted in the root
+fn read_profile_namespaces(root: &Path) -> eyre::Result<Vec<String>> {
+    match std::fs::read_to_string(root.join(PROFILE_IDS_FILE)) {
+        Ok(profile_namespaces) => {
+            serde_json::from_str(&profile_namespaces).wrap_err("Malformed profile IDs")
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Allow only the owner of the directory into it
+#[cfg(unix)]
+fn restrict_to_owner(path: &Path) -> eyre::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o700))
+        .wrap_err_with(|| format!("Failed to restrict the permissions of {path:?}"))
+}
+
+#[cfg(not(unix))]
+fn restrict_to_owner(_path: &Path) -> eyre::Result<()> {
+    Ok(())
+}
+
+/// Create a directory that did not exist yet, with any missing parents
+///
+/// Returns `false` without changing anything if the directory already exists.
+async fn claim_directory(path: &Path) -> io::Result<bool> {
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+
+    match tokio::fs::create_dir(path).await {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Convenience function to ensure consistent construction of file paths
+fn repository_key_to_file_path(repository_key: &RepositoryKey) -> PathBuf {
+    PathBuf::from(format!(
+        "{}/{}/{}-{}/",
+        repository_key.user_id,
+        ip_addr_to_path_segment(&repository_key.ip_addr),
+        repository_key.get_profile_id(),
+        repository_key.repository_index
+    ))
+}
+
+/// The IP address as a directory name, with the `:`s of IPv6 addresses that Windows rejects replaced
+fn ip_addr_to_path_segment(ip_addr: &IpAddr) -> String {
+    ip_addr.to_string().replace(':', "_")
+}
+
+/// The user, IP address, and profile of a repository index key, with no profile as `None`
+fn parse_repository_index_key(
+    repository_index_key: &str,
+) -> Option<(&str, IpAddr, Option<String>)> {
+    let mut parts = repository_index_key.rsplitn(3, '/');
+    let profile_id = parts.next()?;
+    let ip_addr = parts.next()?.parse().ok()?;
+    let user_id = parts.next()?;
+    let profile_id = Some(profile_id.to_string()).filter(|profile_id| profile_id != NO_PROFILE);
+
+    Some((user_id, ip_addr, profile_id))
+}
+
+/// Convenience function to ensure consistent construction of repository index keys
+fn create_repository_index_key(user_id: &str, ip_addr: &IpAddr, profile_id: &str) -> String {
+    format!("{user_id}/{ip_addr}/{profile_id}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::namespaces::NamespaceSet;
+    use crate::signatures::tests::{TEST_PUBLIC_KEY, TEST_SIGNATURE, TEST_SIGNED_CONTENT};
+    use crate::signatures::SignatureVerifier;
+    use futures::StreamExt;
+    use std::{
+        io::{Cursor, Read},
+        net::Ipv4Addr,
+        sync::Arc,
+    };
+    use zip::read::ZipArchive;
+
+    #[tokio::test]
+    async fn simple_end_to_end_test() -> eyre::Result<()> {
+        let test_file_path = "com/example/file.txt";
+        let test_file_contents = "test_file_content";
+        let local_repository = LocalRepository::new()?;
+
+        // start the repository
+        let repository_key = local_repository
+            .start(
+                "test_user",
+                &IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+                "test_profile",
+            )
+            .await?;
+
+        let file_contents = futures::stream::once(async { Ok(Bytes::from(test_file_contents)) });
+
+        // add a file
+        local_repository
+            .add_file(
+                &repository_key,
+                Path::new(&test_file_path),
+                Box::pin(file_contents),
+            )
+            .await?;
+
+        // finish the repository
+        let zip_file = local_repository.finish(&repository_key).await?;
+        let zip_contents = zip_file.as_buffer()?;
+
+        // verifiy the zip
+        let mut zip_reader = ZipArchive::new(Cursor::new(zip_contents))?;
+        assert_eq!(
+            zip_reader.file_names().collect::<Vec<&str>>(),
+            vec![test_file_path]
+        );
+        let mut actual_file = zip_reader.by_name(test_file_path)?;
+        let mut actual_content = String::new();
+        actual_file.read_to_string(&mut actual_content)?;
+
+        assert_eq!(&actual_content, test_file_contents);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn concurrent_starts_use_separate_directories() -> eyre::Result<()> {
+        let local_repository = Arc::new(LocalRepository::new()?);
+
+        let starts = (0..64).map(|_| {
+            let local_repository = Arc::clone(&local_repository);
+            tokio::spawn(async move {
+                local_repository
+                    .start(
+                        "test_user",
+                        &IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+                        "test_profile",
+                    )
+                    .await
+            })
+        });
+        let repository_keys = futures::future::try_join_all(starts)
+            .await?
+            .into_iter()
+            .collect::<eyre::Result<Vec<_>>>()?;
+
+        let repository_paths = repository_keys
+            .iter()
+            .map(|repository_key| local_repository.absolute_path_for_repository(repository_key))
+            .collect::<eyre::Result<std::collections::HashSet<_>>>()?;
+        assert_eq!(repository_paths.len(), 64);
+        for repository_key in &repository_keys {
+            assert!(matches!(
+                local_repository.get_state(repository_key).await?,
+                RepositoryState::Open
+            ));
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn start_skips_existing_directories() -> eyre::Result<()> {
+        let local_repository = LocalRepository::new()?;
+        let ip_addr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
+
+        // a directory left behind at the next index, as if the persisted indexes were lost
+        let leftover_key =
+            RepositoryKey::new("test_user", &ip_addr, Some("test_profile".to_string()), 0);
+        let leftover_path = local_repository.absolute_path_for_repository(&leftover_key)?;
+        tokio::fs::create_dir_all(&leftover_path).await?;
+
+        let repository_key = local_repository
+            .start("test_user", &ip_addr, "test_profile")
+            .await?;
+
+        assert_ne!(
+            local_repository.absolute_path_for_repository(&repository_key)?,
+            leftover_path
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn export_archive() -> eyre::Result<()> {
+        let local_repository = LocalRepository::new()?;
+        let repository_key = local_repository
+            .start(
+                "test_user",
+                &IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+                "test_profile",
+            )
+            .await?;
+        for file_path in [
+            "com/example/example/0.1.0/example-0.1.0.jar",
+            "com/example/example/0.1.0/example-0.1.0.pom",
+        ] {
+            local_repository
+                .add_file(
+                    &repository_key,
+                    Path::new(file_path),
+                    Box::pin(futures::stream::once(
+                        async move { Ok(Bytes::from(file_path)) },
+                    )),
+                )
+                .await?;
+        }
+
+        let archive = local_repository.export_archive().await?;
+
+        let mut entries = HashMap::new();
+        let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(archive.as_slice()));
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            let path = entry.path()?.to_string_lossy().into_owned();
+            let mut contents = String::new();
+            std::io::Read::read_to_string(&mut entry, &mut contents)?;
+            entries.insert(path, contents);
+        }
+
+        let repository_dir = repository_key_to_file_path(&repository_key);
+        for file_path in [
+            "com/example/example/0.1.0/example-0.1.0.jar",
+            "com/example/example/0.1.0/example-0.1.0.pom",
+        ] {
+            let entry_path = repository_dir.join(REPOSITORY_FOLDER).join(file_path);
+            assert_eq!(
+                entries.get(entry_path.to_string_lossy().as_ref()),
+                Some(&file_path.to_string())
+            );
+        }
+        assert!(entries.contains_key(
+            repository_dir
+                .join(REPOSITORY_STATE_FILE)
+                .to_string_lossy()
+                .as_ref()
+        ));
+        assert!(entries.contains_key(REPOSITORY_INDEXES_FILE));
+        // exporting leaves the repository as it was
+        assert!(matches!(
+            local_repository.get_state(&repository_key).await?,
+            RepositoryState::Open
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reject_directory_traversal() -> eyre::Result<()> {
+        let local_repository = LocalRepository::new()?;
+
+        // start the repository
+        let repository_key = local_repository
+            .start(
+                "test_user",
+                &IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+                "test_profile",
+            )
+            .await?;
+
+        let file_contents = futures::stream::once(async { Ok(Bytes::from("test_file_content")) });
 
         // add a file
         if let Err(e) = local_repository
             .add_file(
                 &repository_key,
-                "../../other_test_user/other_test_repository/com/example/file.txt",
-                file_contents,
+                Path::new("../../other_test_user/other_test_repository/com/example/file.txt"),
+                Box::pin(file_contents),
             )
             .await
         {
@@ -456,6 +1942,1668 @@ mod tests {
             eyre::bail!("Failed to prevent directory traversal");
         }
 
+        Ok(())
+    }
+    #[tokio::test]
+    async fn move_file() -> eyre::Result<()> {
+        let local_repository = LocalRepository::new()?;
+        let repository_key = local_repository
+            .start(
+                "test_user",
+                &IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+                "test_profile",
+            )
+            .await?;
+        let stray_path = Path::new("com/example/example/0.1.0/exmaple-0.1.0.jar");
+        let file_path = Path::new("com/example/example/0.1.0/example-0.1.0.jar");
+        local_repository
+            .add_file(
+                &repository_key,
+                stray_path,
+                Box::pin(futures::stream::once(async {
+                    Ok(Bytes::from("test_file_content"))
+                })),
+            )
+            .await?;
+
+        local_repository
+            .move_file(&repository_key, stray_path, file_path)
+            .await?;
+
+        assert_eq!(
+            local_repository
+                .get_file(&repository_key, stray_path)
+                .await?,
+            None
+        );
+        assert_eq!(
+            local_repository
+                .get_file(&repository_key, file_path)
+                .await?,
+            Some(b"test_file_content".to_vec())
+        );
+        assert!(local_repository
+            .move_file(&repository_key, stray_path, file_path)
+            .await
+            .is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reject_move_file_traversal() -> eyre::Result<()> {
+        let local_repository = LocalRepository::new()?;
+        let repository_key = local_repository
+            .start(
+                "test_user",
+                &IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+                "test_profile",
+            )
+            .await?;
+        let file_path = Path::new("com/example/example/0.1.0/example-0.1.0.jar");
+        local_repository
+            .add_file(
+                &repository_key,
+                file_path,
+                Box::pin(futures::stream::once(async {
+                    Ok(Bytes::from("test_file_content"))
+                })),
+            )
+            .await?;
+
+        assert!(local_repository
+            .move_file(
+                &repository_key,
+                file_path,
+                Path::new("com/example/../../../../../../../example-0.1.0.jar"),
+            )
+            .await
+            .is_err());
+        assert!(local_repository
+            .move_file(
+                &repository_key,
+                Path::new("../../../../../../../example-0.1.0.jar"),
+                file_path,
+            )
+            .await
+            .is_err());
+        assert_eq!(
+            local_repository
+                .get_file(&repository_key, file_path)
+                .await?,
+            Some(b"test_file_content".to_vec())
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn record_and_retrieve_errors() -> eyre::Result<()> {
+        let local_repository = LocalRepository::new()?;
+
+        // start the repository
+        let repository_key = local_repository
+            .start(
+                "test_user",
+                &IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+                "test_profile",
+            )
+            .await?;
+
+        assert!(local_repository
+            .get_errors(&repository_key)
+            .await?
+            .is_empty());
+
+        // record errors, including one spanning multiple lines
+        local_repository
+            .record_errors(
+                &repository_key,
+                vec![
+                    "Missing signature for file: example.jar".to_string(),
+                    "Invalid POM:\nmissing <version>".to_string(),
+                ],
+            )
+            .await?;
+
+        assert_eq!(
+            local_repository.get_errors(&repository_key).await?,
+            vec![
+                "Missing signature for file: example.jar".to_string(),
+                "Invalid POM: missing <version>".to_string(),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reject_missing_signatures() -> eyre::Result<()> {
+        let local_repository = LocalRepository::with_options(RepositoryOptions {
+            require_signatures: true,
+            ..Default::default()
+        })?;
+
+        // start the repository
+        let repository_key = local_repository
+            .start(
+                "test_user",
+                &IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+                "test_profile",
+            )
+            .await?;
+
+        // add an unsigned jar and a signed pom
+        for file_path in [
+            "com/example/example/0.1.0/example-0.1.0.jar",
+            "com/example/example/0.1.0/example-0.1.0.pom",
+            "com/example/example/0.1.0/example-0.1.0.pom.asc",
+        ] {
+            local_repository
+                .add_file(
+                    &repository_key,
+                    Path::new(&file_path),
+                    Box::pin(futures::stream::once(async {
+                        Ok(Bytes::from("test_file_content"))
+                    })),
+                )
+                .await?;
+        }
+
+        let error = local_repository
+            .finish(&repository_key)
+            .await
+            .err()
+            .ok_or_else(|| eyre::eyre!("Finished with a missing signature"))?;
+
+        let error = error.to_string();
+        assert!(
+            error.contains("Missing signature: com/example/example/0.1.0/example-0.1.0.jar.asc")
+        );
+        assert!(!error.contains("example-0.1.0.pom.asc"));
+
+        // the repository is left open, so that the signature can still be uploaded
+        assert!(matches!(
+            local_repository.get_state(&repository_key).await?,
+            RepositoryState::Open
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn verify_signatures() -> eyre::Result<()> {
+        let verifier = SignatureVerifier::from_armored_keyring(TEST_PUBLIC_KEY.as_bytes())?;
+        let local_repository = LocalRepository::with_options(RepositoryOptions {
+            signature_verifier: Some(Arc::new(verifier)),
+            ..Default::default()
+        })?;
+
+        let valid_repository_key = local_repository
+            .start(
+                "test_user",
+                &IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+                "test_profile",
+            )
+            .await?;
+        let invalid_repository_key = local_repository
+            .start(
+                "test_user",
+                &IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+                "test_profile",
+            )
+            .await?;
+
+        for (repository_key, contents) in [
+            (&valid_repository_key, TEST_SIGNED_CONTENT),
+            (&invalid_repository_key, "tampered_file_content"),
+        ] {
+            local_repository
+                .add_file(
+                    repository_key,
+                    Path::new("com/example/example/0.1.0/example-0.1.0.jar"),
+                    Box::pin(futures::stream::once(
+                        async move { Ok(Bytes::from(contents)) },
+                    )),
+                )
+                .await?;
+            local_repository
+                .add_file(
+                    repository_key,
+                    Path::new("com/example/example/0.1.0/example-0.1.0.jar.asc"),
+                    Box::pin(futures::stream::once(async {
+                        Ok(Bytes::from(TEST_SIGNATURE))
+                    })),
+                )
+                .await?;
+        }
+
+        local_repository.finish(&valid_repository_key).await?;
+
+        let error = local_repository
+            .finish(&invalid_repository_key)
+            .await
+            .err()
+            .ok_or_else(|| eyre::eyre!("Finished with an invalid signature"))?;
+        assert!(error
+            .to_string()
+            .contains("Invalid signature: com/example/example/0.1.0/example-0.1.0.jar.asc"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn stale_checksums() -> eyre::Result<()> {
+        let jar_path = "com/example/example/0.1.0/example-0.1.0.jar";
+        let sha1_path = "com/example/example/0.1.0/example-0.1.0.jar.sha1";
+
+        for policy in [
+            ChecksumMismatchPolicy::Regenerate,
+            ChecksumMismatchPolicy::Reject,
+        ] {
+            let local_repository = LocalRepository::with_options(RepositoryOptions {
+                checksum_mismatch: policy,
+                ..Default::default()
+            })?;
+
+            let repository_key = local_repository
+                .start(
+                    "test_user",
+                    &IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+                    "test_profile",
+                )
+                .await?;
+
+            // the sidecar was computed for a previous upload of the jar
+            for (file_path, contents) in [
+                (jar_path, "test_file_content"),
+                (sha1_path, "da39a3ee5e6b4b0d3255bfef95601890afd80709"),
+            ] {
+                local_repository
+                    .add_file(
+                        &repository_key,
+                        Path::new(&file_path),
+                        Box::pin(futures::stream::once(
+                            async move { Ok(Bytes::from(contents)) },
+                        )),
+                    )
+                    .await?;
+            }
+
+            let finished = local_repository.finish(&repository_key).await;
+
+            match policy {
+                ChecksumMismatchPolicy::Regenerate => {
+                    let zip_contents = finished?.as_buffer()?;
+                    let mut zip_reader = ZipArchive::new(Cursor::new(zip_contents))?;
+                    let mut actual_checksum = String::new();
+                    zip_reader
+                        .by_name(sha1_path)?
+                        .read_to_string(&mut actual_checksum)?;
+
+                    assert_eq!(actual_checksum, "67b983e13c778959f437eb8ddc785b82aed2ce30");
+                }
+                _ => {
+                    let error = finished
+                        .err()
+                        .ok_or_else(|| eyre::eyre!("Finished with a stale checksum"))?;
+                    assert!(error
+                        .to_string()
+                        .contains(&format!("Checksum mismatch: {sha1_path}")));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn get_added_file() -> eyre::Result<()> {
+        let local_repository = LocalRepository::new()?;
+
+        let repository_key = local_repository
+            .start(
+                "test_user",
+                &IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+                "test_profile",
+            )
+            .await?;
+        local_repository
+            .add_file(
+                &repository_key,
+                Path::new("com/example/example/0.1.0/example-0.1.0.pom"),
+                Box::pin(futures::stream::once(async {
+                    Ok(Bytes::from("test_file_content"))
+                })),
+            )
+            .await?;
+
+        let contents = local_repository
+            .get_file(
+                &repository_key,
+                Path::new("com/example/example/0.1.0/example-0.1.0.pom"),
+            )
+            .await?;
+        assert_eq!(contents.as_deref(), Some("test_file_content".as_bytes()));
+
+        let missing = local_repository
+            .get_file(
+                &repository_key,
+                Path::new("com/example/example/0.1.0/example-0.1.0.jar"),
+            )
+            .await?;
+        assert_eq!(missing, None);
+
+        let size = local_repository
+            .get_file_size(
+                &repository_key,
+                Path::new("com/example/example/0.1.0/example-0.1.0.pom"),
+            )
+            .await?;
+        assert_eq!(size, Some("test_file_content".len() as u64));
+        let missing_size = local_repository
+            .get_file_size(&repository_key, Path::new("com/example/example/0.1.0"))
+            .await?;
+        assert_eq!(missing_size, None);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn generate_metadata() -> eyre::Result<()> {
+        let local_repository = LocalRepository::with_options(RepositoryOptions {
+            generate_maven_metadata: true,
+            ..Default::default()
+        })?;
+
+        let repository_key = local_repository
+            .start(
+                "test_user",
+                &IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+                "test_profile",
+            )
+            .await?;
+        local_repository
+            .add_file(
+                &repository_key,
+                Path::new("com/example/example/0.1.0/example-0.1.0.jar"),
+                Box::pin(futures::stream::once(async {
+                    Ok(Bytes::from("test_file_content"))
+                })),
+            )
+            .await?;
+
+        let zip_contents = local_repository
+            .finish(&repository_key)
+            .await?
+            .as_buffer()?;
+        let mut zip_reader = ZipArchive::new(Cursor::new(zip_contents))?;
+
+        let mut metadata = String::new();
+        zip_reader
+            .by_name("com/example/example/maven-metadata.xml")?
+            .read_to_string(&mut metadata)?;
+        assert!(metadata.contains("<groupId>com.example</groupId>"));
+        assert!(metadata.contains("<release>0.1.0</release>"));
+
+        let mut metadata_sha1 = String::new();
+        zip_reader
+            .by_name("com/example/example/maven-metadata.xml.sha1")?
+            .read_to_string(&mut metadata_sha1)?;
+        assert_eq!(
+            metadata_sha1,
+            ChecksumAlgorithm::Sha1.digest(metadata.as_bytes())
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn generate_missing_checksums() -> eyre::Result<()> {
+        let local_repository = LocalRepository::with_options(RepositoryOptions {
+            generate_checksums: true,
+            ..Default::default()
+        })?;
+
+        let repository_key = local_repository
+            .start(
+                "test_user",
+                &IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+                "test_profile",
+            )
+            .await?;
+        let files = [
+            (
+                "com/example/example/0.1.0/example-0.1.0.jar",
+                "test_jar_content",
+            ),
+            ("com/example/example/0.1.0/example-0.1.0.pom", "<project />"),
+            // uploaded checksums are kept as they are
+            (
+                "com/example/example/0.1.0/example-0.1.0.pom.sha1",
+                "uploaded",
+            ),
+        ];
+        for (file_path, file_contents) in files {
+            local_repository
+                .add_file(
+                    &repository_key,
+                    Path::new(file_path),
+                    Box::pin(futures::stream::once(async move {
+                        Ok(Bytes::from(file_contents))
+                    })),
+                )
+                .await?;
+        }
+
+        let zip_contents = local_repository
+            .finish(&repository_key)
+            .await?
+            .as_buffer()?;
+        let mut zip_reader = ZipArchive::new(Cursor::new(zip_contents))?;
+
+        for (sidecar_path, expected_contents) in [
+            (
+                "com/example/example/0.1.0/example-0.1.0.jar.md5",
+                ChecksumAlgorithm::Md5.digest(b"test_jar_content"),
+            ),
+            (
+                "com/example/example/0.1.0/example-0.1.0.jar.sha1",
+                ChecksumAlgorithm::Sha1.digest(b"test_jar_content"),
+            ),
+            (
+                "com/example/example/0.1.0/example-0.1.0.pom.md5",
+                ChecksumAlgorithm::Md5.digest(b"<project />"),
+            ),
+            (
+                "com/example/example/0.1.0/example-0.1.0.pom.sha1",
+                "uploaded".to_string(),
+            ),
+        ] {
+            let mut contents = String::new();
+            zip_reader
+                .by_name(sidecar_path)?
+                .read_to_string(&mut contents)?;
+            assert_eq!(contents, expected_contents, "{sidecar_path}");
+        }
+        assert!(zip_reader
+            .by_name("com/example/example/0.1.0/example-0.1.0.pom.sha1.md5")
+            .is_err());
+        assert_eq!(zip_reader.len(), 6);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn concurrent_no_profile_sessions() -> eyre::Result<()> {
+        let local_repository = LocalRepository::new()?;
+        let ip_addr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
+
+        let first_key = local_repository
+            .open_no_profile_repository("test_user", &ip_addr, Some("first"))
+            .await?;
+        let second_key = local_repository
+            .open_no_profile_repository("test_user", &ip_addr, Some("second"))
+            .await?;
+        let default_key = local_repository
+            .open_no_profile_repository("test_user", &ip_addr, None)
+            .await?;
+        assert_ne!(first_key, second_key);
+        assert_ne!(default_key, first_key);
+        assert_ne!(default_key, second_key);
+
+        // later requests in a session land in the same repository
+        let first_key_again = local_repository
+            .open_no_profile_repository("test_user", &ip_addr, Some("first"))
+            .await?;
+        assert_eq!(first_key_again, first_key);
+
+        for (repository_key, file_path) in [
+            (&first_key, "com/example/first/0.1.0/first-0.1.0.jar"),
+            (&second_key, "com/example/second/0.1.0/second-0.1.0.jar"),
+        ] {
+            local_repository
+                .add_file(
+                    repository_key,
+                    Path::new(&file_path),
+                    Box::pin(futures::stream::once(async {
+                        Ok(Bytes::from("test_file_content"))
+                    })),
+                )
+                .await?;
+        }
+
+        for (repository_key, file_path) in [
+            (&first_key, "com/example/first/0.1.0/first-0.1.0.jar"),
+            (&second_key, "com/example/second/0.1.0/second-0.1.0.jar"),
+        ] {
+            let zip_contents = local_repository.finish(repository_key).await?.as_buffer()?;
+            let zip_reader = ZipArchive::new(Cursor::new(zip_contents))?;
+            assert_eq!(zip_reader.file_names().collect::<Vec<_>>(), vec![file_path]);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn closed_and_dropped_repositories_end_sessions() -> eyre::Result<()> {
+        let local_repository = LocalRepository::new()?;
+        let ip_addr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
+
+        let closed_key = local_repository
+            .open_no_profile_repository("test_user", &ip_addr, Some("closed"))
+            .await?;
+        let dropped_key = local_repository
+            .open_no_profile_repository("test_user", &ip_addr, Some("dropped"))
+            .await?;
+        local_repository
+            .add_file(
+                &closed_key,
+                Path::new("com/example/example/0.1.0/example-0.1.0.jar"),
+                Box::pin(futures::stream::once(async {
+                    Ok(Bytes::from("test_file_content"))
+                })),
+            )
+            .await?;
+        local_repository.finish(&closed_key).await?;
+        local_repository.abort(&dropped_key).await?;
+        assert!(local_repository.no_profile_sessions.read().await.is_empty());
+
+        // the next upload in either session starts a new repository, rather than reopening the old one
+        for (session_id, ended_key) in [("closed", &closed_key), ("dropped", &dropped_key)] {
+            assert_eq!(
+                local_repository
+                    .find_no_profile_repository("test_user", &ip_addr, Some(session_id))
+                    .await,
+                None
+            );
+            let next_key = local_repository
+                .open_no_profile_repository("test_user", &ip_addr, Some(session_id))
+                .await?;
+            assert_ne!(&next_key, ended_key);
+        }
+        assert!(matches!(
+            local_repository.get_state(&closed_key).await?,
+            RepositoryState::Closed
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn forget_least_recently_used_sessions() -> eyre::Result<()> {
+        let local_repository = LocalRepository::new()?;
+        let ip_addr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
+
+        let oldest_key = local_repository
+            .open_no_profile_repository("test_user", &ip_addr, Some("oldest"))
+            .await?;
+        {
+            let mut no_profile_sessions = local_repository.no_profile_sessions.write().await;
+            for session in 1..MAX_NO_PROFILE_SESSIONS {
+                no_profile_sessions.insert(
+                    format!("other_user/{session}"),
+                    NoProfileSession {
+                        repository_index: 0,
+                        last_used: Instant::now() + Duration::from_secs(1),
+                    },
+                );
+            }
+        }
+
+        local_repository
+            .open_no_profile_repository("test_user", &ip_addr, Some("newest"))
+            .await?;
+
+        assert_eq!(
+            local_repository.no_profile_sessions.read().await.len(),
+            MAX_NO_PROFILE_SESSIONS
+        );
+        assert_eq!(
+            local_repository
+                .find_no_profile_repository("test_user", &ip_addr, Some("oldest"))
+                .await,
+            None
+        );
+        assert!(local_repository.exists(&oldest_key).await);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn deterministic_bundles() -> eyre::Result<()> {
+        let local_repository = LocalRepository::with_options(RepositoryOptions {
+            deterministic_bundles: true,
+            ..Default::default()
+        })?;
+
+        let mut bundles = Vec::new();
+        // add the files in a different order each time
+        for file_paths in [
+            [
+                "com/example/example/0.1.0/example-0.1.0.jar",
+                "com/example/example/0.1.0/example-0.1.0.pom",
+            ],
+            [
+                "com/example/example/0.1.0/example-0.1.0.pom",
+                "com/example/example/0.1.0/example-0.1.0.jar",
+            ],
+        ] {
+            let repository_key = local_repository
+                .start(
+                    "test_user",
+                    &IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+                    "test_profile",
+                )
+                .await?;
+            for file_path in file_paths {
+                local_repository
+                    .add_file(
+                        &repository_key,
+                        Path::new(&file_path),
+                        Box::pin(futures::stream::once(
+                            async move { Ok(Bytes::from(file_path)) },
+                        )),
+                    )
+                    .await?;
+            }
+
+            let zip_file = local_repository.finish(&repository_key).await?;
+            bundles.push(zip_file.as_buffer()?);
+        }
+
+        assert_eq!(bundles[0], bundles[1]);
+        // the bundles match however far apart they are built, as no entry has the time it was written
+        let mut zip_reader = ZipArchive::new(Cursor::new(&bundles[0]))?;
+        for index in 0..zip_reader.len() {
+            let fixed = zip::DateTime::default();
+            let last_modified = zip_reader.by_index(index)?.last_modified();
+            assert_eq!(
+                (last_modified.datepart(), last_modified.timepart()),
+                (fixed.datepart(), fixed.timepart())
+            );
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reject_denied_extensions() -> eyre::Result<()> {
+        let local_repository = LocalRepository::with_options(RepositoryOptions {
+            denied_extensions: vec!["tmp".to_string()],
+            ..Default::default()
+        })?;
+
+        let repository_key = local_repository
+            .start(
+                "test_user",
+                &IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+                "test_profile",
+            )
+            .await?;
+        for file_path in [
+            "com/example/example/0.1.0/example-0.1.0.jar",
+            "com/example/example/0.1.0/LICENSE",
+        ] {
+            local_repository
+                .add_file(
+                    &repository_key,
+                    Path::new(&file_path),
+                    Box::pin(futures::stream::once(async {
+                        Ok(Bytes::from("test_file_content"))
+                    })),
+                )
+                .await?;
+        }
+
+        let error = local_repository
+            .add_file(
+                &repository_key,
+                Path::new("com/example/example/0.1.0/example-0.1.0.jar.tmp"),
+                Box::pin(futures::stream::once(async {
+                    Ok(Bytes::from("test_file_content"))
+                })),
+            )
+            .await
+            .err()
+            .ok_or_else(|| eyre::eyre!("Added a file with a denied extension"))?;
+        assert!(error
+            .to_string()
+            .contains("does not have an allowed extension"));
+        assert_eq!(
+            local_repository
+                .get_file(
+                    &repository_key,
+                    Path::new("com/example/example/0.1.0/example-0.1.0.jar.tmp")
+                )
+                .await?,
+            None
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn record_activity() -> eyre::Result<()> {
+        let local_repository = LocalRepository::with_options(RepositoryOptions {
+            allow_empty_bundles: true,
+            ..Default::default()
+        })?;
+        let repository_key = local_repository
+            .start(
+                "test_user",
+                &IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+                "test_profile",
+            )
+            .await?;
+
+        local_repository.finish(&repository_key).await?;
+        local_repository.release(&repository_key).await?;
+
+        let activity = local_repository.get_activity(&repository_key).await?;
+        let states = activity
+            .iter()
+            .map(|(state, _)| state.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(states, vec!["open", "closed", "released"]);
+        assert!(activity
+            .windows(2)
+            .all(|transitions| transitions[0].1 <= transitions[1].1));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reject_shallow_paths() -> eyre::Result<()> {
+        let local_repository = LocalRepository::with_options(RepositoryOptions {
+            min_path_depth: 4,
+            ..Default::default()
+        })?;
+        let repository_key = local_repository
+            .start(
+                "test_user",
+                &IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+                "test_profile",
+            )
+            .await?;
+
+        let error = local_repository
+            .add_file(
+                &repository_key,
+                Path::new("example-0.1.0.jar"),
+                Box::pin(futures::stream::once(async {
+                    Ok(Bytes::from("test_file_content"))
+                })),
+            )
+            .await
+            .expect_err("Added a file without Maven coordinates");
+        assert!(error
+            .to_string()
+            .contains("is not in a groupId/artifactId/version directory"));
+
+        local_repository
+            .add_file(
+                &repository_key,
+                Path::new("com/example/example/0.1.0/example-0.1.0.jar"),
+                Box::pin(futures::stream::once(async {
+                    Ok(Bytes::from("test_file_content"))
+                })),
+            )
+            .await?;
+
+        let zip_file = local_repository.finish(&repository_key).await?;
+        let zip_reader = ZipArchive::new(Cursor::new(zip_file.as_buffer()?))?;
+        assert_eq!(
+            zip_reader.file_names().collect::<Vec<_>>(),
+            vec!["com/example/example/0.1.0/example-0.1.0.jar"]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reject_unauthorized_namespaces() -> eyre::Result<()> {
+        let local_repository = LocalRepository::with_options(RepositoryOptions {
+            namespaces: Some(NamespaceSet::new(["com.example"])),
+            ..Default::default()
+        })?;
+        let repository_key = local_repository
+            .start(
+                "test_user",
+                &IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+                "com.example",
+            )
+            .await?;
+
+        let error = local_repository
+            .add_file(
+                &repository_key,
+                Path::new("com/examplefoo/example/0.1.0/example-0.1.0.jar"),
+                Box::pin(futures::stream::once(async {
+                    Ok(Bytes::from("test_file_content"))
+                })),
+            )
+            .await
+            .expect_err("Added a file outside of the authorized namespaces");
+        assert!(error
+            .to_string()
+            .contains("is not in an authorized namespace"));
+
+        local_repository
+            .add_file(
+                &repository_key,
+                Path::new("com/example/sub/example/0.1.0/example-0.1.0.jar"),
+                Box::pin(futures::stream::once(async {
+                    Ok(Bytes::from("test_file_content"))
+                })),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reload_repository_indexes() -> eyre::Result<()> {
+        let root = TempDir::new()?;
+        let ip_addr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
+
+        let local_repository = LocalRepository::with_root(root.path(), Default::default())?;
+        let first_key = local_repository
+            .start("test_user", &ip_addr, "test_profile")
+            .await?;
+        local_repository
+            .add_file(
+                &first_key,
+                Path::new("com/example/example/0.1.0/example-0.1.0.jar"),
+                Box::pin(futures::stream::once(async {
+                    Ok(Bytes::from("test_file_content"))
+                })),
+            )
+            .await?;
+        drop(local_repository);
+
+        // as if the process had restarted
+        let local_repository = LocalRepository::with_root(root.path(), Default::default())?;
+        let second_key = local_repository
+            .start("test_user", &ip_addr, "test_profile")
+            .await?;
+
+        assert_eq!(first_key.repository_index, 0);
+        assert_eq!(second_key.repository_index, 1);
+        assert!(local_repository.exists(&first_key).await);
+        assert!(local_repository
+            .get_file(
+                &first_key,
+                Path::new("com/example/example/0.1.0/example-0.1.0.jar")
+            )
+            .await?
+            .is_some());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reload_repository_indexes_with_any_ids() -> eyre::Result<()> {
+        let root = TempDir::new()?;
+        let ip_addr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
+
+        let local_repository = LocalRepository::with_root(root.path(), Default::default())?;
+        let first_key = local_repository
+            .start("test user\n1 test_user", &ip_addr, "test\nprofile 0")
+            .await?;
+        drop(local_repository);
+
+        let local_repository = LocalRepository::with_root(root.path(), Default::default())?;
+        let second_key = local_repository
+            .start("test user\n1 test_user", &ip_addr, "test\nprofile 0")
+            .await?;
+
+        assert_eq!(first_key.repository_index, 0);
+        assert_eq!(second_key.repository_index, 1);
+        assert!(local_repository.exists(&first_key).await);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn numeric_profile_ids() -> eyre::Result<()> {
+        let root = TempDir::new()?;
+
+        let local_repository = LocalRepository::with_root(root.path(), Default::default())?;
+        let first_id = local_repository.numeric_profile_id("com.example").await?;
+        let second_id = local_repository.numeric_profile_id("org.example").await?;
+
+        assert_ne!(first_id, second_id);
+        assert_eq!(
+            local_repository.numeric_profile_id("com.example").await?,
+            first_id
+        );
+        assert_eq!(
+            local_repository.namespace_for_profile_id(second_id).await?,
+            Some("org.example".to_string())
+        );
+        assert_eq!(local_repository.namespace_for_profile_id(0).await?, None);
+        assert_eq!(local_repository.namespace_for_profile_id(3).await?, None);
+        drop(local_repository);
+
+        // the IDs survive a restart
+        let local_repository = LocalRepository::with_root(root.path(), Default::default())?;
+        assert_eq!(
+            local_repository.namespace_for_profile_id(first_id).await?,
+            Some("com.example".to_string())
+        );
+        assert_eq!(local_repository.numeric_profile_id("net.example").await?, 3);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reload_numeric_profile_ids_with_any_namespaces() -> eyre::Result<()> {
+        let root = TempDir::new()?;
+
+        let local_repository = LocalRepository::with_root(root.path(), Default::default())?;
+        let first_id = local_repository
+            .numeric_profile_id("com.example\norg.example")
+            .await?;
+        let second_id = local_repository.numeric_profile_id("").await?;
+        drop(local_repository);
+
+        let local_repository = LocalRepository::with_root(root.path(), Default::default())?;
+        assert_eq!(
+            local_repository.namespace_for_profile_id(first_id).await?,
+            Some("com.example\norg.example".to_string())
+        );
+        assert_eq!(
+            local_repository.namespace_for_profile_id(second_id).await?,
+            Some(String::new())
+        );
+        assert_eq!(local_repository.numeric_profile_id("org.example").await?, 3);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn never_started_repository() -> eyre::Result<()> {
+        let local_repository = LocalRepository::new()?;
+        let repository_key = RepositoryKey::new(
+            "test_user",
+            &IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+            Some("test_profile".to_string()),
+            0,
+        );
+
+        assert!(!local_repository.exists(&repository_key).await);
+        assert!(local_repository.finish(&repository_key).await.is_err());
+        assert!(local_repository.release(&repository_key).await.is_err());
+
+        local_repository
+            .start(
+                "test_user",
+                &IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+                "test_profile",
+            )
+            .await?;
+        assert!(local_repository.exists(&repository_key).await);
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn private_temp_root() -> eyre::Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir_parent = TempDir::new()?;
+        let local_repository = LocalRepository::with_options(RepositoryOptions {
+            temp_dir_prefix: Some("test-repository".to_string()),
+            temp_dir_parent: Some(temp_dir_parent.path().to_path_buf()),
+            private_root: true,
+            ..Default::default()
+        })?;
+
+        assert_eq!(local_repository.root.parent(), Some(temp_dir_parent.path()));
+        assert!(local_repository
+            .root
+            .file_name()
+            .and_then(|file_name| file_name.to_str())
+            .is_some_and(|file_name| file_name.starts_with("test-repository")));
+        let mode = std::fs::metadata(&local_repository.root)?
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o700);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn uploads_keep_repository_from_idle_removal() -> eyre::Result<()> {
+        let local_repository = LocalRepository::new()?;
+        let ip_addr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
+        let idle_key = local_repository
+            .start("test_user", &ip_addr, "test_profile")
+            .await?;
+        let uploading_key = local_repository
+            .start("test_user", &ip_addr, "test_profile")
+            .await?;
+
+        // uploads keep arriving for longer than the repositories may be idle
+        let max_idle = Duration::from_millis(300);
+        for version in 0..5 {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            local_repository
+                .add_file(
+                    &uploading_key,
+                    Path::new(&format!(
+                        "com/example/example/0.{version}.0/example-0.{version}.0.jar"
+                    )),
+                    Box::pin(futures::stream::once(async {
+                        Ok(Bytes::from("test_file_content"))
+                    })),
+                )
+                .await?;
+        }
+
+        let removed = local_repository.remove_idle_repositories(max_idle).await?;
+
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].get_repository_id(), idle_key.get_repository_id());
+        assert!(!local_repository.exists(&idle_key).await);
+        assert!(local_repository.exists(&uploading_key).await);
+        assert!(local_repository
+            .get_file(
+                &uploading_key,
+                Path::new("com/example/example/0.4.0/example-0.4.0.jar")
+            )
+            .await?
+            .is_some());
+
+        // the index of the removed repository is not handed out again
+        let next_key = local_repository
+            .start("test_user", &ip_addr, "test_profile")
+            .await?;
+        assert_eq!(next_key.repository_index, 2);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn new_deployments_are_not_settled() -> eyre::Result<()> {
+        let local_repository = LocalRepository::new()?;
+        let repository_key = local_repository
+            .start(
+                "test_user",
+                &IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+                "test_profile",
+            )
+            .await?;
+
+        local_repository
+            .record_deployment_id(&repository_key, "first_deployment_id")
+            .await?;
+        assert!(
+            !local_repository
+                .is_deployment_settled(&repository_key)
+                .await?
+        );
+        local_repository
+            .record_deployment_settled(&repository_key)
+            .await?;
+        assert!(
+            local_repository
+                .is_deployment_settled(&repository_key)
+                .await?
+        );
+
+        // a republish is followed until it settles in turn
+        local_repository
+            .record_deployment_id(&repository_key, "second_deployment_id")
+            .await?;
+        assert!(
+            !local_repository
+                .is_deployment_settled(&repository_key)
+                .await?
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn idle_removal_leaves_closed_repositories() -> eyre::Result<()> {
+        let local_repository = LocalRepository::new()?;
+        let ip_addr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
+        let open_key = local_repository
+            .start("test_user", &ip_addr, "test_profile")
+            .await?;
+        let closed_key = local_repository
+            .start("test_user", &ip_addr, "test_profile")
+            .await?;
+        local_repository
+            .add_file(
+                &closed_key,
+                Path::new("com/example/example/0.1.0/example-0.1.0.jar"),
+                Box::pin(futures::stream::once(async {
+                    Ok(Bytes::from("test_file_content"))
+                })),
+            )
+            .await?;
+        local_repository.finish(&closed_key).await?;
+
+        let removed = local_repository
+            .remove_idle_repositories(Duration::ZERO)
+            .await?;
+
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].get_repository_id(), open_key.get_repository_id());
+        assert!(!local_repository.exists(&open_key).await);
+        assert!(local_repository.exists(&closed_key).await);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn ipv6_repository_paths() -> eyre::Result<()> {
+        let local_repository = LocalRepository::new()?;
+        let ip_addr: IpAddr = "2001:db8::1".parse()?;
+        let repository_key = local_repository
+            .start("test_user", &ip_addr, "test_profile")
+            .await?;
+
+        let path = repository_key_to_file_path(&repository_key);
+        assert_eq!(path, PathBuf::from("test_user/2001_db8__1/test_profile-0/"));
+        assert_eq!(repository_key.ip_addr, ip_addr);
+
+        local_repository
+            .add_file(
+                &repository_key,
+                Path::new("com/example/example/0.1.0/example-0.1.0.jar"),
+                Box::pin(futures::stream::once(async {
+                    Ok(Bytes::from("test_file_content"))
+                })),
+            )
+            .await?;
+        assert!(local_repository.root.join(&path).is_dir());
+        assert_eq!(
+            local_repository
+                .get_file(
+                    &repository_key,
+                    Path::new("com/example/example/0.1.0/example-0.1.0.jar")
+                )
+                .await?,
+            Some(b"test_file_content".to_vec())
+        );
+        assert_eq!(
+            local_repository
+                .list_repositories("test_user", &ip_addr)
+                .await?
+                .len(),
+            1
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn file_metadata_of_existing_and_missing_files() -> eyre::Result<()> {
+        let local_repository = LocalRepository::new()?;
+        let repository_key = local_repository
+            .start(
+                "test_user",
+                &IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+                "test_profile",
+            )
+            .await?;
+        let before_upload = OffsetDateTime::now_utc() - Duration::from_secs(1);
+        local_repository
+            .add_file(
+                &repository_key,
+                Path::new("com/example/example/0.1.0/example-0.1.0.jar"),
+                Box::pin(futures::stream::once(async {
+                    Ok(Bytes::from("test_file_content"))
+                })),
+            )
+            .await?;
+
+        let file_metadata = local_repository
+            .file_metadata(
+                &repository_key,
+                Path::new("com/example/example/0.1.0/example-0.1.0.jar"),
+            )
+            .await?
+            .ok_or_else(|| eyre::eyre!("The file has no metadata"))?;
+        assert_eq!(file_metadata.size, 17);
+        assert_eq!(
+            file_metadata.sha1,
+            "67b983e13c778959f437eb8ddc785b82aed2ce30"
+        );
+        assert_eq!(
+            file_metadata.etag(),
+            "\"67b983e13c778959f437eb8ddc785b82aed2ce30\""
+        );
+        assert!(file_metadata.modified > before_upload);
+
+        assert_eq!(
+            local_repository
+                .file_metadata(
+                    &repository_key,
+                    Path::new("com/example/example/0.1.0/example-0.1.0.pom"),
+                )
+                .await?,
+            None
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn limit_files_per_repository() -> eyre::Result<()> {
+        let local_repository = LocalRepository::with_options(RepositoryOptions {
+            max_files_per_repository: Some(2),
+            ..Default::default()
+        })?;
+        let repository_key = local_repository
+            .start(
+                "test_user",
+                &IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+                "test_profile",
+            )
+            .await?;
+        let add_file = |file_path: &'static str| {
+            local_repository.add_file(
+                &repository_key,
+                Path::new(file_path),
+                Box::pin(futures::stream::once(async {
+                    Ok(Bytes::from("test_file_content"))
+                })),
+            )
+        };
+
+        // up to the limit, including replacing a file that is already there
+        add_file("com/example/example/0.1.0/example-0.1.0.jar").await?;
+        add_file("com/example/example/0.1.0/example-0.1.0.pom").await?;
+        add_file("com/example/example/0.1.0/example-0.1.0.pom").await?;
+
+        let error = add_file("com/example/example/0.1.0/example-0.1.0.module")
+            .await
+            .expect_err("Exceeded the file limit");
+        assert_eq!(
+            error.downcast_ref::<FileLimitExceeded>(),
+            Some(&FileLimitExceeded {
+                repository_id: repository_key.get_repository_id(),
+                max_files: 2,
+            })
+        );
+        assert_eq!(
+            local_repository
+                .get_file(
+                    &repository_key,
+                    Path::new("com/example/example/0.1.0/example-0.1.0.module")
+                )
+                .await?,
+            None
+        );
+
+        // the files are counted again once they are removed
+        local_repository.remove_files(&repository_key).await?;
+        local_repository.reopen(&repository_key).await?;
+        add_file("com/example/example/0.1.0/example-0.1.0.module").await?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn drop_unknown_repository() -> eyre::Result<()> {
+        let local_repository = LocalRepository::new()?;
+        let ip_addr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
+        let repository_key = local_repository
+            .start("test_user", &ip_addr, "test_profile")
+            .await?;
+        local_repository
+            .add_file(
+                &repository_key,
+                Path::new("com/example/example/0.1.0/example-0.1.0.jar"),
+                Box::pin(futures::stream::once(async {
+                    Ok(Bytes::from("test_file_content"))
+                })),
+            )
+            .await?;
+        local_repository.finish(&repository_key).await?;
+        local_repository.mark_unknown(&repository_key).await?;
+
+        // not closed again, as Central may already have the bundle
+        let Err(error) = local_repository.finish(&repository_key).await else {
+            eyre::bail!("Finished an unknown repository");
+        };
+        assert!(error.downcast_ref::<RepositoryNotOpen>().is_some());
+
+        local_repository.abort(&repository_key).await?;
+        assert!(matches!(
+            local_repository.get_state(&repository_key).await?,
+            RepositoryState::Dropped
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn abort_open_repository() -> eyre::Result<()> {
+        let local_repository = LocalRepository::new()?;
+        let ip_addr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
+        let repository_key = local_repository
+            .start("test_user", &ip_addr, "test_profile")
+            .await?;
+        local_repository
+            .add_file(
+                &repository_key,
+                Path::new("com/example/example/0.1.0/example-0.1.0.jar"),
+                Box::pin(futures::stream::once(async {
+                    Ok(Bytes::from("test_file_content"))
+                })),
+            )
+            .await?;
+
+        local_repository.abort(&repository_key).await?;
+
+        assert!(matches!(
+            local_repository.get_state(&repository_key).await?,
+            RepositoryState::Dropped
+        ));
+        assert!(!local_repository.exists(&repository_key).await);
+        assert!(local_repository
+            .get_file(
+                &repository_key,
+                Path::new("com/example/example/0.1.0/example-0.1.0.jar")
+            )
+            .await?
+            .is_none());
+
+        let error = local_repository
+            .add_file(
+                &repository_key,
+                Path::new("com/example/example/0.1.0/example-0.1.0.pom"),
+                Box::pin(futures::stream::once(async {
+                    Ok(Bytes::from("<project />"))
+                })),
+            )
+            .await
+            .expect_err("files cannot be added to a dropped repository");
+        assert!(error.downcast_ref::<RepositoryNotFound>().is_some());
+        assert!(local_repository.abort(&repository_key).await.is_err());
+
+        // closed repositories are on their way to Central, so cannot be aborted
+        let closed_key = local_repository
+            .start("test_user", &ip_addr, "test_profile")
+            .await?;
+        local_repository
+            .add_file(
+                &closed_key,
+                Path::new("com/example/example/0.1.0/example-0.1.0.jar"),
+                Box::pin(futures::stream::once(async {
+                    Ok(Bytes::from("test_file_content"))
+                })),
+            )
+            .await?;
+        local_repository.finish(&closed_key).await?;
+        assert!(local_repository.abort(&closed_key).await.is_err());
+        assert!(matches!(
+            local_repository.get_state(&closed_key).await?,
+            RepositoryState::Closed
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reject_finishing_closed_repository() -> eyre::Result<()> {
+        let local_repository = LocalRepository::new()?;
+        let repository_key = local_repository
+            .start(
+                "test_user",
+                &IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+                "test_profile",
+            )
+            .await?;
+        local_repository
+            .add_file(
+                &repository_key,
+                Path::new("com/example/example/0.1.0/example-0.1.0.jar"),
+                Box::pin(futures::stream::once(async {
+                    Ok(Bytes::from("test_file_content"))
+                })),
+            )
+            .await?;
+        local_repository.finish(&repository_key).await?;
+
+        for state in [RepositoryState::Closed, RepositoryState::Released] {
+            if matches!(state, RepositoryState::Released) {
+                local_repository.release(&repository_key).await?;
+            }
+            let Err(error) = local_repository.finish(&repository_key).await else {
+                eyre::bail!("Finished a {state} repository");
+            };
+            assert_eq!(
+                error.downcast_ref::<RepositoryNotOpen>(),
+                Some(&RepositoryNotOpen {
+                    repository_id: repository_key.get_repository_id(),
+                    state: state.to_string(),
+                })
+            );
+            assert_eq!(
+                local_repository
+                    .get_state(&repository_key)
+                    .await?
+                    .to_string(),
+                state.to_string()
+            );
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reject_empty_repository() -> eyre::Result<()> {
+        let local_repository = LocalRepository::new()?;
+        let repository_key = local_repository
+            .start(
+                "test_user",
+                &IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+                "test_profile",
+            )
+            .await?;
+
+        let Err(error) = local_repository.finish(&repository_key).await else {
+            eyre::bail!("Finished an empty repository");
+        };
+        assert!(error.to_string().contains("has no files"));
+        assert!(matches!(
+            local_repository.get_state(&repository_key).await?,
+            RepositoryState::Open
+        ));
+
+        let local_repository = LocalRepository::with_options(RepositoryOptions {
+            allow_empty_bundles: true,
+            ..Default::default()
+        })?;
+        let repository_key = local_repository
+            .start(
+                "test_user",
+                &IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+                "test_profile",
+            )
+            .await?;
+
+        let zip_file = local_repository.finish(&repository_key).await?;
+        assert!(zip_file.entries().is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn enforce_repository_quota() -> eyre::Result<()> {
+        let local_repository = LocalRepository::with_options(RepositoryOptions {
+            max_repository_size: Some(30),
+            ..Default::default()
+        })?;
+
+        let repository_key = local_repository
+            .start(
+                "test_user",
+                &IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+                "test_profile",
+            )
+            .await?;
+
+        // 17 bytes fits within the quota
+        local_repository
+            .add_file(
+                &repository_key,
+                Path::new("com/example/example/0.1.0/example-0.1.0.jar"),
+                Box::pin(futures::stream::once(async {
+                    Ok(Bytes::from("test_file_content"))
+                })),
+            )
+            .await?;
+        assert_eq!(local_repository.repository_size(&repository_key).await?, 17);
+
+        // replacing the file does not count its previous size
+        local_repository
+            .add_file(
+                &repository_key,
+                Path::new("com/example/example/0.1.0/example-0.1.0.jar"),
+                Box::pin(futures::stream::once(async {
+                    Ok(Bytes::from("test_file_content_v2"))
+                })),
+            )
+            .await?;
+        assert_eq!(local_repository.repository_size(&repository_key).await?, 20);
+
+        // another 17 bytes does not
+        let error = local_repository
+            .add_file(
+                &repository_key,
+                Path::new("com/example/example/0.1.0/example-0.1.0.pom"),
+                Box::pin(futures::stream::once(async {
+                    Ok(Bytes::from("test_file_content"))
+                })),
+            )
+            .await
+            .expect_err("Exceeded the quota");
+        assert_eq!(
+            error.downcast_ref::<QuotaExceeded>(),
+            Some(&QuotaExceeded {
+                repository_id: repository_key.get_repository_id(),
+                max_size: 30,
+            })
+        );
+
+        // the partial file is cleaned up
+        assert_eq!(local_repository.repository_size(&repository_key).await?, 20);
+        assert_eq!(
+            local_repository
+                .get_file(
+                    &repository_key,
+                    Path::new("com/example/example/0.1.0/example-0.1.0.pom")
+                )
+                .await?,
+            None
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn concurrent_uploads_share_repository_quota() -> eyre::Result<()> {
+        let local_repository = LocalRepository::with_options(RepositoryOptions {
+            max_repository_size: Some(30),
+            ..Default::default()
+        })?;
+
+        let repository_key = local_repository
+            .start(
+                "test_user",
+                &IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+                "test_profile",
+            )
+            .await?;
+
+        // each upload fits on its own, and they take turns writing their chunks
+        let chunks = || {
+            Box::pin(
+                futures::stream::iter([Bytes::from("0123456789"), Bytes::from("0123456789")]).then(
+                    |chunk| async move {
+                        tokio::task::yield_now().await;
+                        Ok(chunk)
+                    },
+                ),
+            )
+        };
+        let (jar, pom) = tokio::join!(
+            local_repository.add_file(
+                &repository_key,
+                Path::new("com/example/example/0.1.0/example-0.1.0.jar"),
+                chunks(),
+            ),
+            local_repository.add_file(
+                &repository_key,
+                Path::new("com/example/example/0.1.0/example-0.1.0.pom"),
+                chunks(),
+            ),
+        );
+
+        assert!(jar.is_ok() != pom.is_ok());
+        let error = jar.err().or(pom.err()).expect("Exceeded the quota");
+        assert!(error.downcast_ref::<QuotaExceeded>().is_some());
+        assert_eq!(local_repository.repository_size(&repository_key).await?, 20);
+
+        // the bytes of the failed upload are given back
+        local_repository
+            .add_file(
+                &repository_key,
+                Path::new("com/example/example/0.1.0/example-0.1.0.module"),
+                Box::pin(futures::stream::once(async {
+                    Ok(Bytes::from("0123456789"))
+                })),
+            )
+            .await?;
+        assert_eq!(local_repository.repository_size(&repository_key).await?, 30);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reject_incomplete_poms() -> eyre::Result<()> {
+        let local_repository = LocalRepository::with_options(RepositoryOptions {
+            validate_poms: true,
+            ..Default::default()
+        })?;
+
+        let repository_key = local_repository
+            .start(
+                "test_user",
+                &IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+                "test_profile",
+            )
+            .await?;
+        local_repository
+            .add_file(
+                &repository_key,
+                Path::new("com/example/example/0.1.0/example-0.1.0.pom"),
+                Box::pin(futures::stream::once(async {
+                    Ok(Bytes::from(
+                        "<project><groupId>com.example</groupId><artifactId>example</artifactId></project>",
+                    ))
+                })),
+            )
+            .await?;
+
+        let error = local_repository
+            .finish(&repository_key)
+            .await
+            .err()
+            .ok_or_else(|| eyre::eyre!("Finished with an incomplete POM"))?;
+        assert!(error.to_string().contains(
+            "Incomplete POM: com/example/example/0.1.0/example-0.1.0.pom (missing version)"
+        ));
+
         Ok(())
     }
 }
@@ -0,0 +1,181 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+pub const MAVEN_METADATA_FILE: &str = "maven-metadata.xml";
+
+/// Generate a `maven-metadata.xml` for each artifact directory in a repository
+///
+/// Artifact directories are found from the `<group>/<artifact>/<version>/<file>` layout of the
+/// provided files, which should be relative to the root of the repository. `last_updated` should
+/// be the `yyyyMMddHHmmss` UTC timestamp expected by Maven.
+pub fn generate_maven_metadata(files: &[PathBuf], last_updated: &str) -> Vec<(PathBuf, String)> {
+    let mut artifacts: BTreeMap<PathBuf, BTreeSet<String>> = BTreeMap::new();
+    for file in files {
+        let is_metadata = file
+            .file_name()
+            .and_then(|file_name| file_name.to_str())
+            .is_some_and(|file_name| file_name.starts_with(MAVEN_METADATA_FILE));
+        if is_metadata || file.components().count() < 4 {
+            continue;
+        }
+
+        let Some(version_dir) = file.parent() else {
+            continue;
+        };
+        let (Some(artifact_dir), Some(version)) = (version_dir.parent(), version_dir.file_name())
+        else {
+            continue;
+        };
+
+        artifacts
+            .entry(artifact_dir.to_path_buf())
+            .or_default()
+            .insert(version.to_string_lossy().to_string());
+    }
+
+    artifacts
+        .into_iter()
+        .filter_map(|(artifact_dir, versions)| {
+            let metadata = artifact_metadata(&artifact_dir, versions, last_updated)?;
+            Some((artifact_dir.join(MAVEN_METADATA_FILE), metadata))
+        })
+        .collect()
+}
+
+fn artifact_metadata(
+    artifact_dir: &Path,
+    versions: BTreeSet<String>,
+    last_updated: &str,
+) -> Option<String> {
+    let artifact_id = artifact_dir.file_name()?.to_string_lossy();
+    let group_id = artifact_dir
+        .parent()?
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join(".");
+
+    let mut versions = versions.into_iter().collect::<Vec<_>>();
+    versions.sort_by(|a, b| compare_versions(a, b));
+
+    let latest = versions.last()?;
+    let release = versions
+        .iter()
+        .rev()
+        .find(|version| !version.ends_with("-SNAPSHOT"));
+
+    let mut metadata = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<metadata>\n");
+    metadata.push_str(&format!("  <groupId>{}</groupId>\n", escape(&group_id)));
+    metadata.push_str(&format!(
+        "  <artifactId>{}</artifactId>\n",
+        escape(&artifact_id)
+    ));
+    metadata.push_str("  <versioning>\n");
+    metadata.push_str(&format!("    <latest>{}</latest>\n", escape(latest)));
+    if let Some(release) = release {
+        metadata.push_str(&format!("    <release>{}</release>\n", escape(release)));
+    }
+    metadata.push_str("    <versions>\n");
+    for version in &versions {
+        metadata.push_str(&format!("      <version>{}</version>\n", escape(version)));
+    }
+    metadata.push_str("    </versions>\n");
+    metadata.push_str(&format!("    <lastUpdated>{last_updated}</lastUpdated>\n"));
+    metadata.push_str("  </versioning>\n</metadata>\n");
+
+    Some(metadata)
+}
+
+/// Order versions by their `.`/`-` separated segments, comparing numeric segments as numbers
+fn compare_versions(a: &str, b: &str) -> Ordering {
+    let mut a_segments = a.split(['.', '-']);
+    let mut b_segments = b.split(['.', '-']);
+    loop {
+        let ordering = match (a_segments.next(), b_segments.next()) {
+            (None, None) => return Ordering::Equal,
+            // a qualifier makes a version older, 1.0-SNAPSHOT < 1.0
+            (None, Some(b_segment)) if b_segment.parse::<u64>().is_err() => Ordering::Greater,
+            (None, Some(_)) => Ordering::Less,
+            (Some(a_segment), None) if a_segment.parse::<u64>().is_err() => Ordering::Less,
+            (Some(_), None) => Ordering::Greater,
+            (Some(a_segment), Some(b_segment)) => {
+                match (a_segment.parse::<u64>(), b_segment.parse::<u64>()) {
+                    (Ok(a_number), Ok(b_number)) => a_number.cmp(&b_number),
+                    (Ok(_), Err(_)) => Ordering::Greater,
+                    (Err(_), Ok(_)) => Ordering::Less,
+                    (Err(_), Err(_)) => a_segment.cmp(b_segment),
+                }
+            }
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_version_metadata() {
+        let files = [
+            "com/example/example/0.1.0/example-0.1.0.jar",
+            "com/example/example/0.1.0/example-0.1.0.pom",
+            "com/example/example/0.1.0/example-0.1.0.pom.asc",
+        ]
+        .map(PathBuf::from);
+
+        let metadata = generate_maven_metadata(&files, "20240601120000");
+
+        let expected_metadata = r#"<?xml version="1.0" encoding="UTF-8"?>
+<metadata>
+  <groupId>com.example</groupId>
+  <artifactId>example</artifactId>
+  <versioning>
+    <latest>0.1.0</latest>
+    <release>0.1.0</release>
+    <versions>
+      <version>0.1.0</version>
+    </versions>
+    <lastUpdated>20240601120000</lastUpdated>
+  </versioning>
+</metadata>
+"#;
+        assert_eq!(
+            metadata,
+            vec![(
+                PathBuf::from("com/example/example/maven-metadata.xml"),
+                expected_metadata.to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn order_versions() {
+        let files = [
+            "com/example/example/0.10.0/example-0.10.0.jar",
+            "com/example/example/0.9.0/example-0.9.0.jar",
+            "com/example/example/0.11.0-SNAPSHOT/example-0.11.0-SNAPSHOT.jar",
+        ]
+        .map(PathBuf::from);
+
+        let metadata = generate_maven_metadata(&files, "20240601120000");
+
+        let (_, metadata) = &metadata[0];
+        assert!(metadata.contains("<latest>0.11.0-SNAPSHOT</latest>"));
+        assert!(metadata.contains("<release>0.10.0</release>"));
+        assert!(metadata.contains(
+            "<version>0.9.0</version>\n      <version>0.10.0</version>\n      <version>0.11.0-SNAPSHOT</version>"
+        ));
+        assert_eq!(compare_versions("1.0-SNAPSHOT", "1.0"), Ordering::Less);
+    }
+}
@@ -0,0 +1,128 @@
+use std::path::Path;
+
+/// The namespaces that may be published to, each covering its subgroups
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct NamespaceSet {
+    namespaces: Vec<String>,
+}
+
+impl NamespaceSet {
+    /// Collect the namespaces, ignoring surrounding whitespace and empty entries
+    pub fn new<I, S>(namespaces: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let namespaces = namespaces
+            .into_iter()
+            .map(|namespace| namespace.as_ref().trim().to_string())
+            .filter(|namespace| !namespace.is_empty())
+            .collect();
+        Self { namespaces }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.namespaces.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.namespaces.iter().map(String::as_str)
+    }
+
+    /// Whether a namespace covers the group, either exactly or as a dotted prefix
+    ///
+    /// `com.example` covers `com.example` and `com.example.sub`, but not `com.examplefoo`.
+    pub fn authorizes(&self, group_id: &str) -> bool {
+        self.matching_namespace(group_id).is_some()
+    }
+
+    /// The most specific namespace that covers the group
+    pub fn matching_namespace(&self, group_id: &str) -> Option<&str> {
+        self.namespaces
+            .iter()
+            .map(String::as_str)
+            .filter(|namespace| {
+                group_id == *namespace
+                    || group_id
+                        .strip_prefix(namespace)
+                        .is_some_and(|subgroup| subgroup.starts_with('.'))
+            })
+            .max_by_key(|namespace| namespace.len())
+    }
+
+    /// Whether a namespace covers the directories of a file in a Maven repository layout
+    pub fn authorizes_path(&self, file_path: &Path) -> bool {
+        let Some(parent) = file_path.parent() else {
+            return false;
+        };
+        let directories = parent
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>();
+
+        self.authorizes(&directories.join("."))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dotted_prefixes() {
+        let namespaces = NamespaceSet::new(["com.example", "com.example.sub", "org.example"]);
+
+        assert!(namespaces.authorizes("com.example"));
+        assert!(namespaces.authorizes("com.example.foo"));
+        assert!(namespaces.authorizes("org.example.foo.bar"));
+        assert!(!namespaces.authorizes("com.examplefoo"));
+        assert!(!namespaces.authorizes("com.example2"));
+        assert!(!namespaces.authorizes("com"));
+        assert!(!namespaces.authorizes("com."));
+        assert!(!namespaces.authorizes(""));
+        assert!(!namespaces.authorizes("net.example"));
+    }
+
+    #[test]
+    fn most_specific_namespace() {
+        let namespaces = NamespaceSet::new(["com.example", "com.example.sub", "org.example"]);
+
+        assert_eq!(
+            namespaces.matching_namespace("com.example"),
+            Some("com.example")
+        );
+        assert_eq!(
+            namespaces.matching_namespace("com.example.other"),
+            Some("com.example")
+        );
+        assert_eq!(
+            namespaces.matching_namespace("com.example.sub.deep"),
+            Some("com.example.sub")
+        );
+        assert_eq!(
+            namespaces.matching_namespace("com.example.subdeep"),
+            Some("com.example")
+        );
+    }
+
+    #[test]
+    fn ignore_blank_namespaces() {
+        let namespaces = NamespaceSet::new([" com.example ", "", "  "]);
+
+        assert_eq!(namespaces, NamespaceSet::new(["com.example"]));
+        assert!(NamespaceSet::new([""; 0]).is_empty());
+    }
+
+    #[test]
+    fn paths_in_namespaces() {
+        let namespaces = NamespaceSet::new(["com.example"]);
+
+        assert!(
+            namespaces.authorizes_path(Path::new("com/example/example/0.1.0/example-0.1.0.jar"))
+        );
+        assert!(namespaces.authorizes_path(Path::new("com/example/example/maven-metadata.xml")));
+        assert!(!namespaces
+            .authorizes_path(Path::new("com/examplefoo/example/0.1.0/example-0.1.0.jar")));
+        assert!(!namespaces.authorizes_path(Path::new("example-0.1.0.jar")));
+    }
+}
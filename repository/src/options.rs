@@ -0,0 +1,233 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::checksums::{ChecksumAlgorithm, ChecksumMismatchPolicy};
+use crate::metadata::MAVEN_METADATA_FILE;
+use crate::namespaces::NamespaceSet;
+use crate::signatures::{SignatureVerifier, SIGNATURE_EXTENSION};
+
+/// Behaviors that [Repository](crate::traits::Repository) implementations apply to their contents
+///
+/// The defaults accept and bundle whatever files are uploaded.
+#[derive(Clone, Debug, Default)]
+pub struct RepositoryOptions {
+    /// Reject finishing a repository where a `.jar`/`.pom` has no sibling `.asc`
+    pub require_signatures: bool,
+
+    /// Verify every present `.asc` against the keyring before finishing a repository
+    pub signature_verifier: Option<Arc<SignatureVerifier>>,
+
+    /// What to do with checksum sidecars that do not match their file when finishing a repository
+    pub checksum_mismatch: ChecksumMismatchPolicy,
+
+    /// Reject finishing a repository with a malformed POM, or one missing its coordinates
+    pub validate_poms: bool,
+
+    /// Write the `.md5` and `.sha1` sidecars missing for any uploaded file when finishing a repository
+    pub generate_checksums: bool,
+
+    /// Generate a `maven-metadata.xml`, with checksums, for each artifact when finishing a repository
+    pub generate_maven_metadata: bool,
+
+    /// Bundle files sorted by path with fixed timestamps, so that the same contents produce the same bytes
+    pub deterministic_bundles: bool,
+
+    /// Reject files that would grow a repository beyond this many bytes
+    pub max_repository_size: Option<u64>,
+
+    /// Reject new files once a repository holds this many
+    pub max_files_per_repository: Option<usize>,
+
+    /// Only accept files with these lowercase extensions, or any extension if unset
+    pub allowed_extensions: Option<Vec<String>>,
+
+    /// Reject files with these lowercase extensions
+    pub denied_extensions: Vec<String>,
+
+    /// Reject files with fewer path segments than this, such as 4 for `groupId/artifactId/version/file`
+    ///
+    /// `maven-metadata.xml` and its checksums are exempt, as they sit above the version directory.
+    pub min_path_depth: usize,
+
+    /// Only accept files in these namespaces, or in any namespace if unset
+    pub namespaces: Option<NamespaceSet>,
+
+    /// Finish a repository without any files into an empty bundle, instead of rejecting it
+    pub allow_empty_bundles: bool,
+
+    /// Start of the name of a temporary directory that repositories are kept in, `local-repository` if unset
+    pub temp_dir_prefix: Option<String>,
+
+    /// Where to create a temporary directory that repositories are kept in, the system one if unset
+    ///
+    /// The system temporary directory honors `TMPDIR`.
+    pub temp_dir_parent: Option<PathBuf>,
+
+    /// Restrict the directory that repositories are kept in to its owner, `0700` on Unix
+    pub private_root: bool,
+}
+
+impl RepositoryOptions {
+    /// Whether the allowed and denied extensions let the file be added to a repository
+    ///
+    /// Signatures and checksums are matched by the extension of the file they sit beside, so that
+    /// `example-0.1.0.jar.asc.sha1` is allowed along with `.jar`, though either extension denies it.
+    /// `maven-metadata.xml` and its sidecars are always allowed. Files without an extension are only
+    /// accepted when no allowed extensions are set.
+    pub fn allows_file(&self, file_path: &Path) -> bool {
+        let file_name = file_path
+            .file_name()
+            .and_then(|file_name| file_name.to_str())
+            .map(str::to_lowercase)
+            .unwrap_or_default();
+        let base_file_name = strip_sidecar_extensions(&file_name);
+        if base_file_name == MAVEN_METADATA_FILE {
+            return true;
+        }
+
+        let extension = file_extension(&file_name);
+        let base_extension = file_extension(base_file_name);
+        if [extension, base_extension]
+            .iter()
+            .flatten()
+            .any(|extension| {
+                self.denied_extensions
+                    .iter()
+                    .any(|denied| denied == extension)
+            })
+        {
+            return false;
+        }
+
+        match &self.allowed_extensions {
+            Some(allowed_extensions) => base_extension.is_some_and(|extension| {
+                allowed_extensions
+                    .iter()
+                    .any(|allowed| allowed == extension)
+            }),
+            None => true,
+        }
+    }
+
+    /// Whether the file is nested deeply enough to have Maven coordinates
+    pub fn has_min_path_depth(&self, file_path: &Path) -> bool {
+        let is_metadata = file_path
+            .file_name()
+            .and_then(|file_name| file_name.to_str())
+            .is_some_and(|file_name| file_name.starts_with(MAVEN_METADATA_FILE));
+
+        is_metadata || file_path.components().count() >= self.min_path_depth
+    }
+}
+
+/// The text after the last `.` of the file name, so that `.ds_store` has `ds_store`
+fn file_extension(file_name: &str) -> Option<&str> {
+    let (_, extension) = file_name.rsplit_once('.')?;
+    Some(extension).filter(|extension| !extension.is_empty())
+}
+
+/// The name of the file that a lowercase signature or checksum file name belongs to, or the name itself
+fn strip_sidecar_extensions(file_name: &str) -> &str {
+    let file_name = ChecksumAlgorithm::ALL
+        .iter()
+        .find_map(|algorithm| file_name.strip_suffix(&format!(".{}", algorithm.extension())))
+        .unwrap_or(file_name);
+    file_name
+        .strip_suffix(&format!(".{SIGNATURE_EXTENSION}"))
+        .unwrap_or(file_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allowed_and_denied_extensions() {
+        let options = RepositoryOptions {
+            allowed_extensions: Some(vec!["jar".to_string(), "pom".to_string()]),
+            denied_extensions: vec!["tmp".to_string(), "ds_store".to_string()],
+            ..Default::default()
+        };
+
+        assert!(options.allows_file(Path::new("com/example/example-0.1.0.jar")));
+        assert!(options.allows_file(Path::new("com/example/example-0.1.0.JAR")));
+        assert!(!options.allows_file(Path::new("com/example/example-0.1.0.tmp")));
+        assert!(!options.allows_file(Path::new("com/example/.DS_Store")));
+        assert!(!options.allows_file(Path::new("com/example/example-0.1.0.module")));
+        assert!(!options.allows_file(Path::new("com/example/LICENSE")));
+
+        let options = RepositoryOptions {
+            denied_extensions: vec!["tmp".to_string()],
+            ..Default::default()
+        };
+
+        assert!(options.allows_file(Path::new("com/example/example-0.1.0.jar")));
+        assert!(!options.allows_file(Path::new("com/example/example-0.1.0.tmp")));
+        assert!(options.allows_file(Path::new("com/example/LICENSE")));
+
+        assert!(RepositoryOptions::default().allows_file(Path::new("com/example/anything.lock")));
+    }
+
+    #[test]
+    fn sidecars_match_the_extension_of_their_file() {
+        let options = RepositoryOptions {
+            allowed_extensions: Some(vec!["jar".to_string(), "pom".to_string()]),
+            ..Default::default()
+        };
+
+        assert!(options.allows_file(Path::new("com/example/example-0.1.0.jar.asc")));
+        assert!(options.allows_file(Path::new("com/example/example-0.1.0.jar.ASC")));
+        assert!(options.allows_file(Path::new("com/example/example-0.1.0.pom.sha1")));
+        assert!(options.allows_file(Path::new("com/example/example-0.1.0.jar.md5")));
+        assert!(options.allows_file(Path::new("com/example/example-0.1.0.jar.sha256")));
+        assert!(options.allows_file(Path::new("com/example/example-0.1.0.jar.sha512")));
+        assert!(options.allows_file(Path::new("com/example/example-0.1.0.jar.asc.sha1")));
+        assert!(!options.allows_file(Path::new("com/example/example-0.1.0.module.asc")));
+        assert!(!options.allows_file(Path::new("com/example/example-0.1.0.module.sha1")));
+        assert!(!options.allows_file(Path::new("com/example/example.asc")));
+
+        let options = RepositoryOptions {
+            denied_extensions: vec!["tmp".to_string(), "md5".to_string()],
+            ..Default::default()
+        };
+
+        assert!(!options.allows_file(Path::new("com/example/example-0.1.0.tmp.sha1")));
+        assert!(!options.allows_file(Path::new("com/example/example-0.1.0.jar.md5")));
+        assert!(options.allows_file(Path::new("com/example/example-0.1.0.jar.sha1")));
+    }
+
+    #[test]
+    fn maven_metadata_is_always_allowed() {
+        let options = RepositoryOptions {
+            allowed_extensions: Some(vec!["jar".to_string()]),
+            denied_extensions: vec!["xml".to_string(), "sha1".to_string()],
+            ..Default::default()
+        };
+
+        assert!(options.allows_file(Path::new("com/example/example/maven-metadata.xml")));
+        assert!(options.allows_file(Path::new("com/example/example/maven-metadata.xml.sha1")));
+        assert!(options.allows_file(Path::new("com/example/example/maven-metadata.xml.asc")));
+        assert!(!options.allows_file(Path::new("com/example/example/0.1.0/example-0.1.0.xml")));
+    }
+
+    #[test]
+    fn min_path_depth() {
+        let options = RepositoryOptions {
+            min_path_depth: 4,
+            ..Default::default()
+        };
+
+        assert!(
+            options.has_min_path_depth(Path::new("com/example/example/0.1.0/example-0.1.0.jar"))
+        );
+        assert!(options.has_min_path_depth(Path::new(
+            "com/example/example/0.1.0/example-0.1.0.jar.sha1"
+        )));
+        assert!(!options.has_min_path_depth(Path::new("example-0.1.0.jar")));
+        assert!(!options.has_min_path_depth(Path::new("example/0.1.0/example-0.1.0.jar")));
+        assert!(options.has_min_path_depth(Path::new("com/example/example/maven-metadata.xml")));
+        assert!(options.has_min_path_depth(Path::new("com/example/example/maven-metadata.xml.md5")));
+
+        assert!(RepositoryOptions::default().has_min_path_depth(Path::new("example-0.1.0.jar")));
+    }
+}
@@ -0,0 +1,242 @@
+use std::path::{Path, PathBuf};
+
+use xml::reader::{EventReader, XmlEvent};
+
+pub const POM_EXTENSION: &str = "pom";
+
+/// The coordinates declared by a POM, directly and through its parent
+#[derive(Debug, Default, PartialEq)]
+pub struct PomCoordinates {
+    pub group_id: Option<String>,
+    pub artifact_id: Option<String>,
+    pub version: Option<String>,
+    pub parent: Option<ParentCoordinates>,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct ParentCoordinates {
+    pub group_id: Option<String>,
+    pub artifact_id: Option<String>,
+    pub version: Option<String>,
+}
+
+impl ParentCoordinates {
+    /// The path of the parent's POM in a Maven repository layout, if it is fully specified
+    pub fn pom_path(&self) -> Option<PathBuf> {
+        let (group_id, artifact_id, version) = (
+            self.group_id.as_ref()?,
+            self.artifact_id.as_ref()?,
+            self.version.as_ref()?,
+        );
+
+        let mut pom_path = PathBuf::from_iter(group_id.split('.'));
+        pom_path.push(artifact_id);
+        pom_path.push(version);
+        pom_path.push(format!("{artifact_id}-{version}.{POM_EXTENSION}"));
+        Some(pom_path)
+    }
+}
+
+/// Read the coordinates of a POM, failing if it is not well-formed XML with a `<project>` root
+pub fn parse_pom(contents: &[u8]) -> eyre::Result<PomCoordinates> {
+    let mut coordinates = PomCoordinates::default();
+    let mut element_path: Vec<String> = Vec::new();
+    let mut text = String::new();
+
+    for event in EventReader::new(contents) {
+        match event? {
+            XmlEvent::StartElement { name, .. } => {
+                if element_path.is_empty() && name.local_name != "project" {
+                    eyre::bail!(
+                        "expected a <project> root element, found <{}>",
+                        name.local_name
+                    );
+                }
+                if element_path.len() == 1 && name.local_name == "parent" {
+                    coordinates.parent = Some(ParentCoordinates::default());
+                }
+                element_path.push(name.local_name);
+                text.clear();
+            }
+            XmlEvent::Characters(characters) | XmlEvent::CData(characters) => {
+                text.push_str(&characters);
+            }
+            XmlEvent::EndElement { .. } => {
+                let value = Some(text.trim().to_string()).filter(|value| !value.is_empty());
+                let path = element_path.iter().map(String::as_str).collect::<Vec<_>>();
+                match (path.as_slice(), &mut coordinates.parent) {
+                    (["project", "groupId"], _) => coordinates.group_id = value,
+                    (["project", "artifactId"], _) => coordinates.artifact_id = value,
+                    (["project", "version"], _) => coordinates.version = value,
+                    (["project", "parent", "groupId"], Some(parent)) => parent.group_id = value,
+                    (["project", "parent", "artifactId"], Some(parent)) => {
+                        parent.artifact_id = value
+                    }
+                    (["project", "parent", "version"], Some(parent)) => parent.version = value,
+                    _ => {}
+                }
+                element_path.pop();
+                text.clear();
+            }
+            _ => {}
+        }
+    }
+
+    Ok(coordinates)
+}
+
+/// Find the problems with the POMs in a bundle, naming the file and what is wrong with it
+///
+/// `groupId` and `version` may be inherited from a parent, as long as the parent is in the bundle.
+pub fn check_poms(poms: &[(PathBuf, Vec<u8>)]) -> Vec<String> {
+    let pom_paths = poms.iter().map(|(path, _)| path).collect::<Vec<_>>();
+    let parent_in_bundle = |coordinates: &PomCoordinates| {
+        coordinates
+            .parent
+            .as_ref()
+            .and_then(ParentCoordinates::pom_path)
+            .is_some_and(|parent_path| pom_paths.contains(&&parent_path))
+    };
+
+    let mut problems = Vec::new();
+    for (path, contents) in poms {
+        let coordinates = match parse_pom(contents) {
+            Ok(coordinates) => coordinates,
+            Err(e) => {
+                problems.push(format!("Malformed POM: {} ({e})", path.display()));
+                continue;
+            }
+        };
+
+        let mut missing = Vec::new();
+        if coordinates.group_id.is_none() && !parent_in_bundle(&coordinates) {
+            missing.push("groupId");
+        }
+        if coordinates.artifact_id.is_none() {
+            missing.push("artifactId");
+        }
+        if coordinates.version.is_none() && !parent_in_bundle(&coordinates) {
+            missing.push("version");
+        }
+        if !missing.is_empty() {
+            problems.push(format!(
+                "Incomplete POM: {} (missing {})",
+                path.display(),
+                missing.join(", ")
+            ));
+        }
+    }
+
+    problems
+}
+
+/// Whether the path is a POM
+pub fn is_pom(file_path: &Path) -> bool {
+    file_path
+        .extension()
+        .is_some_and(|extension| extension == POM_EXTENSION)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VALID_POM: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<project xmlns="http://maven.apache.org/POM/4.0.0">
+  <modelVersion>4.0.0</modelVersion>
+  <groupId>com.example</groupId>
+  <artifactId>example</artifactId>
+  <version>0.1.0</version>
+  <dependencies>
+    <dependency>
+      <groupId>org.example</groupId>
+      <artifactId>dependency</artifactId>
+      <version>1.0.0</version>
+    </dependency>
+  </dependencies>
+</project>"#;
+
+    #[test]
+    fn valid_pom() -> eyre::Result<()> {
+        assert_eq!(
+            parse_pom(VALID_POM.as_bytes())?,
+            PomCoordinates {
+                group_id: Some("com.example".to_string()),
+                artifact_id: Some("example".to_string()),
+                version: Some("0.1.0".to_string()),
+                parent: None,
+            }
+        );
+
+        let problems = check_poms(&[(
+            PathBuf::from("com/example/example/0.1.0/example-0.1.0.pom"),
+            VALID_POM.as_bytes().to_vec(),
+        )]);
+        assert!(problems.is_empty(), "{problems:?}");
+
+        Ok(())
+    }
+
+    #[test]
+    fn malformed_pom() {
+        let problems = check_poms(&[(
+            PathBuf::from("com/example/example/0.1.0/example-0.1.0.pom"),
+            b"<project><groupId>com.example</project>".to_vec(),
+        )]);
+
+        assert_eq!(problems.len(), 1);
+        assert!(
+            problems[0].starts_with("Malformed POM: com/example/example/0.1.0/example-0.1.0.pom")
+        );
+    }
+
+    #[test]
+    fn pom_missing_version() {
+        let problems = check_poms(&[(
+            PathBuf::from("com/example/example/0.1.0/example-0.1.0.pom"),
+            b"<project><groupId>com.example</groupId><artifactId>example</artifactId></project>"
+                .to_vec(),
+        )]);
+
+        assert_eq!(
+            problems,
+            vec!["Incomplete POM: com/example/example/0.1.0/example-0.1.0.pom (missing version)"]
+        );
+    }
+
+    #[test]
+    fn inherit_from_parent_in_bundle() {
+        let child_pom = br#"<project>
+  <parent>
+    <groupId>com.example</groupId>
+    <artifactId>parent</artifactId>
+    <version>0.1.0</version>
+  </parent>
+  <artifactId>child</artifactId>
+</project>"#;
+        let parent_pom = br#"<project>
+  <groupId>com.example</groupId>
+  <artifactId>parent</artifactId>
+  <version>0.1.0</version>
+</project>"#;
+        let child = (
+            PathBuf::from("com/example/child/0.1.0/child-0.1.0.pom"),
+            child_pom.to_vec(),
+        );
+        let parent = (
+            PathBuf::from("com/example/parent/0.1.0/parent-0.1.0.pom"),
+            parent_pom.to_vec(),
+        );
+
+        assert!(check_poms(&[child, parent]).is_empty());
+
+        let child = (
+            PathBuf::from("com/example/child/0.1.0/child-0.1.0.pom"),
+            child_pom.to_vec(),
+        );
+        assert_eq!(
+            check_poms(&[child]),
+            vec!["Incomplete POM: com/example/child/0.1.0/child-0.1.0.pom (missing groupId, version)"]
+        );
+    }
+}
@@ -0,0 +1,177 @@
+use std::path::{Path, PathBuf};
+
+use eyre::WrapErr;
+use pgp::{types::PublicKeyTrait, Deserializable, SignedPublicKey, StandaloneSignature};
+
+/// Extensions of the files that must be accompanied by a detached signature
+pub const SIGNED_EXTENSIONS: &[&str] = &["jar", "pom"];
+
+/// Extension of an ASCII-armored detached signature
+pub const SIGNATURE_EXTENSION: &str = "asc";
+
+/// Whether the file is an artifact that Central requires a signature for
+pub fn requires_signature(file_path: &Path) -> bool {
+    file_path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| SIGNED_EXTENSIONS.contains(&extension))
+}
+
+/// The path of the detached signature that is expected next to the file
+pub fn signature_path(file_path: &Path) -> PathBuf {
+    let mut signature_path = file_path.as_os_str().to_owned();
+    signature_path.push(".");
+    signature_path.push(SIGNATURE_EXTENSION);
+    PathBuf::from(signature_path)
+}
+
+/// Verifies detached signatures against a keyring of trusted public keys
+pub struct SignatureVerifier {
+    keys: Vec<SignedPublicKey>,
+}
+
+impl SignatureVerifier {
+    /// Load every public key from an ASCII-armored keyring file
+    pub fn from_keyring_file(keyring_path: impl AsRef<Path>) -> eyre::Result<Self> {
+        let keyring_path = keyring_path.as_ref();
+        let keyring = std::fs::read(keyring_path)
+            .wrap_err_with(|| format!("Failed to read keyring: {keyring_path:?}"))?;
+
+        Self::from_armored_keyring(&keyring)
+            .wrap_err_with(|| format!("Failed to load keyring: {keyring_path:?}"))
+    }
+
+    /// Load every public key from ASCII-armored keyring contents
+    pub fn from_armored_keyring(keyring: &[u8]) -> eyre::Result<Self> {
+        let (keys, _headers) = SignedPublicKey::from_armor_many(keyring)?;
+        let keys = keys.collect::<Result<Vec<_>, _>>()?;
+
+        for key in &keys {
+            key.verify()
+                .wrap_err_with(|| format!("Invalid public key: {:?}", key.key_id()))?;
+        }
+
+        if keys.is_empty() {
+            eyre::bail!("No public keys found in the keyring");
+        }
+
+        Ok(Self { keys })
+    }
+
+    /// Check that the armored detached signature was made over the content by a key in the keyring
+    pub fn verify(&self, content: &[u8], armored_signature: &[u8]) -> eyre::Result<()> {
+        let (signature, _headers) = StandaloneSignature::from_armor_single(armored_signature)
+            .wrap_err("Failed to parse the signature")?;
+
+        let verified = self.keys.iter().any(|key| {
+            signature.verify(key, content).is_ok()
+                || key
+                    .public_subkeys
+                    .iter()
+                    .any(|subkey| signature.verify(subkey, content).is_ok())
+        });
+
+        if verified {
+            Ok(())
+        } else {
+            eyre::bail!("Signature was not made over the content by a trusted key")
+        }
+    }
+}
+
+impl std::fmt::Debug for SignatureVerifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SignatureVerifier")
+            .field(
+                "keys",
+                &self.keys.iter().map(|key| key.key_id()).collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub(crate) const TEST_PUBLIC_KEY: &str = "-----BEGIN PGP PUBLIC KEY BLOCK-----
+
+mDMEatCqHBYJKwYBBAHaRw8BAQdAMOhiB9QcDAKFLd4YRLgDI5YuyRW9ALYYCcg/
+S1Id3YG0HlRlc3QgU2lnbmVyIDx0ZXN0QGV4YW1wbGUuY29tPoiQBBMWCAA4FiEE
+dryNLer5VdGDeWhaH62gQGXC5zAFAmrQqhwCGwMFCwkIBwIGFQoJCAsCBBYCAwEC
+HgECF4AACgkQH62gQGXC5zDo+gEA4tYrE17182I6U3OmtN7QgkExzmRaAAh4K7hF
+Z2U8QoMA/0rsiOeTyDr1B9vFFGRvOmOQT+mdPz/9urPPjtpWLJYA
+=v/Xo
+-----END PGP PUBLIC KEY BLOCK-----
+";
+
+    pub(crate) const OTHER_PUBLIC_KEY: &str = "-----BEGIN PGP PUBLIC KEY BLOCK-----
+
+mDMEatCqHBYJKwYBBAHaRw8BAQdAU9hDiRV3RUqapF1tExXz77IZBmhOgKTdzmRr
+8zCS0rK0IE90aGVyIFNpZ25lciA8b3RoZXJAZXhhbXBsZS5jb20+iJAEExYIADgW
+IQR68F0cGd3TBiaUjXHoqUBMKNgl9gUCatCqHAIbAwULCQgHAgYVCgkICwIEFgID
+AQIeAQIXgAAKCRDoqUBMKNgl9tw+AP4p0x49DG07r9RKDF6MYVU5fjJLRia42aXk
+7AHzffCV8gD/W0k4nE8upUWiMl+mHzHyD+XlPkbzLlyyIzpjresm3wg=
+=36p7
+-----END PGP PUBLIC KEY BLOCK-----
+";
+
+    /// The content that [TEST_SIGNATURE] was made over with the [TEST_PUBLIC_KEY]
+    pub(crate) const TEST_SIGNED_CONTENT: &str = "test_file_content";
+
+    pub(crate) const TEST_SIGNATURE: &str = "-----BEGIN PGP SIGNATURE-----
+
+iHUEABYIAB0WIQR2vI0t6vlV0YN5aFofraBAZcLnMAUCatCqHAAKCRAfraBAZcLn
+MPbTAQCG7+43YeeVXyVOiA3cP3lwlOgU5+HDRI3s33t6MZAxTAD/XRu54/vAxGS/
+mjoLWWdKPhATYazK/c6TxFhA1TdNqQo=
+=4995
+-----END PGP SIGNATURE-----
+";
+
+    #[test]
+    fn signature_paths() {
+        assert!(requires_signature(Path::new(
+            "com/example/example/0.1.0/example-0.1.0.jar"
+        )));
+        assert!(!requires_signature(Path::new(
+            "com/example/example/0.1.0/example-0.1.0.jar.sha1"
+        )));
+        assert_eq!(
+            signature_path(Path::new("com/example/example/0.1.0/example-0.1.0.pom")),
+            PathBuf::from("com/example/example/0.1.0/example-0.1.0.pom.asc")
+        );
+    }
+
+    #[test]
+    fn verify_valid_signature() -> eyre::Result<()> {
+        let verifier = SignatureVerifier::from_armored_keyring(TEST_PUBLIC_KEY.as_bytes())?;
+
+        verifier.verify(TEST_SIGNED_CONTENT.as_bytes(), TEST_SIGNATURE.as_bytes())?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn reject_tampered_content() -> eyre::Result<()> {
+        let verifier = SignatureVerifier::from_armored_keyring(TEST_PUBLIC_KEY.as_bytes())?;
+
+        let error = verifier
+            .verify(b"tampered_file_content", TEST_SIGNATURE.as_bytes())
+            .expect_err("Verified tampered content");
+        assert!(error.to_string().contains("by a trusted key"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn reject_untrusted_key() -> eyre::Result<()> {
+        let verifier = SignatureVerifier::from_armored_keyring(OTHER_PUBLIC_KEY.as_bytes())?;
+
+        let error = verifier
+            .verify(TEST_SIGNED_CONTENT.as_bytes(), TEST_SIGNATURE.as_bytes())
+            .expect_err("Verified a signature from an untrusted key");
+        assert!(error.to_string().contains("by a trusted key"));
+
+        Ok(())
+    }
+}
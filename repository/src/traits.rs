@@ -1,24 +1,31 @@
 use async_trait::async_trait;
 use bytes::Bytes;
 use eyre::WrapErr;
-use futures::Stream;
+use futures::stream::BoxStream;
 use std::{
     fmt::{Debug, Display},
     io::{Cursor, Write},
     net::IpAddr,
-    path::Path,
+    path::{Path, PathBuf},
+    pin::Pin,
+    time::Duration,
 };
-use tokio::{fs::File, io::AsyncReadExt};
-use zip::{write::SimpleFileOptions, ZipWriter};
+use time::OffsetDateTime;
+use tokio::{
+    fs::File,
+    io::{AsyncRead, AsyncReadExt},
+};
+use zip::{write::SimpleFileOptions, DateTime, ZipWriter};
 
 /// A constant for deployments that do not provide a profile
 pub const NO_PROFILE: &str = "no-profile";
 
 /// A trait to define the actions of NXRM2 staging repositories
 ///
-/// Defined abstractly in order to allow multiple implementations that can be swapped out.
+/// Defined abstractly in order to allow multiple implementations that can be swapped out, including
+/// at runtime as a `dyn Repository`.
 #[async_trait]
-pub trait Repository {
+pub trait Repository: Send + Sync {
     /// Open a new repository for the provided user and profile
     ///
     /// The profile_id value should be the namespace that was requested for publishing.
@@ -32,31 +39,211 @@ pub trait Repository {
     /// Opens a new repository without a provided profile
     ///
     /// This mimics the behavior of the general file upload of NXRM2.
-    /// Repositories are differentiated soley based on user ID and IP address.
+    /// Repositories are differentiated based on user ID and IP address, and an optional upload
+    /// session that allows concurrent uploads from the same client to be kept apart. Each new
+    /// session is given a fresh repository.
     async fn open_no_profile_repository(
         &self,
         user_id: &str,
         ip_addr: &IpAddr,
+        session_id: Option<&str>,
     ) -> eyre::Result<RepositoryKey>;
 
-    async fn add_file<P, S>(
+    /// The repository that [Repository::open_no_profile_repository] would open, without opening it
+    ///
+    /// `None` if the session has no repository yet, so that reading from it creates nothing.
+    async fn find_no_profile_repository(
+        &self,
+        user_id: &str,
+        ip_addr: &IpAddr,
+        session_id: Option<&str>,
+    ) -> Option<RepositoryKey>;
+
+    async fn add_file(
+        &self,
+        repository_key: &RepositoryKey,
+        file_path: &Path,
+        file_contents: BoxStream<'_, eyre::Result<Bytes>>,
+    ) -> eyre::Result<()>;
+
+    /// Move a file that was added to the repository to another path in it, replacing any file there
+    ///
+    /// Lets a file uploaded to the wrong path be relocated before the repository is finished.
+    async fn move_file(
+        &self,
+        repository_key: &RepositoryKey,
+        from: &Path,
+        to: &Path,
+    ) -> eyre::Result<()>;
+
+    /// Retrieve a file that was added to the repository, if it is still present
+    async fn get_file(
         &self,
         repository_key: &RepositoryKey,
-        file_path: P,
-        file_contents: S,
-    ) -> eyre::Result<()>
-    where
-        P: AsRef<Path> + Debug + Send,
-        S: Stream<Item = eyre::Result<Bytes>> + Send;
+        file_path: &Path,
+    ) -> eyre::Result<Option<Vec<u8>>>;
 
+    /// Open a file that was added to the repository to be read a piece at a time, if it is still present
+    async fn open_file(
+        &self,
+        repository_key: &RepositoryKey,
+        file_path: &Path,
+    ) -> eyre::Result<Option<StoredFile>>;
+
+    /// The size, modification time, and SHA-1 of a file that was added to the repository, if it is still present
+    ///
+    /// The SHA-1 is computed from the contents on each call.
+    async fn file_metadata(
+        &self,
+        repository_key: &RepositoryKey,
+        file_path: &Path,
+    ) -> eyre::Result<Option<FileMetadata>>;
+
+    /// The size of a file that was added to the repository, if it is still present
+    async fn get_file_size(
+        &self,
+        repository_key: &RepositoryKey,
+        file_path: &Path,
+    ) -> eyre::Result<Option<u64>>;
+
+    /// The total size of the files in the repository
+    async fn repository_size(&self, repository_key: &RepositoryKey) -> eyre::Result<u64>;
+
+    /// Close the repository and bundle its files
+    ///
+    /// Fails with [RepositoryNotOpen] unless the repository is open, leaving it unchanged.
+    /// The files are kept, so that the repository can be reopened if the bundle is not accepted,
+    /// until they are removed with [Repository::remove_files].
     async fn finish(&self, repository_key: &RepositoryKey) -> eyre::Result<ZipFile>;
 
+    /// Remove the files of a finished repository, once its bundle is no longer needed
+    async fn remove_files(&self, repository_key: &RepositoryKey) -> eyre::Result<()>;
+
     async fn release(&self, repository_key: &RepositoryKey) -> eyre::Result<()>;
 
+    /// Remove the repository along with everything recorded about it, such as once it is released
+    ///
+    /// Its index is not handed out again.
+    async fn drop_repository(&self, repository_key: &RepositoryKey) -> eyre::Result<()>;
+
+    /// Discard an open repository along with its files, leaving it `Dropped` instead of closing it
+    ///
+    /// A repository in the `Unknown` state can be discarded too, once its deployment has been
+    /// looked for at Central. Files can no longer be added to the repository.
+    async fn abort(&self, repository_key: &RepositoryKey) -> eyre::Result<()>;
+
+    /// Return a closed repository to `Open`, so that it can be finished again
+    ///
+    /// Used to roll back a repository whose bundle was not accepted by Central.
+    async fn reopen(&self, repository_key: &RepositoryKey) -> eyre::Result<()>;
+
+    /// Leave a closed repository `Unknown`, as its bundle was sent to Central without a reply
+    ///
+    /// Central may have received the bundle, so the repository is not reopened to be published again.
+    async fn mark_unknown(&self, repository_key: &RepositoryKey) -> eyre::Result<()>;
+
     async fn get_state(&self, repository_key: &RepositoryKey) -> eyre::Result<RepositoryState>;
+
+    /// The states the repository has moved through, oldest first, with when each was entered
+    async fn get_activity(
+        &self,
+        repository_key: &RepositoryKey,
+    ) -> eyre::Result<Vec<(RepositoryState, OffsetDateTime)>>;
+
+    /// Note activity on the repository, such as an upload, so that it is not considered idle
+    async fn touch(&self, repository_key: &RepositoryKey) -> eyre::Result<()>;
+
+    /// When the repository last saw an upload or changed state, if ever
+    async fn last_activity(
+        &self,
+        repository_key: &RepositoryKey,
+    ) -> eyre::Result<Option<OffsetDateTime>>;
+
+    /// Remove the open repositories without any activity for longer than `max_idle`, returning them
+    ///
+    /// Their indexes stay allocated, so that the repository IDs are not handed out again.
+    async fn remove_idle_repositories(
+        &self,
+        max_idle: Duration,
+    ) -> eyre::Result<Vec<RepositoryKey>>;
+
+    /// Whether the repository has been started or opened, and not since dropped
+    async fn exists(&self, repository_key: &RepositoryKey) -> bool;
+
+    /// List the repositories opened by the user from the IP address, ordered by profile and index
+    async fn list_repositories(
+        &self,
+        user_id: &str,
+        ip_addr: &IpAddr,
+    ) -> eyre::Result<Vec<(RepositoryKey, RepositoryState)>>;
+
+    /// The numeric ID of the namespace's staging profile, allocated the first time the namespace is seen
+    ///
+    /// For clients that require numeric profile IDs. The same namespace always has the same ID.
+    async fn numeric_profile_id(&self, namespace: &str) -> eyre::Result<u64>;
+
+    /// The namespace that a numeric profile ID was allocated for, if any
+    async fn namespace_for_profile_id(&self, profile_id: u64) -> eyre::Result<Option<String>>;
+
+    /// Record the errors that caused a publish of the repository to fail
+    ///
+    /// Replaces any previously recorded errors, so that only the latest failure is reported.
+    async fn record_errors(
+        &self,
+        repository_key: &RepositoryKey,
+        errors: Vec<String>,
+    ) -> eyre::Result<()>;
+
+    /// Retrieve the errors recorded by the most recent failed publish, if any
+    async fn get_errors(&self, repository_key: &RepositoryKey) -> eyre::Result<Vec<String>>;
+
+    /// Record the id of the Central deployment the repository was uploaded as
+    async fn record_deployment_id(
+        &self,
+        repository_key: &RepositoryKey,
+        deployment_id: &str,
+    ) -> eyre::Result<()>;
+
+    /// Retrieve the id of the Central deployment the repository was uploaded as, if any
+    async fn get_deployment_id(
+        &self,
+        repository_key: &RepositoryKey,
+    ) -> eyre::Result<Option<String>>;
+
+    /// Record that the Central deployment of the repository has settled, so that it need not be checked again
+    ///
+    /// Forgotten when another deployment is recorded.
+    async fn record_deployment_settled(&self, repository_key: &RepositoryKey) -> eyre::Result<()>;
+
+    /// Whether the Central deployment of the repository has been recorded as settled
+    async fn is_deployment_settled(&self, repository_key: &RepositoryKey) -> eyre::Result<bool>;
 }
 
-#[derive(Debug, PartialEq)]
+/// A file in a repository, opened without reading its contents into memory
+pub struct StoredFile {
+    pub reader: Pin<Box<dyn AsyncRead + Send>>,
+    /// The size in bytes of the whole file
+    pub size: u64,
+}
+
+/// What a file in a repository is, without its contents
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileMetadata {
+    /// The size in bytes of the whole file
+    pub size: u64,
+    pub modified: OffsetDateTime,
+    /// The lowercase hex SHA-1 of the contents
+    pub sha1: String,
+}
+
+impl FileMetadata {
+    /// A strong `ETag` for the contents, which only changes when they do
+    pub fn etag(&self) -> String {
+        format!("\"{}\"", self.sha1)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct RepositoryKey {
     pub user_id: String,
     pub ip_addr: IpAddr,
@@ -90,18 +277,35 @@ impl RepositoryKey {
         user_id: &str,
         ip_addr: &IpAddr,
         repository_id: &str,
-    ) -> eyre::Result<Self> {
-        if let Some((profile_id, repository_index)) = repository_id.rsplit_once('-') {
-            let repository_index: u32 = repository_index.parse()?;
-            Ok(Self::new(
-                user_id,
-                ip_addr,
-                Some(profile_id.to_string()),
-                repository_index,
-            ))
-        } else {
-            Err(eyre::eyre!("Invalid repository_id: {repository_id}"))
+    ) -> Result<Self, RepositoryIdError> {
+        if repository_id.contains(['/', '\\', '\0']) {
+            return Err(RepositoryIdError::InvalidCharacters(
+                repository_id.to_string(),
+            ));
+        }
+
+        let (profile_id, repository_index) = repository_id
+            .rsplit_once('-')
+            .ok_or_else(|| RepositoryIdError::MissingSeparator(repository_id.to_string()))?;
+
+        if profile_id.is_empty() {
+            return Err(RepositoryIdError::EmptyProfile(repository_id.to_string()));
+        }
+
+        // `u32::from_str` accepts a leading `+`, so require plain digits
+        if repository_index.is_empty() || !repository_index.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(RepositoryIdError::InvalidIndex(repository_id.to_string()));
         }
+        let repository_index: u32 = repository_index
+            .parse()
+            .map_err(|_| RepositoryIdError::InvalidIndex(repository_id.to_string()))?;
+
+        Ok(Self::new(
+            user_id,
+            ip_addr,
+            Some(profile_id.to_string()),
+            repository_index,
+        ))
     }
 
     pub fn get_profile_id(&self) -> String {
@@ -124,10 +328,123 @@ impl Display for RepositoryKey {
     }
 }
 
+/// The ways that a client-provided repository ID can be malformed
+#[derive(Debug, PartialEq)]
+pub enum RepositoryIdError {
+    /// The ID is not of the form `<profile>-<index>`
+    MissingSeparator(String),
+    /// The ID has no profile before the final `-`
+    EmptyProfile(String),
+    /// The ID's index is not a non-negative integer
+    InvalidIndex(String),
+    /// The ID contains path separators or other characters unsafe for storage
+    InvalidCharacters(String),
+}
+
+impl Display for RepositoryIdError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RepositoryIdError::MissingSeparator(repository_id) => write!(
+                f,
+                "Invalid repository_id {repository_id:?}: expected <profile>-<index>"
+            ),
+            RepositoryIdError::EmptyProfile(repository_id) => write!(
+                f,
+                "Invalid repository_id {repository_id:?}: the profile must not be empty"
+            ),
+            RepositoryIdError::InvalidIndex(repository_id) => write!(
+                f,
+                "Invalid repository_id {repository_id:?}: the index must be a non-negative integer"
+            ),
+            RepositoryIdError::InvalidCharacters(repository_id) => write!(
+                f,
+                "Invalid repository_id {repository_id:?}: path separators are not allowed"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RepositoryIdError {}
+
+/// A file that would grow a repository beyond its allowed size
+#[derive(Debug, PartialEq)]
+pub struct QuotaExceeded {
+    pub repository_id: String,
+    pub max_size: u64,
+}
+
+impl Display for QuotaExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Repository {} would exceed its maximum size of {} bytes",
+            self.repository_id, self.max_size
+        )
+    }
+}
+
+impl std::error::Error for QuotaExceeded {}
+
+/// A new file that would take a repository beyond its allowed number of files
+#[derive(Debug, PartialEq)]
+pub struct FileLimitExceeded {
+    pub repository_id: String,
+    pub max_files: usize,
+}
+
+impl Display for FileLimitExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Repository {} would exceed its maximum of {} files",
+            self.repository_id, self.max_files
+        )
+    }
+}
+
+impl std::error::Error for FileLimitExceeded {}
+
+/// A repository that has never been started or opened
+#[derive(Debug, PartialEq)]
+pub struct RepositoryNotFound {
+    pub repository_id: String,
+}
+
+impl Display for RepositoryNotFound {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Repository {} does not exist", self.repository_id)
+    }
+}
+
+impl std::error::Error for RepositoryNotFound {}
+
+/// A repository that can no longer be closed, such as one that was already published
+#[derive(Debug, PartialEq)]
+pub struct RepositoryNotOpen {
+    pub repository_id: String,
+    pub state: String,
+}
+
+impl Display for RepositoryNotOpen {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Repository {} is {}, only open repositories can be closed",
+            self.repository_id, self.state
+        )
+    }
+}
+
+impl std::error::Error for RepositoryNotOpen {}
+
 pub enum RepositoryState {
     Open,
     Closed,
     Released,
+    /// Discarded while open, without being closed
+    Dropped,
+    /// Sent to Central without a reply, so a deployment may or may not have been created
+    Unknown,
     NotFound,
 }
 
@@ -137,6 +454,8 @@ impl Display for RepositoryState {
             RepositoryState::Open => "open",
             RepositoryState::Closed => "closed",
             RepositoryState::Released => "released",
+            RepositoryState::Dropped => "dropped",
+            RepositoryState::Unknown => "unknown",
             RepositoryState::NotFound => "not_found",
         };
         write!(f, "{state_display}")
@@ -151,21 +470,82 @@ impl TryFrom<&str> for RepositoryState {
             "open" => Ok(RepositoryState::Open),
             "closed" => Ok(RepositoryState::Closed),
             "released" => Ok(RepositoryState::Released),
+            "dropped" => Ok(RepositoryState::Dropped),
+            "unknown" => Ok(RepositoryState::Unknown),
             "not_found" => Ok(RepositoryState::NotFound),
             other => Err(format!("Could not convert {other} into a RepositoryState")),
         }
     }
 }
 
+/// Validate a client-provided artifact path before it is handed to a [Repository]
+///
+/// Rejects absolute paths (including Windows drive prefixes) and any `..` segments, so that no
+/// implementation can be tricked into writing outside of the repository. Empty and `.` segments
+/// are dropped from the returned relative path.
+pub fn sanitize_artifact_path(file_path: &str) -> eyre::Result<PathBuf> {
+    let bytes = file_path.as_bytes();
+    let has_drive_prefix = bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':';
+    if has_drive_prefix || file_path.starts_with(['/', '\\']) {
+        eyre::bail!("Invalid path to upload, absolute paths are not allowed: {file_path}");
+    }
+
+    let mut sanitized_path = PathBuf::new();
+    for segment in file_path.split(['/', '\\']) {
+        match segment {
+            "" | "." => continue,
+            ".." => eyre::bail!(
+                "Invalid path to upload, directory traversal is not allowed: {file_path}"
+            ),
+            segment => sanitized_path.push(segment),
+        }
+    }
+
+    if sanitized_path.as_os_str().is_empty() {
+        eyre::bail!("Invalid path to upload, no file provided: {file_path}");
+    }
+
+    Ok(sanitized_path)
+}
+
 /// Convenience wrapper for the API
 pub struct ZipFile {
     writer: ZipWriter<Cursor<Vec<u8>>>,
+    entries: Vec<ZipEntry>,
+    file_options: SimpleFileOptions,
+}
+
+/// A file that has been added to a [ZipFile]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ZipEntry {
+    pub path: String,
+    /// The uncompressed size of the file
+    pub size: u64,
 }
 
 impl ZipFile {
     pub fn in_memory() -> Self {
         let writer = ZipWriter::new(Cursor::new(Vec::new()));
-        Self { writer }
+        Self {
+            writer,
+            entries: Vec::new(),
+            file_options: SimpleFileOptions::default(),
+        }
+    }
+
+    /// A zip file whose entries are stamped with a fixed modification time instead of the current time
+    ///
+    /// Adding the same files in the same order produces identical bytes.
+    pub fn deterministic() -> Self {
+        Self {
+            file_options: SimpleFileOptions::default().last_modified_time(DateTime::default()),
+            ..Self::in_memory()
+        }
+    }
+
+    /// The files added so far, in the order they were added
+    pub fn entries(&self) -> &[ZipEntry] {
+        &self.entries
     }
 
     pub async fn add_file(
@@ -176,7 +556,7 @@ impl ZipFile {
         let relative_path = relative_path.as_ref().display().to_string();
         tracing::trace!("Adding file to .zip: {relative_path}");
         self.writer
-            .start_file(relative_path, SimpleFileOptions::default())?;
+            .start_file(relative_path.as_str(), self.file_options)?;
         let mut contents = Vec::new();
         file.read_to_end(&mut contents)
             .await
@@ -185,6 +565,11 @@ impl ZipFile {
             .write_all(&contents)
             .wrap_err("Failed to add file contents to .zip")?;
 
+        self.entries.push(ZipEntry {
+            path: relative_path,
+            size: contents.len() as u64,
+        });
+
         Ok(())
     }
 
@@ -218,4 +603,117 @@ mod tests {
         assert_eq!(actual_repository_key, expected_repository_key);
         Ok(())
     }
+
+    #[test]
+    fn reject_repository_id_without_index() {
+        let actual_error = RepositoryKey::from_user_context_and_repository_id(
+            "user",
+            &IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+            "profile-",
+        )
+        .expect_err("Parsed an ID without an index");
+
+        assert_eq!(
+            actual_error,
+            RepositoryIdError::InvalidIndex("profile-".to_string())
+        );
+    }
+
+    #[test]
+    fn reject_repository_id_without_profile() {
+        let actual_error = RepositoryKey::from_user_context_and_repository_id(
+            "user",
+            &IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+            "-1",
+        )
+        .expect_err("Parsed an ID without a profile");
+
+        assert_eq!(
+            actual_error,
+            RepositoryIdError::EmptyProfile("-1".to_string())
+        );
+    }
+
+    #[test]
+    fn reject_repository_id_without_separator() {
+        let actual_error = RepositoryKey::from_user_context_and_repository_id(
+            "user",
+            &IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+            "foo",
+        )
+        .expect_err("Parsed an ID without a separator");
+
+        assert_eq!(
+            actual_error,
+            RepositoryIdError::MissingSeparator("foo".to_string())
+        );
+    }
+
+    #[test]
+    fn reject_repository_id_with_non_numeric_index() {
+        let actual_error = RepositoryKey::from_user_context_and_repository_id(
+            "user",
+            &IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+            "profile-+1",
+        )
+        .expect_err("Parsed an ID with a signed index");
+
+        assert_eq!(
+            actual_error,
+            RepositoryIdError::InvalidIndex("profile-+1".to_string())
+        );
+    }
+
+    #[test]
+    fn reject_repository_id_with_traversal() {
+        let actual_error = RepositoryKey::from_user_context_and_repository_id(
+            "user",
+            &IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+            "../../other_user/profile-1",
+        )
+        .expect_err("Parsed an ID with path separators");
+
+        assert_eq!(
+            actual_error,
+            RepositoryIdError::InvalidCharacters("../../other_user/profile-1".to_string())
+        );
+    }
+
+    #[test]
+    fn sanitize_nested_artifact_path() -> eyre::Result<()> {
+        let actual_path = sanitize_artifact_path("com/example/example/0.1.0/./example-0.1.0.jar")?;
+
+        assert_eq!(
+            actual_path,
+            PathBuf::from("com/example/example/0.1.0/example-0.1.0.jar")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn sanitize_rejects_directory_traversal() {
+        let actual_error = sanitize_artifact_path("com/example/../../../other_user/file.txt")
+            .expect_err("Allowed directory traversal");
+
+        assert!(actual_error
+            .to_string()
+            .contains("directory traversal is not allowed"));
+    }
+
+    #[test]
+    fn sanitize_rejects_absolute_paths() {
+        for file_path in [
+            "/etc/passwd",
+            "\\\\server\\share",
+            "C:\\Windows\\win.ini",
+            "c:/file",
+        ] {
+            let actual_error =
+                sanitize_artifact_path(file_path).expect_err("Allowed an absolute path");
+
+            assert!(actual_error
+                .to_string()
+                .contains("absolute paths are not allowed"));
+        }
+    }
 }